@@ -0,0 +1,463 @@
+//! Structural comparison of archives, to explain why two archives that should
+//! be identical differ.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{self, Error, ErrorKind};
+
+use object::read::archive::ArchiveFile;
+
+use crate::archive_inspection::get_bytes;
+use crate::{inspect_archive, ArchiveLayout, MemberLayout, SpecialMemberKind};
+
+/// A field of a member header, or the padding after the member.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum MemberField {
+    Mtime,
+    Uid,
+    Gid,
+    Perms,
+    Size,
+    Padding,
+}
+
+impl fmt::Display for MemberField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MemberField::Mtime => "mtime",
+            MemberField::Uid => "uid",
+            MemberField::Gid => "gid",
+            MemberField::Perms => "perms",
+            MemberField::Size => "size",
+            MemberField::Padding => "padding",
+        })
+    }
+}
+
+/// A difference between a left and a right archive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ArchiveDifference {
+    Magic {
+        left: String,
+        right: String,
+    },
+    SpecialMemberRemoved {
+        kind: SpecialMemberKind,
+    },
+    SpecialMemberAdded {
+        kind: SpecialMemberKind,
+    },
+    MemberRemoved {
+        name: String,
+        left_index: usize,
+    },
+    MemberAdded {
+        name: String,
+        right_index: usize,
+    },
+    /// A member that is in both archives, but in a different position
+    /// relative to the other members that are in both archives.
+    MemberMoved {
+        name: String,
+        left_index: usize,
+        right_index: usize,
+    },
+    MemberField {
+        name: String,
+        field: MemberField,
+        left: u64,
+        right: u64,
+    },
+    MemberData {
+        name: String,
+    },
+    /// A symbol table entry that maps `symbol` to the member `member_name`.
+    SymbolRemoved {
+        symbol: Vec<u8>,
+        member_name: String,
+    },
+    SymbolAdded {
+        symbol: Vec<u8>,
+        member_name: String,
+    },
+    /// The symbol tables have the same entries in a different order.
+    SymbolOrder,
+    /// The member names in the string tables differ, or are laid out
+    /// differently.
+    StringTable {
+        left: Vec<String>,
+        right: Vec<String>,
+    },
+}
+
+impl fmt::Display for ArchiveDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveDifference::Magic { left, right } => {
+                write!(f, "magic differs: {left:?} != {right:?}")
+            }
+            ArchiveDifference::SpecialMemberRemoved { kind } => {
+                write!(f, "special member {kind:?} is only in the left archive")
+            }
+            ArchiveDifference::SpecialMemberAdded { kind } => {
+                write!(f, "special member {kind:?} is only in the right archive")
+            }
+            ArchiveDifference::MemberRemoved { name, left_index } => write!(
+                f,
+                "member {name} at index {left_index} is only in the left archive"
+            ),
+            ArchiveDifference::MemberAdded { name, right_index } => write!(
+                f,
+                "member {name} at index {right_index} is only in the right archive"
+            ),
+            ArchiveDifference::MemberMoved {
+                name,
+                left_index,
+                right_index,
+            } => write!(
+                f,
+                "member {name} moved from index {left_index} to index {right_index}"
+            ),
+            ArchiveDifference::MemberField {
+                name,
+                field,
+                left,
+                right,
+            } => write!(f, "member {name} {field} differs: {left} != {right}"),
+            ArchiveDifference::MemberData { name } => write!(f, "member {name} data differs"),
+            ArchiveDifference::SymbolRemoved {
+                symbol,
+                member_name,
+            } => write!(
+                f,
+                "symbol {} in member {member_name} is only in the left archive",
+                String::from_utf8_lossy(symbol)
+            ),
+            ArchiveDifference::SymbolAdded {
+                symbol,
+                member_name,
+            } => write!(
+                f,
+                "symbol {} in member {member_name} is only in the right archive",
+                String::from_utf8_lossy(symbol)
+            ),
+            ArchiveDifference::SymbolOrder => f.write_str("symbol table order differs"),
+            ArchiveDifference::StringTable { left, right } => {
+                write!(f, "string table differs: {left:?} != {right:?}")
+            }
+        }
+    }
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Returns the (symbol, member name) entries of the symbol tables.
+fn get_symbols(archive: &[u8], layout: &ArchiveLayout) -> io::Result<Vec<(Vec<u8>, String)>> {
+    let member_names = layout
+        .members
+        .iter()
+        .map(|member| (member.header_offset, member.name.as_str()))
+        .collect::<HashMap<_, _>>();
+    let get_member_name = |offset: u64| {
+        member_names
+            .get(&offset)
+            .map(|name| name.to_string())
+            .ok_or_else(|| invalid_data("symbol table refers to an invalid member offset"))
+    };
+
+    let mut symbols = Vec::new();
+    let aix_symbol_tables = layout.special_members.iter().filter(|member| {
+        matches!(
+            member.kind,
+            SpecialMemberKind::AixGlobalSymbolTable | SpecialMemberKind::AixGlobalSymbolTable64
+        )
+    });
+    let mut is_aix = false;
+    for symbol_table in aix_symbol_tables {
+        // The global symbol tables of big archives hold the number of symbols,
+        // the offsets of their members, and the null-terminated names.
+        is_aix = true;
+        let data = get_bytes(archive, symbol_table.data_offset, symbol_table.size)?;
+        let read_u64 = |offset: usize| {
+            data.get(offset..offset + 8)
+                .map(|bytes| u64::from_be_bytes(bytes.try_into().unwrap()))
+                .ok_or_else(|| invalid_data("truncated big archive symbol table"))
+        };
+        let count = usize::try_from(read_u64(0)?)
+            .map_err(|_| invalid_data("truncated big archive symbol table"))?;
+        let names_offset = count
+            .checked_add(1)
+            .and_then(|entries| entries.checked_mul(8))
+            .ok_or_else(|| invalid_data("truncated big archive symbol table"))?;
+        let mut names = data
+            .get(names_offset..)
+            .ok_or_else(|| invalid_data("truncated big archive symbol table"))?
+            .split(|&c| c == 0);
+        for i in 0..count {
+            let name = names
+                .next()
+                .ok_or_else(|| invalid_data("truncated big archive symbol table"))?;
+            symbols.push((name.to_vec(), get_member_name(read_u64((i + 1) * 8)?)?));
+        }
+    }
+    if is_aix {
+        return Ok(symbols);
+    }
+
+    let file = ArchiveFile::parse(archive).map_err(|err| invalid_data(&err.to_string()))?;
+    if let Some(file_symbols) = file
+        .symbols()
+        .map_err(|err| invalid_data(&err.to_string()))?
+    {
+        for symbol in file_symbols {
+            let symbol = symbol.map_err(|err| invalid_data(&err.to_string()))?;
+            symbols.push((symbol.name().to_vec(), get_member_name(symbol.offset().0)?));
+        }
+    }
+    Ok(symbols)
+}
+
+/// Returns the names in the string table, or `None` if there is no string
+/// table.
+fn get_string_table(archive: &[u8], layout: &ArchiveLayout) -> io::Result<Option<Vec<String>>> {
+    let Some(string_table) = layout
+        .special_members
+        .iter()
+        .find(|member| member.kind == SpecialMemberKind::StringTable)
+    else {
+        return Ok(None);
+    };
+    let data = get_bytes(archive, string_table.data_offset, string_table.size)?;
+    Ok(Some(
+        data.split(|&c| c == b'\n' || c == 0)
+            .filter(|name| !name.is_empty())
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .collect(),
+    ))
+}
+
+/// Returns the number of bytes between the end of each member and the next
+/// header, or the end of the archive.
+fn get_paddings(archive: &[u8], layout: &ArchiveLayout) -> Vec<Option<u64>> {
+    let mut header_offsets = layout
+        .special_members
+        .iter()
+        .map(|member| member.header_offset)
+        .chain(layout.members.iter().map(|member| member.header_offset))
+        .collect::<Vec<_>>();
+    header_offsets.sort_unstable();
+    layout
+        .members
+        .iter()
+        .map(|member| {
+            let end = member.data_offset? + member.size;
+            let next = header_offsets
+                .iter()
+                .copied()
+                .find(|&offset| offset >= end)
+                .unwrap_or(archive.len() as u64);
+            Some(next - end)
+        })
+        .collect()
+}
+
+/// Returns the (name, occurrence) keys that identify members in both archives,
+/// so that members with the same name are matched in order.
+fn get_member_keys(members: &[MemberLayout]) -> Vec<(&str, usize)> {
+    let mut counts = HashMap::new();
+    members
+        .iter()
+        .map(|member| {
+            let count = counts.entry(member.name.as_str()).or_insert(0);
+            *count += 1;
+            (member.name.as_str(), *count - 1)
+        })
+        .collect()
+}
+
+/// Returns the indices of a longest strictly increasing subsequence of
+/// `values`.
+fn get_longest_increasing_subsequence(values: &[usize]) -> HashSet<usize> {
+    // `tails[n]` is the index of the smallest value that ends an increasing
+    // subsequence of length `n + 1`.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut predecessors = vec![None; values.len()];
+    for (i, &value) in values.iter().enumerate() {
+        let n = tails.partition_point(|&tail| values[tail] < value);
+        predecessors[i] = n.checked_sub(1).map(|n| tails[n]);
+        if n == tails.len() {
+            tails.push(i);
+        } else {
+            tails[n] = i;
+        }
+    }
+    let mut subsequence = HashSet::new();
+    let mut next = tails.last().copied();
+    while let Some(i) = next {
+        subsequence.insert(i);
+        next = predecessors[i];
+    }
+    subsequence
+}
+
+/// Compares the structure of the `left` and `right` archives, and returns
+/// their differences: the magic, special members, added, removed and moved
+/// members, member header fields and data, symbol table entries and the names
+/// in the string table.
+///
+/// The Arm64EC symbol table of COFF archives isn't compared.
+pub fn diff_archives(left: &[u8], right: &[u8]) -> io::Result<Vec<ArchiveDifference>> {
+    let left_layout = inspect_archive(left)?;
+    let right_layout = inspect_archive(right)?;
+    let mut differences = Vec::new();
+
+    if left_layout.magic != right_layout.magic {
+        differences.push(ArchiveDifference::Magic {
+            left: left_layout.magic.clone(),
+            right: right_layout.magic.clone(),
+        });
+    }
+
+    let special_kinds = |layout: &ArchiveLayout| {
+        layout
+            .special_members
+            .iter()
+            .map(|member| member.kind)
+            .collect::<Vec<_>>()
+    };
+    let left_special_kinds = special_kinds(&left_layout);
+    let right_special_kinds = special_kinds(&right_layout);
+    for kind in &left_special_kinds {
+        if !right_special_kinds.contains(kind) {
+            differences.push(ArchiveDifference::SpecialMemberRemoved { kind: *kind });
+        }
+    }
+    for kind in &right_special_kinds {
+        if !left_special_kinds.contains(kind) {
+            differences.push(ArchiveDifference::SpecialMemberAdded { kind: *kind });
+        }
+    }
+
+    // Match the members by name, and compare the members in both archives.
+    let left_keys = get_member_keys(&left_layout.members);
+    let right_keys = get_member_keys(&right_layout.members);
+    let right_indices = right_keys
+        .iter()
+        .enumerate()
+        .map(|(index, key)| (*key, index))
+        .collect::<HashMap<_, _>>();
+    let left_paddings = get_paddings(left, &left_layout);
+    let right_paddings = get_paddings(right, &right_layout);
+    let mut common = Vec::new();
+    for (left_index, key) in left_keys.iter().enumerate() {
+        let name = key.0.to_string();
+        let Some(&right_index) = right_indices.get(key) else {
+            differences.push(ArchiveDifference::MemberRemoved { name, left_index });
+            continue;
+        };
+        common.push((left_index, right_index));
+
+        let left_member = &left_layout.members[left_index];
+        let right_member = &right_layout.members[right_index];
+        for (field, left_value, right_value) in [
+            (MemberField::Mtime, left_member.mtime, right_member.mtime),
+            (MemberField::Uid, left_member.uid, right_member.uid),
+            (MemberField::Gid, left_member.gid, right_member.gid),
+            (MemberField::Perms, left_member.perms, right_member.perms),
+            (MemberField::Size, left_member.size, right_member.size),
+            (
+                MemberField::Padding,
+                left_paddings[left_index].unwrap_or(0),
+                right_paddings[right_index].unwrap_or(0),
+            ),
+        ] {
+            if left_value != right_value {
+                differences.push(ArchiveDifference::MemberField {
+                    name: name.clone(),
+                    field,
+                    left: left_value,
+                    right: right_value,
+                });
+            }
+        }
+
+        if let (Some(left_offset), Some(right_offset)) =
+            (left_member.data_offset, right_member.data_offset)
+        {
+            if get_bytes(left, left_offset, left_member.size)?
+                != get_bytes(right, right_offset, right_member.size)?
+            {
+                differences.push(ArchiveDifference::MemberData { name });
+            }
+        }
+    }
+    let left_key_set = left_keys.iter().collect::<HashSet<_>>();
+    for (right_index, key) in right_keys.iter().enumerate() {
+        if !left_key_set.contains(key) {
+            differences.push(ArchiveDifference::MemberAdded {
+                name: key.0.to_string(),
+                right_index,
+            });
+        }
+    }
+
+    // Members that are in both archives are moved if they aren't part of the
+    // longest run of members that keep their relative order.
+    let in_order = get_longest_increasing_subsequence(
+        &common
+            .iter()
+            .map(|&(_, right_index)| right_index)
+            .collect::<Vec<_>>(),
+    );
+    for (i, &(left_index, right_index)) in common.iter().enumerate() {
+        if !in_order.contains(&i) {
+            differences.push(ArchiveDifference::MemberMoved {
+                name: left_keys[left_index].0.to_string(),
+                left_index,
+                right_index,
+            });
+        }
+    }
+
+    let left_symbols = get_symbols(left, &left_layout)?;
+    let right_symbols = get_symbols(right, &right_layout)?;
+    let left_symbol_set = left_symbols.iter().collect::<HashSet<_>>();
+    let right_symbol_set = right_symbols.iter().collect::<HashSet<_>>();
+    for symbol in &left_symbols {
+        if !right_symbol_set.contains(symbol) {
+            differences.push(ArchiveDifference::SymbolRemoved {
+                symbol: symbol.0.clone(),
+                member_name: symbol.1.clone(),
+            });
+        }
+    }
+    let mut symbols_added = false;
+    for symbol in &right_symbols {
+        if !left_symbol_set.contains(symbol) {
+            symbols_added = true;
+            differences.push(ArchiveDifference::SymbolAdded {
+                symbol: symbol.0.clone(),
+                member_name: symbol.1.clone(),
+            });
+        }
+    }
+    if !symbols_added && left_symbols.len() == right_symbols.len() && left_symbols != right_symbols
+    {
+        differences.push(ArchiveDifference::SymbolOrder);
+    }
+
+    let left_string_table = get_string_table(left, &left_layout)?.unwrap_or_default();
+    let right_string_table = get_string_table(right, &right_layout)?.unwrap_or_default();
+    if left_string_table != right_string_table {
+        differences.push(ArchiveDifference::StringTable {
+            left: left_string_table,
+            right: right_string_table,
+        });
+    }
+
+    Ok(differences)
+}
@@ -0,0 +1,315 @@
+//! Inspection of the layout of archives, for debugging the output of
+//! [crate::write_archive_to_stream].
+
+use std::collections::HashSet;
+use std::io::{self, Error, ErrorKind};
+use std::mem::size_of;
+
+use object::archive;
+
+use crate::alignment::align_to;
+use crate::archive::big_archive;
+use crate::archive_writer::BIG_AR_MEM_HDR_SIZE;
+
+const FIX_LEN_HDR_SIZE: usize = size_of::<big_archive::FixLenHdr>();
+
+/// The kind of a member that describes the archive rather than holding a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum SpecialMemberKind {
+    /// The GNU symbol table or first COFF linker member, named `/`.
+    SymbolTable,
+    /// The GNU 64-bit symbol table, named `/SYM64/`.
+    SymbolTable64,
+    /// The table of long member names, named `//`.
+    StringTable,
+    /// The second COFF linker member, which is also named `/`.
+    CoffLinkerMember,
+    /// The COFF symbol table for Arm64EC and x64 objects, named
+    /// `/<ECSYMBOLS>/`.
+    EcSymbolTable,
+    /// The BSD symbol table, named `__.SYMDEF` or `__.SYMDEF SORTED`.
+    BsdSymbolTable,
+    /// The BSD 64-bit symbol table, named `__.SYMDEF_64` or
+    /// `__.SYMDEF_64 SORTED`.
+    BsdSymbolTable64,
+    /// The AIX big archive member table.
+    AixMemberTable,
+    /// The AIX big archive global symbol table for 32-bit objects.
+    AixGlobalSymbolTable,
+    /// The AIX big archive global symbol table for 64-bit objects.
+    AixGlobalSymbolTable64,
+}
+
+/// The location of a special member in an archive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SpecialMember {
+    pub kind: SpecialMemberKind,
+    pub name: String,
+    pub header_offset: u64,
+    pub data_offset: u64,
+    pub size: u64,
+}
+
+/// The location and metadata of a member that holds a file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MemberLayout {
+    pub name: String,
+    pub header_offset: u64,
+    /// The offset of the file data, or `None` for the members of thin
+    /// archives, which aren't stored in the archive.
+    pub data_offset: Option<u64>,
+    pub size: u64,
+    pub mtime: u64,
+    pub uid: u64,
+    pub gid: u64,
+    pub perms: u64,
+}
+
+/// The structure of an archive, as returned by [inspect_archive].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ArchiveLayout {
+    /// The magic string at the start of the archive, such as `!<arch>\n`.
+    pub magic: String,
+    /// The special members, in file order for the common archive format and
+    /// in the order of the fixed-length header for AIX big archives.
+    pub special_members: Vec<SpecialMember>,
+    /// The members that hold files, in archive order.
+    pub members: Vec<MemberLayout>,
+}
+
+fn invalid_data(message: impl Into<String>) -> Error {
+    Error::new(ErrorKind::InvalidData, message.into())
+}
+
+fn parse_field(field: &[u8], radix: u32) -> io::Result<u64> {
+    let digits = std::str::from_utf8(field)
+        .map_err(|_| invalid_data("invalid number in archive member header"))?
+        .trim_end_matches([' ', '\0']);
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(digits, radix)
+        .map_err(|_| invalid_data("invalid number in archive member header"))
+}
+
+pub(crate) fn get_bytes(archive: &[u8], offset: u64, size: u64) -> io::Result<&[u8]> {
+    usize::try_from(offset)
+        .ok()
+        .zip(usize::try_from(size).ok())
+        .and_then(|(offset, size)| archive.get(offset..offset.checked_add(size)?))
+        .ok_or_else(|| invalid_data("truncated archive"))
+}
+
+fn get_special_member_kind(
+    name: &str,
+    special_members: &[SpecialMember],
+) -> Option<SpecialMemberKind> {
+    Some(match name {
+        "/" if special_members
+            .last()
+            .is_some_and(|member| member.kind == SpecialMemberKind::SymbolTable) =>
+        {
+            SpecialMemberKind::CoffLinkerMember
+        }
+        "/" => SpecialMemberKind::SymbolTable,
+        "/SYM64/" => SpecialMemberKind::SymbolTable64,
+        "//" => SpecialMemberKind::StringTable,
+        "/<ECSYMBOLS>/" => SpecialMemberKind::EcSymbolTable,
+        "__.SYMDEF" | "__.SYMDEF SORTED" => SpecialMemberKind::BsdSymbolTable,
+        "__.SYMDEF_64" | "__.SYMDEF_64 SORTED" => SpecialMemberKind::BsdSymbolTable64,
+        _ => return None,
+    })
+}
+
+/// Returns the name of a member from the string table, which is terminated by
+/// `/\n` in GNU archives and by a null character in COFF archives.
+fn get_long_name(string_table: &[u8], offset: &str) -> io::Result<String> {
+    let name = offset
+        .parse::<usize>()
+        .ok()
+        .and_then(|offset| string_table.get(offset..))
+        .ok_or_else(|| invalid_data(format!("invalid long member name offset /{offset}")))?;
+    let end = name
+        .iter()
+        .position(|&c| c == b'\n' || c == 0)
+        .unwrap_or(name.len());
+    let name = &name[..end];
+    Ok(String::from_utf8_lossy(name.strip_suffix(b"/").unwrap_or(name)).into_owned())
+}
+
+fn inspect_common_archive(archive: &[u8], thin: bool) -> io::Result<ArchiveLayout> {
+    let mut layout = ArchiveLayout {
+        magic: String::from_utf8_lossy(&archive[..archive::MAGIC.len()]).into_owned(),
+        special_members: Vec::new(),
+        members: Vec::new(),
+    };
+    let mut string_table: &[u8] = &[];
+    let mut offset = archive::MAGIC.len() as u64;
+    while offset < archive.len() as u64 {
+        let header_offset = offset;
+        let header = get_bytes(archive, offset, size_of::<archive::Header>() as u64)?;
+        if header[58..] != archive::TERMINATOR {
+            return Err(invalid_data(format!(
+                "invalid archive member header at offset {offset}"
+            )));
+        }
+        let raw_name = String::from_utf8_lossy(&header[..16]);
+        let raw_name = raw_name.trim_end_matches(' ');
+        let mut size = parse_field(&header[48..58], 10)?;
+        let mut data_offset = offset + header.len() as u64;
+
+        // Resolve BSD names that follow the header and GNU long names.
+        let name = if let Some(name_len) = raw_name.strip_prefix("#1/") {
+            let name_len = parse_field(name_len.as_bytes(), 10)?;
+            let name = get_bytes(archive, data_offset, name_len)?;
+            let end = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+            data_offset += name_len;
+            size = size
+                .checked_sub(name_len)
+                .ok_or_else(|| invalid_data("archive member name is larger than the member"))?;
+            String::from_utf8_lossy(&name[..end]).into_owned()
+        } else if let Some(string_offset) = raw_name
+            .strip_prefix('/')
+            .filter(|rest| !rest.is_empty() && rest.bytes().all(|c| c.is_ascii_digit()))
+        {
+            get_long_name(string_table, string_offset)?
+        } else if get_special_member_kind(raw_name, &layout.special_members).is_some() {
+            raw_name.to_string()
+        } else {
+            raw_name.strip_suffix('/').unwrap_or(raw_name).to_string()
+        };
+
+        if let Some(kind) = get_special_member_kind(&name, &layout.special_members) {
+            if kind == SpecialMemberKind::StringTable {
+                string_table = get_bytes(archive, data_offset, size)?;
+            }
+            layout.special_members.push(SpecialMember {
+                kind,
+                name,
+                header_offset,
+                data_offset,
+                size,
+            });
+            offset = align_to(data_offset + size, 2);
+            continue;
+        }
+
+        layout.members.push(MemberLayout {
+            name,
+            header_offset,
+            data_offset: (!thin).then_some(data_offset),
+            size,
+            mtime: parse_field(&header[16..28], 10)?,
+            uid: parse_field(&header[28..34], 10)?,
+            gid: parse_field(&header[34..40], 10)?,
+            perms: parse_field(&header[40..48], 8)?,
+        });
+        offset = if thin {
+            data_offset
+        } else {
+            align_to(data_offset + size, 2)
+        };
+    }
+    Ok(layout)
+}
+
+struct BigArchiveMember<'a> {
+    header: &'a [u8],
+    name: String,
+    data_offset: u64,
+    size: u64,
+}
+
+fn read_big_archive_member(archive: &[u8], offset: u64) -> io::Result<BigArchiveMember<'_>> {
+    let header = get_bytes(archive, offset, BIG_AR_MEM_HDR_SIZE)?;
+    let name_len = parse_field(&header[108..112], 10)?;
+    let name = get_bytes(archive, offset + 112, name_len)?;
+    Ok(BigArchiveMember {
+        header,
+        name: String::from_utf8_lossy(name).into_owned(),
+        data_offset: offset + BIG_AR_MEM_HDR_SIZE + align_to(name_len, 2),
+        size: parse_field(&header[..20], 10)?,
+    })
+}
+
+fn inspect_big_archive(archive: &[u8]) -> io::Result<ArchiveLayout> {
+    let fix_len_hdr = archive
+        .get(..FIX_LEN_HDR_SIZE)
+        .ok_or_else(|| invalid_data("truncated archive"))?;
+    let field = |index: usize| {
+        let start = archive::AIX_BIG_MAGIC.len() + index * 20;
+        parse_field(&fix_len_hdr[start..start + 20], 10)
+    };
+    let mut layout = ArchiveLayout {
+        magic: String::from_utf8_lossy(&archive::AIX_BIG_MAGIC).into_owned(),
+        special_members: Vec::new(),
+        members: Vec::new(),
+    };
+
+    for (index, kind) in [
+        SpecialMemberKind::AixMemberTable,
+        SpecialMemberKind::AixGlobalSymbolTable,
+        SpecialMemberKind::AixGlobalSymbolTable64,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let header_offset = field(index)?;
+        if header_offset == 0 {
+            continue;
+        }
+        let member = read_big_archive_member(archive, header_offset)?;
+        layout.special_members.push(SpecialMember {
+            kind,
+            name: member.name,
+            header_offset,
+            data_offset: member.data_offset,
+            size: member.size,
+        });
+    }
+
+    // Follow the linked list of members from the first member.
+    let last_offset = field(4)?;
+    let mut offset = field(3)?;
+    let mut seen = HashSet::new();
+    while offset != 0 {
+        if !seen.insert(offset) {
+            return Err(invalid_data("big archive member list contains a cycle"));
+        }
+        let member = read_big_archive_member(archive, offset)?;
+        let header = member.header;
+        layout.members.push(MemberLayout {
+            name: member.name,
+            header_offset: offset,
+            data_offset: Some(member.data_offset),
+            size: member.size,
+            mtime: parse_field(&header[60..72], 10)?,
+            uid: parse_field(&header[72..84], 10)?,
+            gid: parse_field(&header[84..96], 10)?,
+            perms: parse_field(&header[96..108], 8)?,
+        });
+        if offset == last_offset {
+            break;
+        }
+        offset = parse_field(&header[20..40], 10)?;
+    }
+    Ok(layout)
+}
+
+/// Returns the magic string, special members and members of the GNU, BSD,
+/// COFF, thin or AIX big archive `archive`, with their offsets and sizes.
+pub fn inspect_archive(archive: &[u8]) -> io::Result<ArchiveLayout> {
+    if archive.starts_with(&archive::MAGIC) {
+        inspect_common_archive(archive, false)
+    } else if archive.starts_with(&archive::THIN_MAGIC) {
+        inspect_common_archive(archive, true)
+    } else if archive.starts_with(&archive::AIX_BIG_MAGIC) {
+        inspect_big_archive(archive)
+    } else {
+        Err(Error::new(ErrorKind::InvalidInput, "not an archive"))
+    }
+}
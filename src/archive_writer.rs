@@ -5,17 +5,19 @@
 // See https://llvm.org/LICENSE.txt for license information.
 // SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
 
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io::{self, Cursor, Seek, Write};
 use std::mem::size_of;
 
 use crate::alignment::*;
 use crate::archive::*;
 use crate::coff_import_file;
+use crate::duplicate_members::{find_duplicate_members, DuplicateMembers};
+use crate::macho_universal_writer::{get_member_symbolic_data, FatMembers};
 use crate::math_extras::align_to_power_of2;
 use crate::ObjectReader;
 
-const BIG_AR_MEM_HDR_SIZE: u64 = {
+pub(crate) const BIG_AR_MEM_HDR_SIZE: u64 = {
     // `try_into` is not const, so check the size manually.
     assert!(
         std::mem::size_of::<usize>() <= std::mem::size_of::<u64>()
@@ -137,7 +139,7 @@ fn print_bsd_member_header<W: Write>(
     )
 }
 
-fn print_big_archive_member_header<W: Write>(
+pub(crate) fn print_big_archive_member_header<W: Write>(
     w: &mut W,
     name: &str,
     mtime: u64,
@@ -237,6 +239,9 @@ struct MemberData<'a> {
     symbols: Vec<u64>,
     header: Vec<u8>,
     data: &'a [u8],
+    /// The object file that symbols are read from, which is a slice of the
+    /// member for a Mach-O universal binary.
+    symbolic_data: &'a [u8],
     padding: &'static [u8],
     pre_head_pad_size: u64,
     object_reader: &'static ObjectReader,
@@ -255,6 +260,7 @@ fn compute_string_table(names: &[u8]) -> MemberData<'_> {
         data: names,
         padding: if pad != 0 { b"\n" } else { b"" },
         pre_head_pad_size: 0,
+        symbolic_data: &[],
         object_reader: &crate::DEFAULT_OBJECT_READER,
     }
 }
@@ -442,7 +448,7 @@ fn write_symbol_table<W: Write + Seek>(
     for m in members {
         if is_aix_big_archive(kind) {
             pos += m.pre_head_pad_size;
-            if (m.object_reader.is_64_bit_object_file)(m.data) != is_64_bit {
+            if (m.object_reader.is_64_bit_object_file)(m.symbolic_data) != is_64_bit {
                 pos += u64::try_from(m.header.len() + m.data.len() + m.padding.len()).unwrap();
                 continue;
             }
@@ -540,7 +546,7 @@ fn write_ec_symbols<W: Write + Seek>(w: &mut W, sym_map: &SymMap) -> io::Result<
 
 // NOTE: isECObject was moved to object_reader.rs
 
-fn is_import_descriptor(name: &[u8]) -> bool {
+pub(crate) fn is_import_descriptor(name: &[u8]) -> bool {
     name.starts_with(coff_import_file::IMPORT_DESCRIPTOR_PREFIX)
         || name == coff_import_file::NULL_IMPORT_DESCRIPTOR_SYMBOL_NAME
         || (name.starts_with(coff_import_file::NULL_THUNK_DATA_PREFIX)
@@ -611,6 +617,7 @@ fn compute_member_data<'a, S: Write + Seek>(
     thin: bool,
     sym_map: &mut Option<&mut SymMap>,
     new_members: &'a [NewArchiveMember<'a>],
+    fat_members: FatMembers,
 ) -> io::Result<Vec<MemberData<'a>>> {
     const PADDING_DATA: &[u8; 8] = &[b'\n'; 8];
 
@@ -787,8 +794,9 @@ fn compute_member_data<'a, S: Write + Seek>(
             )?;
         }
 
+        let symbolic_data = get_member_symbolic_data(buf, fat_members, &m.member_name)?;
         let symbols = write_symbols(
-            buf,
+            symbolic_data,
             index.try_into().unwrap(),
             sym_names,
             sym_map,
@@ -801,6 +809,7 @@ fn compute_member_data<'a, S: Write + Seek>(
             symbols,
             header,
             data,
+            symbolic_data,
             padding,
             pre_head_pad_size: mem_head_pad_size,
             object_reader: m.object_reader,
@@ -817,17 +826,95 @@ fn compute_member_data<'a, S: Write + Seek>(
     Ok(ret)
 }
 
+/// Options for [write_archive_to_stream_with_options].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WriteArchiveOptions {
+    /// How members that are Mach-O universal binaries are handled, as their
+    /// symbols can only be read from one of their slices.
+    pub fat_members: FatMembers,
+
+    /// Whether members whose content is identical to a previous member are
+    /// left out.
+    pub duplicate_members: DuplicateMembers,
+}
+
 pub fn write_archive_to_stream<'a, W: Write + Seek>(
+    w: &mut W,
+    new_members: &'a [NewArchiveMember<'a>],
+    kind: ArchiveKind,
+    thin: bool,
+    is_ec: bool,
+) -> io::Result<()> {
+    write_archive_to_stream_with_options(
+        w,
+        new_members,
+        kind,
+        thin,
+        is_ec,
+        WriteArchiveOptions::default(),
+    )
+}
+
+/// Writes an archive like [write_archive_to_stream], with the additional
+/// `options`.
+pub fn write_archive_to_stream_with_options<'a, W: Write + Seek>(
     w: &mut W,
     new_members: &'a [NewArchiveMember<'a>],
     mut kind: ArchiveKind,
     thin: bool,
     is_ec: bool,
+    options: WriteArchiveOptions,
 ) -> io::Result<()> {
-    assert!(
-        !thin || !is_bsd_like(kind),
-        "Only the gnu format has a thin mode"
-    );
+    let WriteArchiveOptions {
+        fat_members,
+        duplicate_members,
+    } = options;
+    if let DuplicateMembers::Remove { match_names } = duplicate_members {
+        let duplicates = find_duplicate_members(new_members, match_names);
+        if !duplicates.is_empty() {
+            let duplicate_indices = duplicates
+                .iter()
+                .map(|duplicate| duplicate.index)
+                .collect::<HashSet<_>>();
+            let remaining = new_members
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| !duplicate_indices.contains(index))
+                .map(|(_, m)| NewArchiveMember {
+                    buf: Box::new((*m.buf).as_ref()),
+                    object_reader: m.object_reader,
+                    member_name: m.member_name.clone(),
+                    mtime: m.mtime,
+                    uid: m.uid,
+                    gid: m.gid,
+                    perms: m.perms,
+                })
+                .collect::<Vec<_>>();
+            return write_archive_to_stream_with_options(
+                w,
+                &remaining,
+                kind,
+                thin,
+                is_ec,
+                WriteArchiveOptions {
+                    duplicate_members: DuplicateMembers::Keep,
+                    ..options
+                },
+            );
+        }
+    }
+    if thin && (is_bsd_like(kind) || is_aix_big_archive(kind)) {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Only the gnu format has a thin mode",
+        ));
+    }
+
+    // A COFF symbol map can't refer to members outside of the archive, so
+    // `llvm-lib /llvmlibthin` writes thin archives in the GNU format.
+    if thin && is_coff_archive(kind) {
+        kind = ArchiveKind::Gnu;
+    }
 
     let mut sym_names = Cursor::new(Vec::new());
     let mut string_table = Cursor::new(Vec::new());
@@ -847,6 +934,7 @@ pub fn write_archive_to_stream<'a, W: Write + Seek>(
         thin,
         &mut is_coff_archive(kind).then_some(&mut sym_map),
         new_members,
+        fat_members,
     )?;
 
     let sym_names = sym_names.into_inner();
@@ -884,7 +972,7 @@ pub fn write_archive_to_stream<'a, W: Write + Seek>(
         // symbols; the second global symbol table does the same for 64-bit file
         // members. As a big archive can have both 32-bit and 64-bit file members,
         // we need to know the number of symbols in each symbol table individually.
-        if is_aix_big_archive(kind) && !(m.object_reader.is_64_bit_object_file)(m.data) {
+        if is_aix_big_archive(kind) && !(m.object_reader.is_64_bit_object_file)(m.symbolic_data) {
             num_syms32 = num_syms32
                 .checked_add(m.symbols.len().try_into().unwrap())
                 .unwrap();
@@ -1021,9 +1109,9 @@ pub fn write_archive_to_stream<'a, W: Write + Seek>(
             // Generate the symbol names for the members.
             for m in &data {
                 write_symbols(
-                    m.data,
+                    m.symbolic_data,
                     0,
-                    if (m.object_reader.is_64_bit_object_file)(m.data) {
+                    if (m.object_reader.is_64_bit_object_file)(m.symbolic_data) {
                         &mut sym_names64
                     } else {
                         &mut sym_names32
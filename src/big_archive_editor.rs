@@ -0,0 +1,486 @@
+//! In-place updates of AIX big archives, which link their members in a doubly
+//! linked list so that members can be added and removed without rewriting the
+//! rest of the archive.
+
+use std::collections::HashSet;
+use std::io::{self, Error, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::mem::size_of;
+
+use object::{BigEndian, U64Bytes};
+
+use crate::alignment::align_to;
+use crate::archive::{big_archive, MAX_MEMBER_SIZE};
+use crate::archive_writer::{print_big_archive_member_header, BIG_AR_MEM_HDR_SIZE};
+use crate::macho_universal_writer::{get_member_symbolic_data, FatMembers};
+use crate::math_extras::align_to_power_of2;
+use crate::NewArchiveMember;
+
+const BIG_ARCHIVE_MAGIC: &[u8] = b"<bigaf>\n";
+const FIX_LEN_HDR_SIZE: u64 = size_of::<big_archive::FixLenHdr>() as u64;
+
+// Offsets of the fields that are patched in BigArMemHdrType.
+const NEXT_OFFSET_FIELD: u64 = 20;
+const PREV_OFFSET_FIELD: u64 = 40;
+const NAME_LEN_FIELD: u64 = 108;
+const NAME_FIELD: u64 = 112;
+
+fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+fn parse_decimal(field: &[u8]) -> io::Result<u64> {
+    let digits = std::str::from_utf8(field)
+        .map_err(|_| invalid_data("invalid number in big archive header"))?
+        .trim_end_matches([' ', '\0']);
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    digits
+        .parse()
+        .map_err(|_| invalid_data("invalid number in big archive header"))
+}
+
+fn read_at<R: Read + Seek>(r: &mut R, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+    r.seek(SeekFrom::Start(offset))?;
+    let mut buf = Vec::new();
+    r.take(len).read_to_end(&mut buf)?;
+    if buf.len() as u64 != len {
+        return Err(invalid_data("truncated big archive"));
+    }
+    Ok(buf)
+}
+
+/// Writes zeros from `*at` up to `to`.
+fn write_padding<W: Write>(w: &mut W, to: u64, at: &mut u64) -> io::Result<()> {
+    let pad = usize::try_from(to - *at).unwrap();
+    *at = to;
+    write!(w, "{nil:\0<pad$}", nil = "")
+}
+
+fn write_decimal_at<W: Write + Seek>(w: &mut W, offset: u64, value: u64) -> io::Result<()> {
+    w.seek(SeekFrom::Start(offset))?;
+    write!(w, "{:<20}", value)
+}
+
+/// The parts of a member header that are needed to relink the member.
+struct MemberHeader {
+    offset: u64,
+    size: u64,
+    next_offset: u64,
+    prev_offset: u64,
+    name: Vec<u8>,
+}
+
+impl MemberHeader {
+    fn read<R: Read + Seek>(r: &mut R, offset: u64) -> io::Result<Self> {
+        let header = read_at(r, offset, NAME_FIELD)?;
+        let name_len = parse_decimal(&header[NAME_LEN_FIELD as usize..])?;
+        Ok(MemberHeader {
+            offset,
+            size: parse_decimal(&header[..NEXT_OFFSET_FIELD as usize])?,
+            next_offset: parse_decimal(
+                &header[NEXT_OFFSET_FIELD as usize..PREV_OFFSET_FIELD as usize],
+            )?,
+            prev_offset: parse_decimal(&header[PREV_OFFSET_FIELD as usize..60])?,
+            name: read_at(r, offset + NAME_FIELD, name_len)?,
+        })
+    }
+
+    fn data_offset(&self) -> u64 {
+        self.offset + BIG_AR_MEM_HDR_SIZE + align_to(self.name.len() as u64, 2)
+    }
+}
+
+/// The offsets in the fixed-length header of a big archive.
+struct FixLenHdr {
+    mem_offset: u64,
+    glob_sym_offset: u64,
+    glob_sym64_offset: u64,
+    first_child_offset: u64,
+    last_child_offset: u64,
+    free_offset: u64,
+}
+
+impl FixLenHdr {
+    fn read<R: Read + Seek>(r: &mut R) -> io::Result<Self> {
+        let header =
+            read_at(r, 0, FIX_LEN_HDR_SIZE).map_err(|_| invalid_data("not an AIX big archive"))?;
+        if !header.starts_with(BIG_ARCHIVE_MAGIC) {
+            return Err(invalid_data("not an AIX big archive"));
+        }
+        let field = |index: usize| {
+            let start = BIG_ARCHIVE_MAGIC.len() + index * 20;
+            parse_decimal(&header[start..start + 20])
+        };
+        Ok(FixLenHdr {
+            mem_offset: field(0)?,
+            glob_sym_offset: field(1)?,
+            glob_sym64_offset: field(2)?,
+            first_child_offset: field(3)?,
+            last_child_offset: field(4)?,
+            free_offset: field(5)?,
+        })
+    }
+
+    fn write<W: Write + Seek>(&self, w: &mut W) -> io::Result<()> {
+        w.seek(SeekFrom::Start(BIG_ARCHIVE_MAGIC.len() as u64))?;
+        for offset in [
+            self.mem_offset,
+            self.glob_sym_offset,
+            self.glob_sym64_offset,
+            self.first_child_offset,
+            self.last_child_offset,
+            self.free_offset,
+        ] {
+            write!(w, "{:<20}", offset)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the members by following the linked list from the first member.
+fn read_members<R: Read + Seek>(r: &mut R, hdr: &FixLenHdr) -> io::Result<Vec<MemberHeader>> {
+    let mut members = Vec::new();
+    let mut seen = HashSet::new();
+    let mut offset = hdr.first_child_offset;
+    while offset != 0 {
+        if !seen.insert(offset) {
+            return Err(invalid_data("big archive member list contains a cycle"));
+        }
+        let member = MemberHeader::read(r, offset)?;
+        offset = member.next_offset;
+        let is_last = member.offset == hdr.last_child_offset;
+        members.push(member);
+        if is_last {
+            break;
+        }
+    }
+    Ok(members)
+}
+
+/// Reads the (member offset, symbol name) entries of a global symbol table.
+fn read_symbol_table<R: Read + Seek>(r: &mut R, offset: u64) -> io::Result<Vec<(u64, Vec<u8>)>> {
+    if offset == 0 {
+        return Ok(Vec::new());
+    }
+    let header = MemberHeader::read(r, offset)?;
+    let data = read_at(r, header.data_offset(), header.size)?;
+    let malformed = || invalid_data("malformed big archive global symbol table");
+
+    let (num_syms, rest) =
+        object::pod::from_bytes::<U64Bytes<BigEndian>>(&data).map_err(|()| malformed())?;
+    let num_syms = usize::try_from(num_syms.get(BigEndian)).map_err(|_| malformed())?;
+    let (offsets, mut names) = object::pod::slice_from_bytes::<U64Bytes<BigEndian>>(rest, num_syms)
+        .map_err(|()| malformed())?;
+    offsets
+        .iter()
+        .map(|offset| {
+            let (name, rest) =
+                names.split_at(names.iter().position(|&c| c == 0).ok_or_else(malformed)?);
+            names = &rest[1..];
+            Ok((offset.get(BigEndian), name.to_vec()))
+        })
+        .collect()
+}
+
+fn write_symbol_table<W: Write>(
+    w: &mut W,
+    symbols: &[(u64, Vec<u8>)],
+    prev_offset: u64,
+    next_offset: u64,
+) -> io::Result<()> {
+    let size = symbol_table_size(symbols) - BIG_AR_MEM_HDR_SIZE;
+    print_big_archive_member_header(w, "", 0, 0, 0, 0, size, prev_offset, next_offset)?;
+    w.write_all(&(symbols.len() as u64).to_be_bytes())?;
+    for (offset, _) in symbols {
+        w.write_all(&offset.to_be_bytes())?;
+    }
+    for (_, name) in symbols {
+        w.write_all(name)?;
+        w.write_all(&[0])?;
+    }
+    Ok(())
+}
+
+/// Returns the size of a global symbol table, including its header.
+fn symbol_table_size(symbols: &[(u64, Vec<u8>)]) -> u64 {
+    BIG_AR_MEM_HDR_SIZE
+        + 8 * (symbols.len() as u64 + 1)
+        + symbols
+            .iter()
+            .map(|(_, name)| name.len() as u64 + 1)
+            .sum::<u64>()
+}
+
+/// A member in the updated list of members.
+struct LinkedMember<'a> {
+    offset: u64,
+    name: &'a [u8],
+    /// The links currently in the archive, or `None` for a new member.
+    links: Option<(u64, u64)>,
+}
+
+fn update_big_archive<'a, RW: Read + Write + Seek>(
+    rw: &mut RW,
+    removed_names: &[&str],
+    new_members: &'a [NewArchiveMember<'a>],
+) -> io::Result<()> {
+    let mut hdr = FixLenHdr::read(rw)?;
+    let members = read_members(rw, &hdr)?;
+    let mut symbols32 = read_symbol_table(rw, hdr.glob_sym_offset)?;
+    let mut symbols64 = read_symbol_table(rw, hdr.glob_sym64_offset)?;
+
+    // Each name removes the first remaining member with that name.
+    let mut removed = vec![false; members.len()];
+    for name in removed_names {
+        let index = members
+            .iter()
+            .zip(&removed)
+            .position(|(m, &removed)| !removed && m.name == name.as_bytes())
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::NotFound,
+                    format!("no member named {name} in archive"),
+                )
+            })?;
+        removed[index] = true;
+    }
+    let removed_offsets = members
+        .iter()
+        .zip(&removed)
+        .filter(|(_, &removed)| removed)
+        .map(|(m, _)| m.offset)
+        .collect::<HashSet<_>>();
+    symbols32.retain(|(offset, _)| !removed_offsets.contains(offset));
+    symbols64.retain(|(offset, _)| !removed_offsets.contains(offset));
+
+    let mut linked_members = members
+        .iter()
+        .zip(&removed)
+        .filter(|(_, &removed)| !removed)
+        .map(|(m, _)| LinkedMember {
+            offset: m.offset,
+            name: &m.name,
+            links: Some((m.prev_offset, m.next_offset)),
+        })
+        .collect::<Vec<_>>();
+
+    // New members go after everything else in the archive, which starts on an
+    // even offset.
+    let end = rw.seek(SeekFrom::End(0))?;
+    let mut pos = align_to(end, 2);
+    let mut new_member_offsets = Vec::with_capacity(new_members.len());
+    for m in new_members {
+        let buf = m.buf.as_ref().as_ref();
+        if buf.len() as u64 > MAX_MEMBER_SIZE {
+            return Err(io::Error::other(format!(
+                "Archive member {} is too big",
+                m.member_name
+            )));
+        }
+        let offset_to_mem_data =
+            pos + BIG_AR_MEM_HDR_SIZE + align_to(m.member_name.len() as u64, 2);
+        pos += align_to_power_of2(
+            offset_to_mem_data,
+            (m.object_reader.get_xcoff_member_alignment)(buf).into(),
+        ) - offset_to_mem_data;
+        new_member_offsets.push(pos);
+        linked_members.push(LinkedMember {
+            offset: pos,
+            name: m.member_name.as_bytes(),
+            links: None,
+        });
+
+        let symbolic_data = get_member_symbolic_data(buf, FatMembers::Ignore, &m.member_name)?;
+        let symbols = if (m.object_reader.is_64_bit_object_file)(symbolic_data) {
+            &mut symbols64
+        } else {
+            &mut symbols32
+        };
+        (m.object_reader.get_symbols)(symbolic_data, &mut |name| {
+            symbols.push((pos, name.to_vec()));
+            Ok(())
+        })?;
+
+        pos += BIG_AR_MEM_HDR_SIZE
+            + align_to(m.member_name.len() as u64, 2)
+            + align_to(buf.len() as u64, 2);
+    }
+
+    // The member table and global symbol tables follow the members, as the
+    // archive writer lays them out.
+    let member_table_offset = pos;
+    let member_table_size = 20
+        + 20 * linked_members.len() as u64
+        + linked_members
+            .iter()
+            .map(|m| m.name.len() as u64 + 1)
+            .sum::<u64>();
+    let member_table_end_offset =
+        member_table_offset + align_to(BIG_AR_MEM_HDR_SIZE + member_table_size, 2);
+    let has_members = !linked_members.is_empty();
+    let glob_sym_offset = if has_members && !symbols32.is_empty() {
+        member_table_end_offset
+    } else {
+        0
+    };
+    let glob_sym64_offset = if !has_members || symbols64.is_empty() {
+        0
+    } else if glob_sym_offset == 0 {
+        member_table_end_offset
+    } else {
+        glob_sym_offset + align_to(symbol_table_size(&symbols32), 2)
+    };
+
+    rw.seek(SeekFrom::Start(end))?;
+    let mut w = io::BufWriter::new(&mut *rw);
+    let mut at = end;
+    let first_new_member = linked_members.len() - new_members.len();
+    for (i, m) in new_members.iter().enumerate() {
+        let index = first_new_member + i;
+        let offset = new_member_offsets[i];
+        let prev_offset = index.checked_sub(1).map_or(0, |i| linked_members[i].offset);
+        let next_offset = linked_members
+            .get(index + 1)
+            .map_or(member_table_offset, |m| m.offset);
+        let buf = m.buf.as_ref().as_ref();
+        write_padding(&mut w, offset, &mut at)?;
+        print_big_archive_member_header(
+            &mut w,
+            &m.member_name,
+            m.mtime,
+            m.uid,
+            m.gid,
+            m.perms,
+            buf.len() as u64,
+            prev_offset,
+            next_offset,
+        )?;
+        w.write_all(buf)?;
+        at = offset
+            + BIG_AR_MEM_HDR_SIZE
+            + align_to(m.member_name.len() as u64, 2)
+            + buf.len() as u64;
+    }
+
+    if has_members {
+        write_padding(&mut w, member_table_offset, &mut at)?;
+        print_big_archive_member_header(
+            &mut w,
+            "",
+            0,
+            0,
+            0,
+            0,
+            member_table_size,
+            linked_members.last().unwrap().offset,
+            if glob_sym_offset != 0 {
+                glob_sym_offset
+            } else {
+                glob_sym64_offset
+            },
+        )?;
+        write!(w, "{:<20}", linked_members.len())?;
+        for m in &linked_members {
+            write!(w, "{:<20}", m.offset)?;
+        }
+        for m in &linked_members {
+            w.write_all(m.name)?;
+            w.write_all(&[0])?;
+        }
+        at = member_table_offset + BIG_AR_MEM_HDR_SIZE + member_table_size;
+
+        if glob_sym_offset != 0 {
+            write_padding(&mut w, glob_sym_offset, &mut at)?;
+            write_symbol_table(&mut w, &symbols32, member_table_offset, glob_sym64_offset)?;
+            at = glob_sym_offset + symbol_table_size(&symbols32);
+        }
+        if glob_sym64_offset != 0 {
+            write_padding(&mut w, glob_sym64_offset, &mut at)?;
+            write_symbol_table(
+                &mut w,
+                &symbols64,
+                if glob_sym_offset != 0 {
+                    glob_sym_offset
+                } else {
+                    member_table_offset
+                },
+                0,
+            )?;
+        }
+    }
+    w.flush()?;
+    drop(w);
+
+    // Relink the remaining members around the removed ones and the new ones.
+    for (index, m) in linked_members.iter().enumerate() {
+        let Some((prev_offset, next_offset)) = m.links else {
+            continue;
+        };
+        let new_prev_offset = index.checked_sub(1).map_or(0, |i| linked_members[i].offset);
+        let new_next_offset = linked_members
+            .get(index + 1)
+            .map_or(member_table_offset, |m| m.offset);
+        if prev_offset != new_prev_offset {
+            write_decimal_at(rw, m.offset + PREV_OFFSET_FIELD, new_prev_offset)?;
+        }
+        if next_offset != new_next_offset {
+            write_decimal_at(rw, m.offset + NEXT_OFFSET_FIELD, new_next_offset)?;
+        }
+    }
+
+    // The removed members and the replaced tables go on the free list, which
+    // is linked through the same fields as the member list.
+    let freed_offsets = members
+        .iter()
+        .zip(&removed)
+        .filter(|(_, &removed)| removed)
+        .map(|(m, _)| m.offset)
+        .chain([hdr.mem_offset, hdr.glob_sym_offset, hdr.glob_sym64_offset])
+        .filter(|&offset| offset != 0);
+    for offset in freed_offsets {
+        write_decimal_at(rw, offset + PREV_OFFSET_FIELD, 0)?;
+        write_decimal_at(rw, offset + NEXT_OFFSET_FIELD, hdr.free_offset)?;
+        if hdr.free_offset != 0 {
+            write_decimal_at(rw, hdr.free_offset + PREV_OFFSET_FIELD, offset)?;
+        }
+        hdr.free_offset = offset;
+    }
+
+    hdr.mem_offset = if has_members { member_table_offset } else { 0 };
+    hdr.glob_sym_offset = glob_sym_offset;
+    hdr.glob_sym64_offset = glob_sym64_offset;
+    hdr.first_child_offset = linked_members.first().map_or(0, |m| m.offset);
+    hdr.last_child_offset = linked_members.last().map_or(0, |m| m.offset);
+    hdr.write(rw)?;
+    rw.flush()
+}
+
+/// Appends `new_members` to the AIX big archive in `rw`, after its existing
+/// members.
+///
+/// The new members, member table and global symbol tables are written to the
+/// end of the archive and the existing members are relinked in place, so
+/// their data isn't rewritten. The replaced tables are put on the free list of
+/// the archive. The symbols of the existing members are taken from the
+/// existing global symbol tables.
+pub fn append_to_big_archive<'a, RW: Read + Write + Seek>(
+    rw: &mut RW,
+    new_members: &'a [NewArchiveMember<'a>],
+) -> io::Result<()> {
+    update_big_archive(rw, &[], new_members)
+}
+
+/// Removes members from the AIX big archive in `rw`. Each name removes the
+/// first remaining member with that name.
+///
+/// The removed members are unlinked from the list of members and put on the
+/// free list of the archive, along with the replaced member table and global
+/// symbol tables. The space on the free list isn't reused; use
+/// [crate::write_archive_to_stream] to write a compact archive instead.
+pub fn remove_from_big_archive<RW: Read + Write + Seek>(
+    rw: &mut RW,
+    member_names: &[&str],
+) -> io::Result<()> {
+    update_big_archive(rw, member_names, &[])
+}
@@ -21,6 +21,22 @@ impl From<MachineTypes> for u16 {
     }
 }
 
+impl TryFrom<u16> for MachineTypes {
+    type Error = u16;
+
+    fn try_from(val: u16) -> Result<Self, Self::Error> {
+        Ok(match val {
+            0x8664 => Self::AMD64,
+            0x1C4 => Self::ARMNT,
+            0xAA64 => Self::ARM64,
+            0xA641 => Self::ARM64EC,
+            0xA64E => Self::ARM64X,
+            0x14C => Self::I386,
+            _ => return Err(val),
+        })
+    }
+}
+
 pub fn is_arm64ec(machine: MachineTypes) -> bool {
     machine == MachineTypes::ARM64EC || machine == MachineTypes::ARM64X
 }
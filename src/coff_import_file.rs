@@ -4,23 +4,29 @@
 // SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
 
 use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::io::{Error, ErrorKind, Result, Seek, Write};
 use std::mem::{offset_of, size_of};
 use std::path::PathBuf;
 use std::str::from_utf8;
 
 use object::pe::{
-    ImageFileHeader, ImageImportDescriptor, ImageRelocation, ImageSectionHeader, ImageSymbol,
-    ImportObjectHeader, IMAGE_FILE_32BIT_MACHINE, IMAGE_REL_AMD64_ADDR32NB,
-    IMAGE_REL_ARM64_ADDR32NB, IMAGE_REL_ARM_ADDR32NB, IMAGE_REL_I386_DIR32NB,
-    IMAGE_SCN_ALIGN_2BYTES, IMAGE_SCN_ALIGN_4BYTES, IMAGE_SCN_ALIGN_8BYTES,
+    ImageAuxSymbolWeak, ImageFileHeader, ImageImportDescriptor, ImageRelocation,
+    ImageSectionHeader, ImageSymbol, ImportObjectHeader, IMAGE_FILE_32BIT_MACHINE,
+    IMAGE_REL_AMD64_ADDR32NB, IMAGE_REL_ARM64_ADDR32NB, IMAGE_REL_ARM_ADDR32NB,
+    IMAGE_REL_I386_DIR32NB, IMAGE_SCN_ALIGN_2BYTES, IMAGE_SCN_ALIGN_4BYTES, IMAGE_SCN_ALIGN_8BYTES,
     IMAGE_SCN_CNT_INITIALIZED_DATA, IMAGE_SCN_LNK_INFO, IMAGE_SCN_LNK_REMOVE, IMAGE_SCN_MEM_READ,
     IMAGE_SCN_MEM_WRITE, IMAGE_SYM_CLASS_EXTERNAL, IMAGE_SYM_CLASS_NULL, IMAGE_SYM_CLASS_SECTION,
     IMAGE_SYM_CLASS_STATIC, IMAGE_SYM_CLASS_WEAK_EXTERNAL, IMAGE_WEAK_EXTERN_SEARCH_ALIAS,
+    IMPORT_OBJECT_DATA,
 };
 use object::pod::bytes_of;
+use object::read::archive::ArchiveFile;
+use object::read::coff::{CoffFile, ImageSymbol as _, ImportObjectData};
 
+use crate::archive_writer::is_import_descriptor;
 use crate::coff::{is_arm64ec, ImportNameType, ImportType, MachineTypes};
+use crate::decoration::rename_decorated;
 use crate::mangler::{get_arm64ec_demangled_function_name, get_arm64ec_mangled_function_name};
 use crate::{write_archive_to_stream, ArchiveKind, NewArchiveMember, DEFAULT_OBJECT_READER};
 
@@ -52,13 +58,12 @@ pub(crate) fn get_short_import_symbol(
     let is_ec = header.machine.get(object::LittleEndian) == object::pe::IMAGE_FILE_MACHINE_ARM64EC;
 
     let name = data.symbol();
-    let demangled_name = is_ec
-        .then(|| get_arm64ec_demangled_function_name(from_utf8(name).unwrap()))
-        .flatten()
-        .map_or_else(
-            || Cow::Borrowed(name),
-            |demangled_name| Cow::Owned(demangled_name.into_bytes()),
-        );
+    let demangled_name = if is_ec {
+        get_arm64ec_demangled_function_name(name)?
+    } else {
+        None
+    };
+    let demangled_name = demangled_name.map_or(Cow::Borrowed(name), Cow::Owned);
 
     // Import symbol is first.
     const IMP_PREFIX: &[u8] = b"__imp_";
@@ -69,11 +74,14 @@ pub(crate) fn get_short_import_symbol(
         .collect::<Vec<_>>())?;
 
     // For data, only the import symbol is needed.
-    if header.import_type() == ImportType::Data.into() {
+    if header.import_type() == IMPORT_OBJECT_DATA {
         return Ok(true);
     }
 
-    // Next, thunk.
+    // Next, thunk. Constants get a symbol with the plain name as well, but it
+    // refers to the import address table entry rather than to a thunk, so that
+    // the importer dereferences it to get the value. Like LLVM, unknown import
+    // types are treated as code.
     f(demangled_name.as_ref())?;
 
     // For Arm64EC, also the EC import symbol and thunk.
@@ -96,6 +104,7 @@ const READER_FOR_SHORT_IMPORT: crate::ObjectReader = crate::ObjectReader {
     ..crate::DEFAULT_OBJECT_READER
 };
 
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct COFFShortExport {
     /// The name of the export as specified in the .def file or on the command
     /// line, i.e. "foo" in "/EXPORT:foo", and "bar" in "/EXPORT:foo=bar". This
@@ -180,11 +189,33 @@ fn get_name_type(sym: &str, ext_name: &str, machine: MachineTypes, mingw: bool)
     }
 }
 
-fn replace(s: &str, mut from: &str, mut to: &str) -> Result<String> {
+// Derived from COFFImportFile::getExportName.
+/// Returns the name that the loader looks up for a symbol imported by name.
+fn apply_name_type(name: &str, name_type: ImportNameType) -> &str {
+    match name_type {
+        ImportNameType::NameNoprefix | ImportNameType::NameUndecorate => {
+            let name = name.strip_prefix(['?', '@', '_']).unwrap_or(name);
+            if name_type == ImportNameType::NameUndecorate {
+                name.split_once('@').map_or(name, |(name, _)| name)
+            } else {
+                name
+            }
+        }
+        _ => name,
+    }
+}
+
+fn replace(s: &str, mut from: &str, mut to: &str, machine: MachineTypes) -> Result<String> {
     if let Some((before, after)) = s.split_once(from) {
         return Ok(format!("{before}{to}{after}"));
     }
 
+    // From and To may be decorated differently from S, so rename the
+    // undecorated name and keep the calling convention of S.
+    if let Some(renamed) = rename_decorated(s, from, to, machine) {
+        return Ok(renamed);
+    }
+
     // From and To may be mangled, but substrings in S may not.
     if from.starts_with('_') && to.starts_with('_') {
         from = &from[1..];
@@ -820,6 +851,197 @@ impl<'a> ObjectFactory<'a> {
     }
 }
 
+/// How the Ordinal/Hint field of short import members is filled in for exports
+/// that are imported by name. Exports with an explicit ordinal, including
+/// `NONAME` exports, always use their ordinal.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ImportHints<'a> {
+    /// Use the ordinal of each export, or 0 if it doesn't have one.
+    #[default]
+    Ordinal,
+    /// Use the index of each import name in the sorted list of the names of
+    /// all exports (including private exports), as `lib.exe` does. This is the
+    /// DLL's export name table if the exports describe the whole DLL.
+    SortedNames,
+    /// Use the index of each import name in the given export name table of the
+    /// DLL, which is sorted by name. Names that are not found get a hint of 0.
+    ExportTable(&'a [&'a str]),
+    /// Use the given hint for each export, in the order of the exports, such
+    /// as the [ImportLibrary::hints] of an import library that was read back.
+    /// Exports without a hint get a hint of 0.
+    PerExport(&'a [u16]),
+}
+
+fn get_import_type(e: &COFFShortExport) -> ImportType {
+    if e.constant {
+        ImportType::Const
+    } else if e.data {
+        ImportType::Data
+    } else {
+        ImportType::Code
+    }
+}
+
+/// The names stored in the short import member for an export.
+struct ShortImportNames<'a> {
+    name: Cow<'a, str>,
+    name_type: ImportNameType,
+    export_name: Option<Cow<'a, str>>,
+}
+
+/// Returns the public symbol name of `e`, which is either the symbol name or
+/// the export name with the `ext_name` rename applied.
+fn get_public_name(e: &COFFShortExport, machine: MachineTypes) -> Result<Cow<'_, str>> {
+    let symbol_name = if let Some(symbol_name) = e.symbol_name.as_ref() {
+        symbol_name
+    } else {
+        &e.name
+    };
+
+    Ok(if let Some(ext_name) = e.ext_name.as_ref() {
+        Cow::Owned(replace(symbol_name, &e.name, ext_name, machine)?)
+    } else {
+        Cow::Borrowed(symbol_name)
+    })
+}
+
+fn get_short_import_names<'a>(
+    e: &'a COFFShortExport,
+    name: Cow<'a, str>,
+    import_type: ImportType,
+    machine: MachineTypes,
+    mingw: bool,
+) -> Result<ShortImportNames<'a>> {
+    // On ARM64EC, use EXPORTAS to import demangled name for mangled symbols.
+    if import_type == ImportType::Code && is_arm64ec(machine) {
+        if let Some(mangled_name) = get_arm64ec_mangled_function_name(name.as_bytes())? {
+            return Ok(ShortImportNames {
+                name: Cow::Owned(from_mangled_utf8(mangled_name)?),
+                name_type: ImportNameType::NameExportas,
+                export_name: Some(name),
+            });
+        }
+        let Some(export_name) = get_arm64ec_demangled_function_name(name.as_bytes())? else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid ARM64EC function name '{name}'"),
+            ));
+        };
+        return Ok(ShortImportNames {
+            name,
+            name_type: ImportNameType::NameExportas,
+            export_name: Some(Cow::Owned(from_mangled_utf8(export_name)?)),
+        });
+    }
+
+    if e.noname {
+        return Ok(ShortImportNames {
+            name,
+            name_type: ImportNameType::Ordinal,
+            export_name: None,
+        });
+    }
+
+    let symbol_name = e.symbol_name.as_deref().unwrap_or(&e.name);
+    Ok(ShortImportNames {
+        name,
+        name_type: get_name_type(symbol_name, &e.name, machine, mingw),
+        export_name: None,
+    })
+}
+
+/// Converts a name produced by the ARM64EC mangler from a UTF-8 name back to a
+/// string.
+fn from_mangled_utf8(name: Vec<u8>) -> Result<String> {
+    String::from_utf8(name).map_err(Error::other)
+}
+
+/// Returns the name that the loader looks up in the DLL's export name table,
+/// or `None` if the import is by ordinal.
+fn get_import_name<'a>(names: &'a ShortImportNames<'_>) -> Option<&'a str> {
+    match names.name_type {
+        ImportNameType::Ordinal => None,
+        ImportNameType::NameExportas => names.export_name.as_deref(),
+        name_type => Some(apply_name_type(&names.name, name_type)),
+    }
+}
+
+/// Computes the hint for each import name according to `hints`.
+fn compute_hints(
+    exports: &[COFFShortExport],
+    machine: MachineTypes,
+    mingw: bool,
+    hints: ImportHints<'_>,
+) -> Result<Option<HashMap<String, u16>>> {
+    // Hints larger than the field can hold are left as 0.
+    let to_hint_map = |names: &mut dyn Iterator<Item = &str>| {
+        names
+            .enumerate()
+            .filter_map(|(index, name)| Some((name.to_string(), u16::try_from(index).ok()?)))
+            .collect::<HashMap<_, _>>()
+    };
+
+    match hints {
+        ImportHints::Ordinal | ImportHints::PerExport(_) => Ok(None),
+        ImportHints::ExportTable(table) => Ok(Some(to_hint_map(&mut table.iter().copied()))),
+        ImportHints::SortedNames => {
+            let mut import_names = Vec::new();
+            for e in exports {
+                if e.alias_target.is_some() {
+                    continue;
+                }
+                let import_type = get_import_type(e);
+                let name = get_public_name(e, machine)?;
+                let names = get_short_import_names(e, name, import_type, machine, mingw)?;
+                if let Some(import_name) = get_import_name(&names) {
+                    import_names.push(import_name.to_string());
+                }
+            }
+            import_names.sort_unstable();
+            import_names.dedup();
+            Ok(Some(to_hint_map(
+                &mut import_names.iter().map(String::as_str),
+            )))
+        }
+    }
+}
+
+/// Checks that `exports` can be written to an import library. Returns an error
+/// for exports that set contradictory flags, and a list of warnings for
+/// exports that are accepted but should be changed.
+pub fn validate_exports(exports: &[COFFShortExport]) -> Result<Vec<String>> {
+    let mut warnings = Vec::new();
+    for e in exports {
+        if e.data && e.constant {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("{}: an export cannot be both DATA and CONSTANT", e.name),
+            ));
+        }
+        if e.constant {
+            warnings.push(format!(
+                "{}: CONSTANT keyword is obsolete; use DATA",
+                e.name
+            ));
+        }
+    }
+    Ok(warnings)
+}
+
+/// Options for [write_import_library_with_options].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ImportLibraryOptions<'a> {
+    /// Whether to write a GNU archive, as MinGW does, rather than a COFF
+    /// archive.
+    pub mingw: bool,
+
+    /// How the Ordinal/Hint field is filled in for exports imported by name.
+    pub hints: ImportHints<'a>,
+}
+
+/// Writes an import library for `exports`. The exports are checked with
+/// [validate_exports] first. This doesn't report the warnings for the
+/// exports; use [write_import_library_with_options] to get them.
 pub fn write_import_library<W: Write + Seek>(
     w: &mut W,
     import_name: &str,
@@ -827,6 +1049,32 @@ pub fn write_import_library<W: Write + Seek>(
     machine: MachineTypes,
     mingw: bool,
 ) -> Result<()> {
+    write_import_library_with_options(
+        w,
+        import_name,
+        exports,
+        machine,
+        ImportLibraryOptions {
+            mingw,
+            ..Default::default()
+        },
+    )
+    .map(|_warnings| ())
+}
+
+/// Writes an import library for `exports` like [write_import_library], with
+/// the additional `options`. Returns the warnings from [validate_exports] for
+/// the exports, which the caller should report.
+pub fn write_import_library_with_options<W: Write + Seek>(
+    w: &mut W,
+    import_name: &str,
+    exports: &[COFFShortExport],
+    machine: MachineTypes,
+    options: ImportLibraryOptions<'_>,
+) -> Result<Vec<String>> {
+    let ImportLibraryOptions { mingw, hints } = options;
+    let warnings = validate_exports(exports)?;
+
     let native_machine = if machine == MachineTypes::ARM64EC {
         MachineTypes::ARM64
     } else {
@@ -842,30 +1090,15 @@ pub fn write_import_library<W: Write + Seek>(
 
     members.push(of.create_null_thunk()?);
 
-    for e in exports {
+    let hint_map = compute_hints(exports, machine, mingw, hints)?;
+
+    for (index, e) in exports.iter().enumerate() {
         if e.private {
             continue;
         }
 
-        let mut import_type = ImportType::Code;
-        if e.data {
-            import_type = ImportType::Data;
-        }
-        if e.constant {
-            import_type = ImportType::Const;
-        }
-
-        let symbol_name = if let Some(symbol_name) = e.symbol_name.as_ref() {
-            symbol_name
-        } else {
-            &e.name
-        };
-
-        let mut name: Cow<'_, str> = if let Some(ext_name) = e.ext_name.as_ref() {
-            Cow::Owned(replace(symbol_name, &e.name, ext_name)?)
-        } else {
-            Cow::Borrowed(symbol_name)
-        };
+        let import_type = get_import_type(e);
+        let name = get_public_name(e, machine)?;
 
         if let Some(alias_target) = e.alias_target.as_ref() {
             if name.as_ref() != alias_target {
@@ -875,33 +1108,24 @@ pub fn write_import_library<W: Write + Seek>(
             }
         }
 
-        let mut name_type = if e.noname {
-            ImportNameType::Ordinal
-        } else {
-            get_name_type(symbol_name, &e.name, machine, mingw)
-        };
+        let names = get_short_import_names(e, name, import_type, machine, mingw)?;
 
-        // On ARM64EC, use EXPORTAS to import demangled name for mangled symbols.
-        let export_name = if import_type == ImportType::Code && crate::coff::is_arm64ec(machine) {
-            if let Some(mangled_name) = get_arm64ec_mangled_function_name(&name) {
-                name_type = ImportNameType::NameExportas;
-                let export_name = name;
-                name = Cow::Owned(mangled_name);
-                Some(export_name)
-            } else {
-                name_type = ImportNameType::NameExportas;
-                get_arm64ec_demangled_function_name(&name).map(Cow::Owned)
+        let ordinal_or_hint = match (hints, &hint_map, get_import_name(&names)) {
+            (ImportHints::PerExport(hints), _, Some(_)) if e.ordinal == 0 => {
+                hints.get(index).copied().unwrap_or(0)
             }
-        } else {
-            None
+            (_, Some(hint_map), Some(import_name)) if e.ordinal == 0 => {
+                hint_map.get(import_name).copied().unwrap_or(0)
+            }
+            _ => e.ordinal,
         };
 
         members.push(of.create_short_import(
-            &name,
-            e.ordinal,
+            &names.name,
+            ordinal_or_hint,
             import_type,
-            name_type,
-            export_name.as_deref(),
+            names.name_type,
+            names.export_name.as_deref(),
             machine,
         )?);
     }
@@ -916,5 +1140,298 @@ pub fn write_import_library<W: Write + Seek>(
         },
         false,
         is_arm64ec(machine),
+    )?;
+    Ok(warnings)
+}
+
+/// An import library that has been read back into the exports it was created
+/// from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImportLibrary {
+    /// The name of the DLL that the import library refers to, i.e. the
+    /// `import_name` passed to [write_import_library].
+    pub import_name: String,
+
+    /// The machine type of the short import members.
+    pub machine: MachineTypes,
+
+    /// The exports, in the order their members appear in the archive.
+    pub exports: Vec<COFFShortExport>,
+
+    /// The hint of each export in `exports` that is imported by name, or 0.
+    /// The Ordinal/Hint field of these exports is a hint for the loader rather
+    /// than an ordinal, so it isn't read into [COFFShortExport::ordinal].
+    pub hints: Vec<u16>,
+}
+
+fn invalid_data(message: impl Into<String>) -> Error {
+    Error::new(ErrorKind::InvalidData, message.into())
+}
+
+fn to_str(name: &[u8]) -> Result<&str> {
+    from_utf8(name).map_err(|_| invalid_data("Import library name is not valid UTF-8"))
+}
+
+fn to_machine(machine: u16) -> Result<MachineTypes> {
+    MachineTypes::try_from(machine)
+        .map_err(|machine| invalid_data(format!("Unsupported machine type: {machine:#x}")))
+}
+
+/// Reconstructs the [COFFShortExport] that produced a short import member, and
+/// its hint. Only the information stored in the member can be recovered, so
+/// renames (`ext_name`) come back as the renamed name.
+fn read_short_import(
+    header: &ImportObjectHeader,
+    data: &ImportObjectData<'_>,
+) -> Result<(COFFShortExport, u16)> {
+    let sym = to_str(data.symbol())?;
+    let import_type = header.import_type();
+    let name_type = header.name_type();
+
+    let mut e = COFFShortExport {
+        name: sym.to_string(),
+        ext_name: None,
+        symbol_name: None,
+        alias_target: None,
+        ordinal: 0,
+        noname: false,
+        data: import_type == u16::from(ImportType::Data),
+        private: false,
+        constant: import_type == u16::from(ImportType::Const),
+    };
+
+    // The Ordinal/Hint field is only an ordinal for imports by ordinal, and is
+    // a hint for imports by name.
+    let mut hint = header.ordinal_or_hint.get(object::LittleEndian);
+    if name_type == u16::from(ImportNameType::Ordinal) {
+        e.ordinal = hint;
+        e.noname = true;
+        hint = 0;
+    } else if name_type == u16::from(ImportNameType::NameUndecorate) {
+        // The name is truncated at the first @ (after any prefix), so keep the
+        // decorated symbol and pass the truncated name as the export name.
+        if let Some(idx) = sym.get(1..).and_then(|s| s.find('@')) {
+            e.name = sym[..idx + 1].to_string();
+            e.symbol_name = Some(sym.to_string());
+        }
+    } else if name_type == u16::from(ImportNameType::NameExportas) {
+        // Only emitted for Arm64EC code, where the export name is the
+        // demangled name that the export was created from.
+        if let Some(export_name) = data.export() {
+            e.name = to_str(export_name)?.to_string();
+        }
+    }
+
+    Ok((e, hint))
+}
+
+/// Reads the weak alias from a member created by
+/// [ObjectFactory::create_weak_external], if it is one. Returns the alias name
+/// and the aliasee.
+fn read_weak_external(file: &CoffFile<'_>) -> Result<Option<(String, String)>> {
+    let symbols = file.coff_symbol_table();
+    for (index, symbol) in symbols.iter() {
+        if symbol.storage_class() != IMAGE_SYM_CLASS_WEAK_EXTERNAL
+            || symbol.number_of_aux_symbols() == 0
+        {
+            continue;
+        }
+        let aux = symbols
+            .get::<ImageAuxSymbolWeak>(index, 1)
+            .map_err(Error::other)?;
+        let target = symbols
+            .symbol(object::SymbolIndex(
+                aux.weak_default_sym_index.get(object::LittleEndian) as usize,
+            ))
+            .map_err(Error::other)?;
+        let name = symbol.name(symbols.strings()).map_err(Error::other)?;
+        let target = target.name(symbols.strings()).map_err(Error::other)?;
+        return Ok(Some((
+            to_str(name)?.to_string(),
+            to_str(target)?.to_string(),
+        )));
+    }
+    Ok(None)
+}
+
+/// Reads the DLL name from a member created by
+/// [ObjectFactory::create_import_descriptor], if it is one.
+fn read_import_descriptor_name(file: &CoffFile<'_>, buf: &[u8]) -> Result<Option<String>> {
+    let symbols = file.coff_symbol_table();
+    let is_import_descriptor = symbols.iter().any(|(_, symbol)| {
+        symbol
+            .name(symbols.strings())
+            .is_ok_and(|name| name.starts_with(IMPORT_DESCRIPTOR_PREFIX))
+    });
+    if !is_import_descriptor {
+        return Ok(None);
+    }
+
+    for section in file.coff_section_table().iter() {
+        if &section.name == b".idata$6" {
+            let data = section
+                .coff_data(buf)
+                .map_err(|_| invalid_data("Import descriptor has an invalid .idata$6 section"))?;
+            let name = data.split(|&c| c == 0).next().unwrap_or_default();
+            return Ok(Some(to_str(name)?.to_string()));
+        }
+    }
+    Ok(None)
+}
+
+/// Reads an import library, such as one written by [write_import_library],
+/// `lib.exe` or `dlltool`, back into the list of exports that it contains.
+pub fn read_import_library(buf: &[u8]) -> Result<ImportLibrary> {
+    let archive = ArchiveFile::parse(buf).map_err(Error::other)?;
+
+    let mut import_name = None;
+    let mut short_import_machine = None;
+    let mut object_machine = None;
+    let mut exports = Vec::<COFFShortExport>::new();
+    let mut hints = Vec::new();
+
+    for member in archive.members() {
+        let member = member.map_err(Error::other)?;
+        if member.name() == b"/<ECSYMBOLS>/" {
+            continue;
+        }
+        let data = member.data(buf).map_err(Error::other)?;
+
+        match object::FileKind::parse(data) {
+            Ok(object::FileKind::CoffImport) => {
+                let mut offset = 0;
+                let header = ImportObjectHeader::parse(data, &mut offset).map_err(Error::other)?;
+                let import = header.parse_data(data, &mut offset).map_err(Error::other)?;
+                short_import_machine
+                    .get_or_insert(to_machine(header.machine.get(object::LittleEndian))?);
+                import_name.get_or_insert(to_str(import.dll())?.to_string());
+                let (export, hint) = read_short_import(header, &import)?;
+                exports.push(export);
+                hints.push(hint);
+            }
+            Ok(object::FileKind::Coff) => {
+                let file = CoffFile::<&[u8]>::parse(data).map_err(Error::other)?;
+                object_machine.get_or_insert(to_machine(
+                    file.coff_header().machine.get(object::LittleEndian),
+                )?);
+
+                if let Some((name, target)) = read_weak_external(&file)? {
+                    // Each alias is written twice: once as-is and once with
+                    // the `__imp_` prefix on both sides.
+                    if name.starts_with("__imp_") && target.starts_with("__imp_") {
+                        continue;
+                    }
+                    exports.push(COFFShortExport {
+                        name,
+                        ext_name: None,
+                        symbol_name: None,
+                        alias_target: Some(target),
+                        ordinal: 0,
+                        noname: false,
+                        data: false,
+                        private: false,
+                        constant: false,
+                    });
+                    hints.push(0);
+                } else if let Some(name) = read_import_descriptor_name(&file, data)? {
+                    import_name.get_or_insert(name);
+                }
+            }
+            _ => {
+                return Err(invalid_data(format!(
+                    "Archive member {} is not part of an import library",
+                    String::from_utf8_lossy(member.name())
+                )));
+            }
+        }
+    }
+
+    Ok(ImportLibrary {
+        import_name: import_name
+            .ok_or_else(|| invalid_data("Import library does not contain an import name"))?,
+        machine: short_import_machine
+            .or(object_machine)
+            .ok_or_else(|| invalid_data("Import library does not contain any members"))?,
+        exports,
+        hints,
+    })
+}
+
+/// Merges several import libraries, such as those written by
+/// [write_import_library], into a single import library, like
+/// `lib.exe a.lib b.lib /OUT:all.lib`.
+///
+/// Members are kept in order, except for members that only define import
+/// descriptor symbols that an earlier member already defines: the
+/// `__NULL_IMPORT_DESCRIPTOR` member that every import library contains is
+/// only kept once, while the `__IMPORT_DESCRIPTOR_*` and NULL thunk members of
+/// each DLL are kept. The symbol map is rebuilt from the merged members.
+pub fn merge_import_libraries<W: Write + Seek>(
+    w: &mut W,
+    libraries: &[&[u8]],
+    mingw: bool,
+) -> Result<()> {
+    let mut members = Vec::new();
+    let mut import_descriptors = HashSet::new();
+    let mut is_ec = false;
+
+    for &buf in libraries {
+        let archive = ArchiveFile::parse(buf).map_err(Error::other)?;
+        for member in archive.members() {
+            let member = member.map_err(Error::other)?;
+            if member.name() == b"/<ECSYMBOLS>/" {
+                continue;
+            }
+            let data = member.data(buf).map_err(Error::other)?;
+
+            let is_short_import = object::FileKind::parse(data) == Ok(object::FileKind::CoffImport);
+            let object_reader = if is_short_import {
+                let mut offset = 0;
+                let header = ImportObjectHeader::parse(data, &mut offset).map_err(Error::other)?;
+                is_ec |= to_machine(header.machine.get(object::LittleEndian)).is_ok_and(is_arm64ec);
+                &READER_FOR_SHORT_IMPORT
+            } else {
+                &DEFAULT_OBJECT_READER
+            };
+
+            let mut member_import_descriptors = Vec::new();
+            (object_reader.get_symbols)(data, &mut |name| {
+                if is_import_descriptor(name) {
+                    member_import_descriptors.push(name.to_vec());
+                }
+                Ok(())
+            })?;
+            if !member_import_descriptors.is_empty()
+                && member_import_descriptors
+                    .iter()
+                    .all(|name| import_descriptors.contains(name))
+            {
+                continue;
+            }
+            import_descriptors.extend(member_import_descriptors);
+
+            let mut new_member = NewArchiveMember::new(
+                data,
+                object_reader,
+                String::from_utf8_lossy(member.name()).into_owned(),
+            );
+            new_member.mtime = member.date().unwrap_or(0);
+            new_member.uid = member.uid().unwrap_or(0).try_into().unwrap_or(0);
+            new_member.gid = member.gid().unwrap_or(0).try_into().unwrap_or(0);
+            new_member.perms = member.mode().unwrap_or(0o644).try_into().unwrap_or(0o644);
+            members.push(new_member);
+        }
+    }
+
+    write_archive_to_stream(
+        w,
+        &members,
+        if mingw {
+            ArchiveKind::Gnu
+        } else {
+            ArchiveKind::Coff
+        },
+        false,
+        is_ec,
     )
 }
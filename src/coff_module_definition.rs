@@ -0,0 +1,124 @@
+// Derived from code in LLVM, which is:
+// Part of the LLVM Project, under the Apache License v2.0 with LLVM Exceptions.
+// See https://llvm.org/LICENSE.txt for license information.
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+//! Writer for Windows module-definition (.def) files. The output is the
+//! inverse of LLVM's `parseCOFFModuleDefinition`, so that parsing a written
+//! file yields the same [COFFShortExport]s.
+
+use std::borrow::Cow;
+use std::io::{Error, ErrorKind, Result, Write};
+
+use crate::coff::MachineTypes;
+use crate::coff_import_file::COFFShortExport;
+use crate::decoration::is_decorated;
+
+/// Words that the .def lexer treats as keywords rather than identifiers.
+const KEYWORDS: &[&str] = &[
+    "BASE",
+    "CONSTANT",
+    "DATA",
+    "EXPORTAS",
+    "EXPORTS",
+    "HEAPSIZE",
+    "LIBRARY",
+    "NAME",
+    "NONAME",
+    "PRIVATE",
+    "STACKSIZE",
+    "VERSION",
+];
+
+/// Quotes `name` if the .def lexer would otherwise split it or treat it as a
+/// keyword.
+fn quote(name: &str) -> Result<Cow<'_, str>> {
+    let needs_quotes = name.is_empty()
+        || name.starts_with('"')
+        || name.contains(['=', ',', ';', ' ', '\t', '\r', '\n', '\x0b'])
+        || KEYWORDS.contains(&name);
+    if !needs_quotes {
+        return Ok(Cow::Borrowed(name));
+    }
+    // The lexer has no escape sequences, so a quoted name ends at the next
+    // quote.
+    if name.contains(['"', '\r', '\n']) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("{name:?} cannot be written to a module-definition file"),
+        ));
+    }
+    Ok(Cow::Owned(format!("\"{name}\"")))
+}
+
+/// Removes the underscore that the .def parser adds to undecorated names on
+/// I386.
+fn undo_leading_underscore(name: &str, machine: MachineTypes, mingw: bool) -> Result<&str> {
+    if machine != MachineTypes::I386 || is_decorated(name, mingw) {
+        return Ok(name);
+    }
+    match name.strip_prefix('_') {
+        Some(stripped) if !is_decorated(stripped, mingw) => Ok(stripped),
+        _ => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("{name}: undecorated I386 names must start with '_'"),
+        )),
+    }
+}
+
+/// Writes a module-definition (.def) file that describes `exports`, such that
+/// passing it to `lib.exe /DEF` or `dlltool --input-def` recreates them.
+///
+/// `machine` and `mingw` must match the values the exports are used with in
+/// [crate::write_import_library], as they control how I386 names are
+/// decorated. The `symbol_name` of each export cannot be expressed in a .def
+/// file and is ignored.
+pub fn write_module_definition<W: Write>(
+    w: &mut W,
+    import_name: &str,
+    exports: &[COFFShortExport],
+    machine: MachineTypes,
+    mingw: bool,
+) -> Result<()> {
+    write!(w, "LIBRARY {}\n", quote(import_name)?)?;
+    write!(w, "EXPORTS\n")?;
+
+    for e in exports {
+        let name = quote(undo_leading_underscore(&e.name, machine, mingw)?)?;
+        if let Some(ext_name) = &e.ext_name {
+            let ext_name = quote(undo_leading_underscore(ext_name, machine, mingw)?)?;
+            write!(w, "   {ext_name}={name}")?;
+        } else {
+            write!(w, "   {name}")?;
+        }
+
+        if let Some(alias_target) = &e.alias_target {
+            let alias_target = quote(undo_leading_underscore(alias_target, machine, mingw)?)?;
+            write!(w, "=={alias_target}")?;
+        }
+
+        if e.ordinal != 0 {
+            write!(w, " @{}", e.ordinal)?;
+            if e.noname {
+                write!(w, " NONAME")?;
+            }
+        } else if e.noname {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("{}: NONAME exports require an ordinal", e.name),
+            ));
+        }
+        if e.data {
+            write!(w, " DATA")?;
+        }
+        if e.private {
+            write!(w, " PRIVATE")?;
+        }
+        if e.constant {
+            write!(w, " CONSTANT")?;
+        }
+        write!(w, "\n")?;
+    }
+
+    w.flush()
+}
@@ -0,0 +1,932 @@
+//! Removal of the debug sections of archive members, like
+//! `objcopy --strip-debug` applied to every member.
+
+use std::borrow::Cow;
+use std::io::{self, Error, ErrorKind};
+use std::mem::size_of;
+
+use object::read::coff::{CoffHeader, ImageSymbol as _};
+use object::read::elf::{FileHeader, SectionHeader as _, Sym as _};
+use object::read::macho::{MachHeader, Nlist as _, Section as _, Segment as _};
+use object::{elf, macho, pe, Endianness, FileKind, LittleEndian as LE, SymbolIndex, U32Bytes};
+
+use crate::object_editing::{invalid_data, pad_to, read_error, write_u16, write_u32, write_u64};
+use crate::symbol_localization::{remap_elf_addrsig, remap_elf_relocations, SHT_LLVM_ADDRSIG};
+use crate::NewArchiveMember;
+
+fn is_debug_section(name: &[u8]) -> bool {
+    name.starts_with(b".debug") || name.starts_with(b".zdebug")
+}
+
+/// Returns the new index of each item that isn't removed.
+fn get_new_indices(removed: &[bool]) -> Vec<Option<u32>> {
+    let mut count = 0;
+    removed
+        .iter()
+        .map(|&removed| {
+            (!removed).then(|| {
+                count += 1;
+                count - 1
+            })
+        })
+        .collect()
+}
+
+fn get_new_index(new_indices: &[Option<u32>], index: u32, message: &str) -> io::Result<u32> {
+    new_indices
+        .get(index as usize)
+        .copied()
+        .flatten()
+        .ok_or_else(|| invalid_data(message))
+}
+
+fn get_bytes(data: &[u8], offset: u64, size: u64) -> io::Result<&[u8]> {
+    usize::try_from(offset)
+        .ok()
+        .zip(usize::try_from(size).ok())
+        .and_then(|(offset, size)| data.get(offset..offset.checked_add(size)?))
+        .ok_or_else(|| invalid_data("truncated object"))
+}
+
+/// Removes the `.debug*` sections of an ELF relocatable object, with their
+/// relocation sections and symbols, and lays out the remaining sections
+/// again.
+fn strip_elf_debug_sections<Elf: FileHeader<Endian = Endianness>>(
+    data: &[u8],
+) -> io::Result<Option<Vec<u8>>> {
+    let header = Elf::parse(data).map_err(read_error)?;
+    let endian = header.endian().map_err(read_error)?;
+    if header.e_type(endian) != elf::ET_REL {
+        return Ok(None);
+    }
+    let is_64 = header.is_class_64();
+    let sections = header.sections(endian, data).map_err(read_error)?;
+    if sections.len() >= elf::SHN_LORESERVE.into() {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            "ELF objects with extended section numbering are not supported",
+        ));
+    }
+
+    let mut removed = sections
+        .iter()
+        .map(|section| {
+            let name = sections.section_name(endian, section)?;
+            Ok(is_debug_section(name))
+        })
+        .collect::<object::read::Result<Vec<_>>>()
+        .map_err(read_error)?;
+    if !removed.contains(&true) {
+        return Ok(None);
+    }
+    // Also remove the relocations of the debug sections, and the section
+    // groups that only hold debug sections.
+    for (index, section) in sections.iter().enumerate() {
+        let is_removed = |index: u32| removed.get(index as usize) == Some(&true);
+        removed[index] |= match section.sh_type(endian) {
+            elf::SHT_REL | elf::SHT_RELA => is_removed(section.sh_info(endian)),
+            elf::SHT_GROUP => match section.group(endian, data).map_err(read_error)? {
+                Some((_, members)) => {
+                    !members.is_empty()
+                        && members.iter().all(|member| is_removed(member.get(endian)))
+                }
+                None => false,
+            },
+            _ => false,
+        };
+    }
+    let new_section_indices = get_new_indices(&removed);
+    let new_section_index = |index: u32| {
+        get_new_index(
+            &new_section_indices,
+            index,
+            "section refers to a removed debug section",
+        )
+    };
+
+    // Remove the symbols that are defined in the debug sections, which are
+    // local, so the index of the first global symbol is recomputed.
+    let symbols = sections
+        .symbols(endian, data, elf::SHT_SYMTAB)
+        .map_err(read_error)?;
+    let mut removed_symbols = Vec::with_capacity(symbols.len());
+    let mut local_count = 0;
+    for (index, symbol) in symbols.iter().enumerate() {
+        let section = symbols
+            .symbol_section(endian, symbol, SymbolIndex(index))
+            .map_err(read_error)?;
+        let is_removed = section.is_some_and(|section| removed.get(section.0) == Some(&true));
+        if !is_removed && symbol.st_bind() == elf::STB_LOCAL {
+            local_count += 1;
+        }
+        removed_symbols.push(is_removed);
+    }
+    let new_symbol_indices = get_new_indices(&removed_symbols);
+    let new_symbol_index = |index: u32| {
+        get_new_index(
+            &new_symbol_indices,
+            index,
+            "relocation refers to a symbol in a removed debug section",
+        )
+    };
+
+    // Rewrite the sections that refer to section or symbol indices.
+    let symbol_size = size_of::<Elf::Sym>();
+    // `st_shndx` is the last field of `Sym32`, and follows `st_name`,
+    // `st_info` and `st_other` in `Sym64`.
+    let st_shndx_offset = if is_64 { 6 } else { 14 };
+    let mut section_data = Vec::with_capacity(sections.len());
+    for (index, section) in sections.iter().enumerate() {
+        if removed[index] || section.sh_type(endian) == elf::SHT_NOBITS {
+            section_data.push(Cow::Borrowed(&[][..]));
+            continue;
+        }
+        let contents = section.data(endian, data).map_err(read_error)?;
+        let is_symbol_table = index == symbols.section().0;
+        let links_symbol_table =
+            !symbols.is_empty() && section.sh_link(endian) as usize == symbols.section().0;
+        let new_contents = match section.sh_type(endian) {
+            elf::SHT_SYMTAB if is_symbol_table => {
+                let mut new_contents = Vec::new();
+                for (index, symbol) in symbols.iter().enumerate() {
+                    if removed_symbols[index] {
+                        continue;
+                    }
+                    let offset = new_contents.len() as u64;
+                    new_contents.extend_from_slice(&contents[index * symbol_size..][..symbol_size]);
+                    let shndx = symbol.st_shndx(endian);
+                    if shndx != elf::SHN_UNDEF && shndx < elf::SHN_LORESERVE {
+                        let shndx = new_section_index(shndx.into())? as u16;
+                        write_u16(&mut new_contents, offset + st_shndx_offset, endian, shndx);
+                    }
+                }
+                Cow::Owned(new_contents)
+            }
+            elf::SHT_SYMTAB_SHNDX if links_symbol_table => {
+                let mut new_contents = Vec::new();
+                for (index, &is_removed) in removed_symbols.iter().enumerate() {
+                    if is_removed {
+                        continue;
+                    }
+                    let shndx = symbols.shndx(endian, SymbolIndex(index)).unwrap_or(0);
+                    let shndx = if shndx == 0 {
+                        0
+                    } else {
+                        new_section_index(shndx)?
+                    };
+                    new_contents.extend_from_slice(&[0; 4]);
+                    let offset = new_contents.len() as u64 - 4;
+                    write_u32(&mut new_contents, offset, endian, shndx);
+                }
+                Cow::Owned(new_contents)
+            }
+            elf::SHT_REL | elf::SHT_RELA if links_symbol_table => {
+                let mut new_contents = contents.to_vec();
+                remap_elf_relocations(header, data, section, &mut new_contents, &|index| {
+                    new_symbol_index(index).map(Some)
+                })?;
+                Cow::Owned(new_contents)
+            }
+            // Symbols in debug sections aren't address-significant, but they
+            // are dropped from the table rather than left dangling.
+            SHT_LLVM_ADDRSIG => Cow::Owned(remap_elf_addrsig(contents, &|index| {
+                new_symbol_indices
+                    .get(index as usize)
+                    .copied()
+                    .ok_or_else(|| invalid_data("invalid symbol index"))
+            })?),
+            elf::SHT_GROUP => {
+                // The group flags are followed by the indices of the members.
+                let (_, members) = section
+                    .group(endian, data)
+                    .map_err(read_error)?
+                    .ok_or_else(|| invalid_data("invalid ELF section group"))?;
+                let mut new_contents = contents[..4].to_vec();
+                for member in members {
+                    if let Some(Some(member)) = new_section_indices.get(member.get(endian) as usize)
+                    {
+                        new_contents.extend_from_slice(&[0; 4]);
+                        let offset = new_contents.len() as u64 - 4;
+                        write_u32(&mut new_contents, offset, endian, *member);
+                    }
+                }
+                Cow::Owned(new_contents)
+            }
+            _ => Cow::Borrowed(contents),
+        };
+        section_data.push(new_contents);
+    }
+
+    // Lay out the sections after the file header, followed by the section
+    // headers.
+    let mut out = data[..size_of::<Elf>()].to_vec();
+    let mut section_offsets = vec![0; sections.len()];
+    for (index, section) in sections.iter().enumerate().skip(1) {
+        if removed[index] {
+            continue;
+        }
+        if section.sh_type(endian) != elf::SHT_NOBITS {
+            pad_to(&mut out, section.sh_addralign(endian).into());
+        }
+        section_offsets[index] = out.len() as u64;
+        out.extend_from_slice(&section_data[index]);
+    }
+    pad_to(&mut out, if is_64 { 8 } else { 4 });
+    let section_headers_offset = out.len() as u64;
+    let section_header_size = u64::from(header.e_shentsize(endian));
+    let original_section_headers_offset: u64 = header.e_shoff(endian).into();
+    for (index, section) in sections.iter().enumerate() {
+        if removed[index] {
+            continue;
+        }
+        let offset = out.len() as u64;
+        out.extend_from_slice(get_bytes(
+            data,
+            original_section_headers_offset + index as u64 * section_header_size,
+            section_header_size,
+        )?);
+        // The offsets of `sh_offset`, `sh_size`, `sh_link` and `sh_info`.
+        let (sh_offset, sh_size, sh_link, sh_info) = if is_64 {
+            (24, 32, 40, 44)
+        } else {
+            (16, 20, 24, 28)
+        };
+        if index != 0 {
+            let new_size = if section.sh_type(endian) == elf::SHT_NOBITS {
+                section.sh_size(endian).into()
+            } else {
+                section_data[index].len() as u64
+            };
+            if is_64 {
+                write_u64(&mut out, offset + sh_offset, endian, section_offsets[index]);
+                write_u64(&mut out, offset + sh_size, endian, new_size);
+            } else {
+                let too_large = || invalid_data("ELF object is too large");
+                let section_offset =
+                    u32::try_from(section_offsets[index]).map_err(|_| too_large())?;
+                write_u32(&mut out, offset + sh_offset, endian, section_offset);
+                write_u32(&mut out, offset + sh_size, endian, new_size as u32);
+            }
+        }
+        let link = section.sh_link(endian);
+        if link != 0 {
+            write_u32(&mut out, offset + sh_link, endian, new_section_index(link)?);
+        }
+        let info = section.sh_info(endian);
+        let new_info = match section.sh_type(endian) {
+            elf::SHT_SYMTAB if index == symbols.section().0 => local_count,
+            elf::SHT_GROUP => new_symbol_index(info)?,
+            elf::SHT_REL | elf::SHT_RELA if info != 0 => new_section_index(info)?,
+            _ if section.sh_flags(endian).into() & u64::from(elf::SHF_INFO_LINK) != 0 => {
+                new_section_index(info)?
+            }
+            _ => info,
+        };
+        write_u32(&mut out, offset + sh_info, endian, new_info);
+    }
+
+    // The offsets of `e_shoff`, `e_shnum` and `e_shstrndx`.
+    let section_count = new_section_indices.iter().flatten().count() as u16;
+    let string_table_index = new_section_index(header.e_shstrndx(endian).into())? as u16;
+    if is_64 {
+        write_u64(&mut out, 40, endian, section_headers_offset);
+        write_u16(&mut out, 60, endian, section_count);
+        write_u16(&mut out, 62, endian, string_table_index);
+    } else {
+        let section_headers_offset = u32::try_from(section_headers_offset)
+            .map_err(|_| invalid_data("ELF object is too large"))?;
+        write_u32(&mut out, 32, endian, section_headers_offset);
+        write_u16(&mut out, 48, endian, section_count);
+        write_u16(&mut out, 50, endian, string_table_index);
+    }
+    Ok(Some(out))
+}
+
+/// Returns whether the relocation at `info` holds a value other than a symbol
+/// index or section ordinal in `r_symbolnum`.
+fn is_macho_relocation_without_target(cputype: u32, info: &macho::RelocationInfo) -> bool {
+    match cputype {
+        macho::CPU_TYPE_ARM64 | macho::CPU_TYPE_ARM64_32 => {
+            info.r_type == macho::ARM64_RELOC_ADDEND
+        }
+        macho::CPU_TYPE_X86 | macho::CPU_TYPE_ARM | macho::CPU_TYPE_POWERPC => {
+            info.r_type == macho::GENERIC_RELOC_PAIR
+        }
+        _ => false,
+    }
+}
+
+/// Removes the sections in the `__DWARF` segment of a Mach-O object, with
+/// their relocations and symbols, and lays out the object again.
+fn strip_macho_debug_sections<Mach: MachHeader<Endian = Endianness>>(
+    data: &[u8],
+) -> io::Result<Option<Vec<u8>>> {
+    let header = Mach::parse(data, 0).map_err(read_error)?;
+    let endian = header.endian().map_err(read_error)?;
+    if header.filetype(endian) != macho::MH_OBJECT {
+        return Ok(None);
+    }
+    let is_64 = header.is_type_64();
+    let word_size = if is_64 { 8 } else { 4 };
+    let cputype = header.cputype(endian);
+
+    let mut commands = Vec::new();
+    let mut sections: Vec<&Mach::Section> = Vec::new();
+    let mut symtab = None;
+    let mut dysymtab = None;
+    let mut command_iter = header.load_commands(endian, data, 0).map_err(read_error)?;
+    while let Some(command) = command_iter.next().map_err(read_error)? {
+        // The number of sections of segment commands.
+        let mut section_count = None;
+        if let Some((segment, section_data)) =
+            Mach::Segment::from_command(command).map_err(read_error)?
+        {
+            let segment_sections = segment.sections(endian, section_data).map_err(read_error)?;
+            section_count = Some(segment_sections.len());
+            sections.extend(segment_sections);
+        } else if let Some(command) = command.symtab().map_err(read_error)? {
+            symtab = Some(command);
+        } else if let Some(command) = command.dysymtab().map_err(read_error)? {
+            dysymtab = Some(command);
+        }
+        commands.push((command, section_count));
+    }
+    let removed = sections
+        .iter()
+        .map(|section| section.segment_name() == b"__DWARF")
+        .collect::<Vec<_>>();
+    if !removed.contains(&true) {
+        return Ok(None);
+    }
+    // Sections are numbered from 1, and 0 means no section.
+    let new_section_ordinals = [Some(0)]
+        .into_iter()
+        .chain(
+            get_new_indices(&removed)
+                .into_iter()
+                .map(|index| Some(index? + 1)),
+        )
+        .collect::<Vec<_>>();
+    let new_section_ordinal = |ordinal: u32| {
+        get_new_index(
+            &new_section_ordinals,
+            ordinal,
+            "relocation refers to a removed debug section",
+        )
+    };
+
+    // Remove the symbols that are defined in the debug sections.
+    let symbols = symtab
+        .map(|symtab| symtab.symbols::<Mach, _>(endian, data))
+        .transpose()
+        .map_err(read_error)?;
+    let symbols = symbols
+        .as_ref()
+        .map_or(&[][..], |symbols| symbols.iter().as_slice());
+    let removed_symbols = symbols
+        .iter()
+        .map(|symbol| {
+            !symbol.is_stab()
+                && symbol.n_type() & macho::N_TYPE == macho::N_SECT
+                && removed.get(usize::from(symbol.n_sect()).wrapping_sub(1)) == Some(&true)
+        })
+        .collect::<Vec<_>>();
+    let new_symbol_indices = get_new_indices(&removed_symbols);
+    let new_symbol_index = |index: u32| {
+        get_new_index(
+            &new_symbol_indices,
+            index,
+            "relocation refers to a symbol in a removed debug section",
+        )
+    };
+
+    // Lay out the section data, relocations and symbol table information after
+    // the load commands, which lose the headers of the removed sections.
+    let segment_header_size = size_of::<Mach::Segment>();
+    let section_header_size = size_of::<Mach::Section>();
+    let mut commands_size = 0;
+    let mut section_index = 0;
+    for (command, section_count) in &commands {
+        commands_size += match *section_count {
+            Some(section_count) => {
+                let kept = removed[section_index..][..section_count]
+                    .iter()
+                    .filter(|&&removed| !removed)
+                    .count();
+                section_index += section_count;
+                segment_header_size + kept * section_header_size
+            }
+            None => command.cmdsize() as usize,
+        };
+    }
+    let data_offset = size_of::<Mach>() + commands_size;
+    let mut out = vec![0; data_offset];
+    out[..size_of::<Mach>()].copy_from_slice(&data[..size_of::<Mach>()]);
+
+    let mut section_offsets = vec![0; sections.len()];
+    for (index, section) in sections.iter().enumerate() {
+        let section_type = section.flags(endian) & macho::SECTION_TYPE;
+        if removed[index]
+            || matches!(
+                section_type,
+                macho::S_ZEROFILL | macho::S_GB_ZEROFILL | macho::S_THREAD_LOCAL_ZEROFILL
+            )
+        {
+            continue;
+        }
+        pad_to(&mut out, 1 << section.align(endian));
+        section_offsets[index] = out.len() as u32;
+        let size: u64 = section.size(endian).into();
+        out.extend_from_slice(get_bytes(data, section.offset(endian).into(), size)?);
+    }
+
+    let mut relocation_offsets = vec![0; sections.len()];
+    for (index, section) in sections.iter().enumerate() {
+        if removed[index] || section.nreloc(endian) == 0 {
+            continue;
+        }
+        pad_to(&mut out, 4);
+        relocation_offsets[index] = out.len() as u32;
+        for relocation in section.relocations(endian, data).map_err(read_error)? {
+            let mut relocation = *relocation;
+            if !relocation.r_scattered(endian, cputype) {
+                let mut info = relocation.info(endian);
+                if is_macho_relocation_without_target(cputype, &info) {
+                    // Keep the addend or paired value.
+                } else if info.r_extern {
+                    info.r_symbolnum = new_symbol_index(info.r_symbolnum)?;
+                } else {
+                    info.r_symbolnum = new_section_ordinal(info.r_symbolnum)?;
+                }
+                relocation = info.relocation(endian);
+            }
+            out.extend_from_slice(object::pod::bytes_of(&relocation));
+        }
+    }
+
+    let mut symbols_offset = 0;
+    if !symbols.is_empty() {
+        pad_to(&mut out, word_size);
+        symbols_offset = out.len() as u32;
+        let symbol_size = size_of::<Mach::Nlist>();
+        let original_offset = symtab.map_or(0, |symtab| symtab.symoff.get(endian)) as usize;
+        for (index, symbol) in symbols.iter().enumerate() {
+            if removed_symbols[index] {
+                continue;
+            }
+            let offset = out.len();
+            out.extend_from_slice(&data[original_offset + index * symbol_size..][..symbol_size]);
+            // `n_sect` follows `n_strx` and `n_type`, and the sections of
+            // debugging symbols are cleared if they are removed.
+            let n_sect = symbol.n_sect();
+            out[offset + 5] = new_section_ordinals
+                .get(usize::from(n_sect))
+                .copied()
+                .flatten()
+                .map_or(Ok(0), u8::try_from)
+                .map_err(|_| invalid_data("invalid Mach-O section ordinal"))?;
+        }
+    }
+
+    let mut indirect_symbols_offset = 0;
+    if let Some(dysymtab) = dysymtab {
+        if dysymtab.tocoff.get(endian) != 0
+            || dysymtab.modtaboff.get(endian) != 0
+            || dysymtab.extrefsymoff.get(endian) != 0
+            || dysymtab.extreloff.get(endian) != 0
+            || dysymtab.locreloff.get(endian) != 0
+        {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "Mach-O objects with dynamic relocations or tables of contents are not supported",
+            ));
+        }
+        let count = dysymtab.nindirectsyms.get(endian);
+        if count != 0 {
+            pad_to(&mut out, 4);
+            indirect_symbols_offset = out.len() as u32;
+            let indirect_symbols = get_bytes(
+                data,
+                dysymtab.indirectsymoff.get(endian).into(),
+                u64::from(count) * 4,
+            )?;
+            let indirect_symbols =
+                object::pod::slice_from_all_bytes::<U32Bytes<_>>(indirect_symbols)
+                    .map_err(|()| invalid_data("malformed indirect symbol table"))?;
+            for index in indirect_symbols {
+                let index = index.get(endian);
+                let new_index =
+                    if index & (macho::INDIRECT_SYMBOL_LOCAL | macho::INDIRECT_SYMBOL_ABS) != 0 {
+                        index
+                    } else {
+                        new_symbol_index(index)?
+                    };
+                out.extend_from_slice(&[0; 4]);
+                let offset = out.len() as u64 - 4;
+                write_u32(&mut out, offset, endian, new_index);
+            }
+        }
+    }
+
+    let mut linkedit_data_offsets = Vec::new();
+    for (command, _) in &commands {
+        if let Some(linkedit_data) = get_linkedit_data_command(command)? {
+            pad_to(&mut out, word_size);
+            linkedit_data_offsets.push(out.len() as u32);
+            out.extend_from_slice(get_bytes(
+                data,
+                linkedit_data.dataoff.get(endian).into(),
+                linkedit_data.datasize.get(endian).into(),
+            )?);
+        }
+    }
+
+    let mut strings_offset = 0;
+    if let Some(symtab) = symtab {
+        pad_to(&mut out, word_size);
+        strings_offset = out.len() as u32;
+        out.extend_from_slice(get_bytes(
+            data,
+            symtab.stroff.get(endian).into(),
+            symtab.strsize.get(endian).into(),
+        )?);
+    }
+
+    // Write the load commands with the new offsets.
+    let mut commands_out = Vec::with_capacity(commands_size);
+    let mut section_index = 0;
+    let mut linkedit_data_offsets = linkedit_data_offsets.into_iter();
+    for (command, section_count) in &commands {
+        let offset = commands_out.len() as u64;
+        if let Some(section_count) = *section_count {
+            commands_out.extend_from_slice(&command.raw_data()[..segment_header_size]);
+            let segment_sections = section_index..section_index + section_count;
+            section_index += section_count;
+            let mut kept = 0;
+            let mut file_start = None;
+            let mut file_end = 0;
+            for index in segment_sections {
+                if removed[index] {
+                    continue;
+                }
+                kept += 1;
+                let section = sections[index];
+                let section_offset = commands_out.len() as u64;
+                commands_out.extend_from_slice(object::pod::bytes_of(section));
+                // The offsets of `offset` and `reloff`.
+                let (offset_field, reloff_field) = if is_64 { (48, 56) } else { (40, 48) };
+                write_u32(
+                    &mut commands_out,
+                    section_offset + offset_field,
+                    endian,
+                    section_offsets[index],
+                );
+                write_u32(
+                    &mut commands_out,
+                    section_offset + reloff_field,
+                    endian,
+                    relocation_offsets[index],
+                );
+                if section_offsets[index] != 0 {
+                    let size: u64 = section.size(endian).into();
+                    let start = u64::from(section_offsets[index]);
+                    file_start = Some(file_start.unwrap_or(start).min(start));
+                    file_end = file_end.max(start + size);
+                }
+            }
+            let command_size = (segment_header_size + kept * section_header_size) as u32;
+            let file_start = file_start.unwrap_or(data_offset as u64);
+            let file_size = file_end.saturating_sub(file_start);
+            write_u32(&mut commands_out, offset + 4, endian, command_size);
+            // The offsets of `fileoff`, `filesize` and `nsects`.
+            if is_64 {
+                write_u64(&mut commands_out, offset + 40, endian, file_start);
+                write_u64(&mut commands_out, offset + 48, endian, file_size);
+                write_u32(&mut commands_out, offset + 64, endian, kept as u32);
+            } else {
+                write_u32(&mut commands_out, offset + 32, endian, file_start as u32);
+                write_u32(&mut commands_out, offset + 36, endian, file_size as u32);
+                write_u32(&mut commands_out, offset + 48, endian, kept as u32);
+            }
+            continue;
+        }
+
+        commands_out.extend_from_slice(command.raw_data());
+        if command.symtab().map_err(read_error)?.is_some() {
+            // `symoff`, `nsyms` and `stroff` follow `cmd` and `cmdsize`.
+            let symbol_count = new_symbol_indices.iter().flatten().count() as u32;
+            write_u32(&mut commands_out, offset + 8, endian, symbols_offset);
+            write_u32(&mut commands_out, offset + 12, endian, symbol_count);
+            write_u32(&mut commands_out, offset + 16, endian, strings_offset);
+        } else if let Some(dysymtab) = command.dysymtab().map_err(read_error)? {
+            // `ilocalsym`, `nlocalsym`, `iextdefsym`, `nextdefsym`,
+            // `iundefsym` and `nundefsym` follow `cmd` and `cmdsize`, and
+            // `indirectsymoff` follows the table of contents, module table and
+            // referenced symbol table.
+            let ranges = [
+                (dysymtab.ilocalsym, dysymtab.nlocalsym),
+                (dysymtab.iextdefsym, dysymtab.nextdefsym),
+                (dysymtab.iundefsym, dysymtab.nundefsym),
+            ];
+            for (i, (first, count)) in ranges.into_iter().enumerate() {
+                let first = first.get(endian) as usize;
+                let count = count.get(endian) as usize;
+                let kept_before = new_symbol_indices[..first.min(symbols.len())]
+                    .iter()
+                    .flatten()
+                    .count();
+                let kept = new_symbol_indices
+                    .get(first..first + count)
+                    .ok_or_else(|| invalid_data("invalid Mach-O dynamic symbol table"))?
+                    .iter()
+                    .flatten()
+                    .count();
+                let field_offset = offset + 8 + i as u64 * 8;
+                write_u32(&mut commands_out, field_offset, endian, kept_before as u32);
+                write_u32(&mut commands_out, field_offset + 4, endian, kept as u32);
+            }
+            write_u32(
+                &mut commands_out,
+                offset + 56,
+                endian,
+                indirect_symbols_offset,
+            );
+        } else if get_linkedit_data_command(command)?.is_some() {
+            // `dataoff` follows `cmd` and `cmdsize`.
+            let data_offset = linkedit_data_offsets.next().unwrap();
+            write_u32(&mut commands_out, offset + 8, endian, data_offset);
+        }
+    }
+    out[size_of::<Mach>()..data_offset].copy_from_slice(&commands_out);
+    // `sizeofcmds` follows `magic`, `cputype`, `cpusubtype`, `filetype` and
+    // `ncmds`.
+    write_u32(&mut out, 20, endian, commands_size as u32);
+    Ok(Some(out))
+}
+
+/// Returns the load command if it refers to data in the `__LINKEDIT` segment
+/// with a `linkedit_data_command`.
+fn get_linkedit_data_command<'data>(
+    command: &object::read::macho::LoadCommandData<'data, Endianness>,
+) -> io::Result<Option<&'data macho::LinkeditDataCommand<Endianness>>> {
+    if matches!(
+        command.cmd(),
+        macho::LC_CODE_SIGNATURE
+            | macho::LC_SEGMENT_SPLIT_INFO
+            | macho::LC_FUNCTION_STARTS
+            | macho::LC_DATA_IN_CODE
+            | macho::LC_DYLIB_CODE_SIGN_DRS
+            | macho::LC_LINKER_OPTIMIZATION_HINT
+            | macho::LC_DYLD_EXPORTS_TRIE
+            | macho::LC_DYLD_CHAINED_FIXUPS
+    ) {
+        Ok(Some(command.data().map_err(read_error)?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Removes the `.debug*` sections of a COFF object, with their relocations and
+/// symbols, and lays out the object again.
+fn strip_coff_debug_sections<Coff: CoffHeader>(data: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    let mut headers_end = 0;
+    let header = Coff::parse(data, &mut headers_end).map_err(read_error)?;
+    let sections = header.sections(data, headers_end).map_err(read_error)?;
+    let symbols = header.symbols(data).map_err(read_error)?;
+    let removed = sections
+        .iter()
+        .map(|section| Ok(is_debug_section(section.name(symbols.strings())?)))
+        .collect::<object::read::Result<Vec<_>>>()
+        .map_err(read_error)?;
+    if !removed.contains(&true) {
+        return Ok(None);
+    }
+    // Sections are numbered from 1.
+    let new_section_numbers = [Some(0)]
+        .into_iter()
+        .chain(
+            get_new_indices(&removed)
+                .into_iter()
+                .map(|index| Some(index? + 1)),
+        )
+        .collect::<Vec<_>>();
+    let new_section_number = |number: u32| {
+        get_new_index(
+            &new_section_numbers,
+            number,
+            "symbol refers to a removed debug section",
+        )
+    };
+
+    // Remove the symbols of the debug sections, with their auxiliary symbols.
+    let mut removed_symbols = Vec::with_capacity(symbols.len());
+    while removed_symbols.len() < symbols.len() {
+        let symbol = symbols
+            .symbol(SymbolIndex(removed_symbols.len()))
+            .map_err(read_error)?;
+        let section_number = symbol.section_number();
+        let is_removed =
+            section_number > 0 && removed.get(section_number as usize - 1) == Some(&true);
+        for _ in 0..=symbol.number_of_aux_symbols() {
+            removed_symbols.push(is_removed);
+        }
+    }
+    let new_symbol_indices = get_new_indices(&removed_symbols);
+    let new_symbol_index = |index: u32| {
+        get_new_index(
+            &new_symbol_indices,
+            index,
+            "relocation refers to a symbol in a removed debug section",
+        )
+    };
+
+    let symbol_size = size_of::<Coff::ImageSymbol>();
+    let symbols_offset = u64::from(header.pointer_to_symbol_table());
+    let mut new_symbols = Vec::new();
+    let mut index = 0;
+    while index < symbols.len() {
+        let symbol = symbols.symbol(SymbolIndex(index)).map_err(read_error)?;
+        let count = 1 + usize::from(symbol.number_of_aux_symbols());
+        if !removed_symbols[index] {
+            let offset = new_symbols.len() as u64;
+            new_symbols.extend_from_slice(get_bytes(
+                data,
+                symbols_offset + (index * symbol_size) as u64,
+                (count * symbol_size) as u64,
+            )?);
+            // `SectionNumber` follows `Name` and `Value`, and is 32-bit in
+            // bigobj files.
+            let section_number = symbol.section_number();
+            if section_number > 0 {
+                let section_number = new_section_number(section_number as u32)?;
+                if Coff::is_type_bigobj() {
+                    write_u32(
+                        &mut new_symbols,
+                        offset + 12,
+                        Endianness::Little,
+                        section_number,
+                    );
+                } else {
+                    write_u16(
+                        &mut new_symbols,
+                        offset + 12,
+                        Endianness::Little,
+                        section_number as u16,
+                    );
+                }
+            }
+            if symbol.has_aux_section() {
+                let aux = symbols
+                    .aux_section(SymbolIndex(index))
+                    .map_err(read_error)?;
+                if aux.selection == pe::IMAGE_COMDAT_SELECT_ASSOCIATIVE {
+                    // `Number` follows `Length`, `NumberOfRelocations`,
+                    // `NumberOfLinenumbers` and `CheckSum`. Bigobj files hold
+                    // the high bits in `HighNumber`, after `Selection`.
+                    let mut number = u32::from(aux.number.get(LE));
+                    if Coff::is_type_bigobj() {
+                        number |= u32::from(aux.high_number.get(LE)) << 16;
+                    }
+                    let number = new_section_number(number)?;
+                    let aux_offset = offset + symbol_size as u64;
+                    write_u16(
+                        &mut new_symbols,
+                        aux_offset + 12,
+                        Endianness::Little,
+                        number as u16,
+                    );
+                    if Coff::is_type_bigobj() {
+                        write_u16(
+                            &mut new_symbols,
+                            aux_offset + 16,
+                            Endianness::Little,
+                            (number >> 16) as u16,
+                        );
+                    }
+                }
+            } else if symbol.storage_class() == pe::IMAGE_SYM_CLASS_WEAK_EXTERNAL && count > 1 {
+                let aux = symbols
+                    .get::<pe::ImageAuxSymbolWeak>(SymbolIndex(index), 1)
+                    .map_err(read_error)?;
+                let default_index = new_symbol_index(aux.weak_default_sym_index.get(LE))?;
+                let aux_offset = offset + symbol_size as u64;
+                write_u32(
+                    &mut new_symbols,
+                    aux_offset,
+                    Endianness::Little,
+                    default_index,
+                );
+            }
+        }
+        index += count;
+    }
+
+    // Lay out the section headers, followed by the data and relocations of
+    // each section, the symbol table and the string table.
+    let kept_sections = sections
+        .iter()
+        .zip(&removed)
+        .filter(|(_, &removed)| !removed)
+        .map(|(section, _)| section)
+        .collect::<Vec<_>>();
+    let section_headers_offset = headers_end as usize;
+    let mut out = data[..section_headers_offset].to_vec();
+    out.resize(
+        section_headers_offset + kept_sections.len() * size_of::<pe::ImageSectionHeader>(),
+        0,
+    );
+    for (i, section) in kept_sections.iter().enumerate() {
+        let mut section = **section;
+        let size = u64::from(section.size_of_raw_data.get(LE));
+        if section.pointer_to_raw_data.get(LE) != 0 && size != 0 {
+            pad_to(&mut out, 4);
+            let raw_data = get_bytes(data, section.pointer_to_raw_data.get(LE).into(), size)?;
+            section.pointer_to_raw_data.set(LE, out.len() as u32);
+            out.extend_from_slice(raw_data);
+        }
+        let relocations = section.coff_relocations(data).map_err(read_error)?;
+        if section.number_of_relocations.get(LE) != 0 {
+            pad_to(&mut out, 4);
+            let relocations_offset = section.pointer_to_relocations.get(LE);
+            section.pointer_to_relocations.set(LE, out.len() as u32);
+            if section.characteristics.get(LE) & pe::IMAGE_SCN_LNK_NRELOC_OVFL != 0 {
+                // The first relocation holds the number of relocations.
+                out.extend_from_slice(get_bytes(
+                    data,
+                    relocations_offset.into(),
+                    size_of::<pe::ImageRelocation>() as u64,
+                )?);
+            }
+            for relocation in relocations {
+                let mut relocation = *relocation;
+                let symbol_index = new_symbol_index(relocation.symbol_table_index.get(LE))?;
+                relocation.symbol_table_index.set(LE, symbol_index);
+                out.extend_from_slice(object::pod::bytes_of(&relocation));
+            }
+        }
+        // Line numbers are a deprecated form of debug information.
+        section.pointer_to_linenumbers.set(LE, 0);
+        section.number_of_linenumbers.set(LE, 0);
+        let header_offset = section_headers_offset + i * size_of::<pe::ImageSectionHeader>();
+        out[header_offset..header_offset + size_of::<pe::ImageSectionHeader>()]
+            .copy_from_slice(object::pod::bytes_of(&section));
+    }
+
+    pad_to(&mut out, 4);
+    let new_symbols_offset = out.len() as u32;
+    out.extend_from_slice(&new_symbols);
+    let strings_offset = symbols_offset + (symbols.len() * symbol_size) as u64;
+    out.extend_from_slice(
+        data.get(strings_offset as usize..)
+            .ok_or_else(|| invalid_data("truncated object"))?,
+    );
+
+    // The offsets of `NumberOfSections`, `PointerToSymbolTable` and
+    // `NumberOfSymbols`. `NumberOfSections` is 32-bit in bigobj files.
+    let number_of_symbols = (new_symbols.len() / symbol_size) as u32;
+    if Coff::is_type_bigobj() {
+        write_u32(&mut out, 44, Endianness::Little, kept_sections.len() as u32);
+        write_u32(&mut out, 48, Endianness::Little, new_symbols_offset);
+        write_u32(&mut out, 52, Endianness::Little, number_of_symbols);
+    } else {
+        write_u16(&mut out, 2, Endianness::Little, kept_sections.len() as u16);
+        write_u32(&mut out, 8, Endianness::Little, new_symbols_offset);
+        write_u32(&mut out, 12, Endianness::Little, number_of_symbols);
+    }
+    Ok(Some(out))
+}
+
+/// Removes the debug sections of the members: the `.debug*` sections of ELF
+/// and COFF objects, and the sections in the `__DWARF` segment of Mach-O
+/// objects, with their relocations and the symbols defined in them.
+///
+/// Only local symbols are removed, so the archive symbol table that
+/// [crate::write_archive_to_stream] writes for the returned members is
+/// unaffected. Members that aren't relocatable objects are kept unchanged.
+pub fn strip_debug_sections<'a>(
+    members: Vec<NewArchiveMember<'a>>,
+) -> io::Result<Vec<NewArchiveMember<'a>>> {
+    let mut stripped_members = Vec::with_capacity(members.len());
+    for mut member in members {
+        let data = (*member.buf).as_ref();
+        let stripped = match FileKind::parse(data) {
+            Ok(FileKind::Elf32) => strip_elf_debug_sections::<elf::FileHeader32<Endianness>>(data),
+            Ok(FileKind::Elf64) => strip_elf_debug_sections::<elf::FileHeader64<Endianness>>(data),
+            Ok(FileKind::MachO32) => {
+                strip_macho_debug_sections::<macho::MachHeader32<Endianness>>(data)
+            }
+            Ok(FileKind::MachO64) => {
+                strip_macho_debug_sections::<macho::MachHeader64<Endianness>>(data)
+            }
+            Ok(FileKind::Coff) => strip_coff_debug_sections::<pe::ImageFileHeader>(data),
+            Ok(FileKind::CoffBig) => strip_coff_debug_sections::<pe::AnonObjectHeaderBigobj>(data),
+            _ => Ok(None),
+        }
+        .map_err(|err| Error::new(err.kind(), format!("{}: {err}", member.member_name)))?;
+        if let Some(stripped) = stripped {
+            member.buf = Box::new(stripped);
+        }
+        stripped_members.push(member);
+    }
+    Ok(stripped_members)
+}
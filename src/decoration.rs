@@ -0,0 +1,160 @@
+// Derived from code in LLVM, which is:
+// Part of the LLVM Project, under the Apache License v2.0 with LLVM Exceptions.
+// See https://llvm.org/LICENSE.txt for license information.
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+//! Parsing and construction of C symbol names decorated with their calling
+//! convention, as used by MSVC and MinGW.
+
+use crate::coff::MachineTypes;
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub(crate) enum CallingConvention {
+    /// `_name` on I386, `name` elsewhere.
+    Cdecl,
+    /// `_name@N`, only on I386.
+    Stdcall,
+    /// `@name@N`, only on I386.
+    Fastcall,
+    /// `name@@N`.
+    Vectorcall,
+}
+
+/// A C symbol name split into its undecorated name and decoration.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub(crate) struct DecoratedName<'a> {
+    pub(crate) convention: CallingConvention,
+    /// The name as written in the source.
+    pub(crate) name: &'a str,
+    /// The number of bytes of arguments, for all conventions but cdecl.
+    pub(crate) arg_bytes: Option<u32>,
+}
+
+/// Splits `name` at the last `suffix` that is followed only by digits.
+fn split_arg_bytes<'a>(name: &'a str, suffix: &str) -> Option<(&'a str, u32)> {
+    let (name, arg_bytes) = name.rsplit_once(suffix)?;
+    if arg_bytes.is_empty() || !arg_bytes.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((name, arg_bytes.parse().ok()?))
+}
+
+/// Parses a symbol name into its calling convention and undecorated name.
+/// Returns `None` for C++ names, which use a different mangling scheme, and
+/// for names that are not validly decorated.
+pub(crate) fn parse_decorated_name(sym: &str, machine: MachineTypes) -> Option<DecoratedName<'_>> {
+    if sym.starts_with('?') {
+        return None;
+    }
+
+    // Vectorcall has no prefix on any machine, so check for it first.
+    if let Some((name, arg_bytes)) = split_arg_bytes(sym, "@@") {
+        if name.is_empty() {
+            return None;
+        }
+        return Some(DecoratedName {
+            convention: CallingConvention::Vectorcall,
+            name,
+            arg_bytes: Some(arg_bytes),
+        });
+    }
+
+    if machine != MachineTypes::I386 {
+        return Some(DecoratedName {
+            convention: CallingConvention::Cdecl,
+            name: sym,
+            arg_bytes: None,
+        });
+    }
+
+    if let Some(rest) = sym.strip_prefix('@') {
+        let (name, arg_bytes) = split_arg_bytes(rest, "@")?;
+        return (!name.is_empty()).then_some(DecoratedName {
+            convention: CallingConvention::Fastcall,
+            name,
+            arg_bytes: Some(arg_bytes),
+        });
+    }
+
+    let rest = sym.strip_prefix('_')?;
+    if let Some((name, arg_bytes)) = split_arg_bytes(rest, "@") {
+        return (!name.is_empty()).then_some(DecoratedName {
+            convention: CallingConvention::Stdcall,
+            name,
+            arg_bytes: Some(arg_bytes),
+        });
+    }
+    Some(DecoratedName {
+        convention: CallingConvention::Cdecl,
+        name: rest,
+        arg_bytes: None,
+    })
+}
+
+/// Decorates `name` for the given calling convention. This is the inverse of
+/// [parse_decorated_name].
+pub(crate) fn decorate(
+    name: &str,
+    convention: CallingConvention,
+    arg_bytes: Option<u32>,
+    machine: MachineTypes,
+) -> String {
+    let arg_bytes = arg_bytes.unwrap_or(0);
+    match convention {
+        CallingConvention::Cdecl if machine == MachineTypes::I386 => format!("_{name}"),
+        CallingConvention::Cdecl => name.to_string(),
+        CallingConvention::Stdcall => format!("_{name}@{arg_bytes}"),
+        CallingConvention::Fastcall => format!("@{name}@{arg_bytes}"),
+        CallingConvention::Vectorcall => format!("{name}@@{arg_bytes}"),
+    }
+}
+
+/// Renames the symbol `sym`, replacing the name `from` with `to` while keeping
+/// the calling convention of `sym`. `from` and `to` may be decorated or not.
+/// Returns `None` if `sym` isn't a decorated version of `from`.
+pub(crate) fn rename_decorated(
+    sym: &str,
+    from: &str,
+    to: &str,
+    machine: MachineTypes,
+) -> Option<String> {
+    let sym = parse_decorated_name(sym, machine)?;
+    let from = parse_decorated_name(from, machine)?;
+    if sym.name != from.name {
+        return None;
+    }
+
+    // A fully decorated target already specifies its calling convention.
+    let to = match parse_decorated_name(to, machine) {
+        Some(to) if to.convention == CallingConvention::Cdecl => to,
+        _ => return Some(to.to_string()),
+    };
+    Some(decorate(to.name, sym.convention, sym.arg_bytes, machine))
+}
+
+// Derived from isDecorated in COFFModuleDefinition.cpp.
+pub(crate) fn is_decorated(sym: &str, mingw: bool) -> bool {
+    // In def files, the symbols can either be listed decorated or undecorated.
+    //
+    // - For cdecl symbols, only the undecorated form is allowed.
+    // - For fastcall and vectorcall symbols, both fully decorated or
+    //   undecorated forms can be present.
+    // - For stdcall symbols in non-MinGW environments, the decorated form is
+    //   fully decorated with leading underscore and trailing stack argument
+    //   size - like "_Func@0".
+    // - In MinGW def files, a decorated stdcall symbol does not include the
+    //   leading underscore though, like "Func@0".
+    //
+    // This function controls whether a leading underscore should be added to
+    // the given symbol name or not. For MinGW, treat a stdcall symbol name such
+    // as "Func@0" as undecorated, i.e. a leading underscore must be added.
+    // For non-MinGW, look for '@' in the whole string and consider "_Func@0"
+    // as decorated, i.e. don't add any more leading underscores.
+    // We can't check for a leading underscore here, since function names
+    // themselves can start with an underscore, while a second one still needs
+    // to be added.
+    sym.starts_with('@')
+        || sym.contains("@@")
+        || sym.starts_with('?')
+        || (!mingw && sym.contains('@'))
+}
@@ -0,0 +1,90 @@
+//! Detection and removal of archive members whose content is identical to a
+//! previous member, such as generated objects that are added under different
+//! names.
+
+use std::collections::HashMap;
+
+use crate::NewArchiveMember;
+
+/// A member whose content is identical to a previous member.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateMember {
+    /// The index of the member in the members of the archive.
+    pub index: usize,
+    /// The name of the member.
+    pub member_name: String,
+    /// The index of the first member with the same content, which is the
+    /// copy that is retained.
+    pub retained_index: usize,
+}
+
+/// How [crate::write_archive_to_stream_with_options] handles members whose
+/// content is identical to a previous member.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicateMembers {
+    /// Write every member, as LLVM does.
+    #[default]
+    Keep,
+    /// Leave out the members that [find_duplicate_members] finds with
+    /// `match_names`, as [remove_duplicate_members] does.
+    Remove { match_names: bool },
+}
+
+/// Returns the members whose content is identical to a previous member, in
+/// archive order. If `match_names` is set, members are only duplicates if
+/// their names are identical too.
+///
+/// Only the content is compared, so the header fields such as the timestamp
+/// and permissions of the members may differ.
+pub fn find_duplicate_members(
+    members: &[NewArchiveMember<'_>],
+    match_names: bool,
+) -> Vec<DuplicateMember> {
+    let mut first_indices = HashMap::new();
+    let mut duplicates = Vec::new();
+    for (index, member) in members.iter().enumerate() {
+        let name = match_names.then_some(member.member_name.as_str());
+        let key = (name, (*member.buf).as_ref());
+        let retained_index = *first_indices.entry(key).or_insert(index);
+        if retained_index != index {
+            duplicates.push(DuplicateMember {
+                index,
+                member_name: member.member_name.clone(),
+                retained_index,
+            });
+        }
+    }
+    duplicates
+}
+
+/// Removes the members whose content is identical to a previous member, as
+/// found by [find_duplicate_members], and returns the remaining members and
+/// the removed duplicates.
+///
+/// [crate::write_archive_to_stream] writes every member that it is given, so
+/// callers that want duplicates removed must either call this on the members
+/// before writing the archive, or write it with
+/// [crate::write_archive_to_stream_with_options] and
+/// [DuplicateMembers::Remove].
+///
+/// The symbols of a removed copy are the same as the symbols of the retained
+/// copy, so the archive symbol table that [crate::write_archive_to_stream]
+/// writes for the remaining members lists them once, for the retained copy.
+/// The indices of the removed duplicates refer to `members`.
+pub fn remove_duplicate_members<'a>(
+    members: Vec<NewArchiveMember<'a>>,
+    match_names: bool,
+) -> (Vec<NewArchiveMember<'a>>, Vec<DuplicateMember>) {
+    let duplicates = find_duplicate_members(&members, match_names);
+    let mut duplicate_indices = duplicates
+        .iter()
+        .map(|duplicate| duplicate.index)
+        .peekable();
+    let remaining = members
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| duplicate_indices.next_if_eq(index).is_none())
+        .map(|(_, member)| member)
+        .collect();
+    (remaining, duplicates)
+}
@@ -0,0 +1,133 @@
+//! Detection of symbols that are defined by more than one archive member,
+//! which the archive symbol tables either list twice or silently ignore.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Error, ErrorKind};
+
+use object::{Object as _, ObjectComdat as _, ObjectSymbol as _};
+
+use crate::macho_universal_writer::{get_member_symbolic_data, FatMembers};
+use crate::NewArchiveMember;
+
+/// A member that defines a symbol.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SymbolDefinition {
+    /// The index of the member in the members of the archive.
+    pub index: usize,
+    pub member_name: String,
+}
+
+/// A symbol that is defined by more than one member.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateSymbol {
+    pub name: Vec<u8>,
+    /// The members that define the symbol, in archive order.
+    pub definitions: Vec<SymbolDefinition>,
+}
+
+/// Returns the names of the weak, COMDAT and common definitions in `buf`,
+/// which a linker doesn't report when they are defined more than once.
+fn get_weak_definitions(buf: &[u8]) -> HashSet<Vec<u8>> {
+    let Ok(file) = object::File::parse(buf) else {
+        return HashSet::new();
+    };
+    let comdat_sections = file
+        .comdats()
+        .flat_map(|comdat| comdat.sections())
+        .collect::<HashSet<_>>();
+    file.symbols()
+        .filter(|symbol| {
+            symbol.is_weak()
+                || symbol.is_common()
+                || symbol
+                    .section_index()
+                    .is_some_and(|index| comdat_sections.contains(&index))
+        })
+        .filter_map(|symbol| symbol.name_bytes().ok())
+        .map(<[u8]>::to_vec)
+        .collect()
+}
+
+/// Returns the symbols that are defined by more than one of `members`, in the
+/// order of their first definition.
+///
+/// The symbols of a member are those that are added to the archive symbol
+/// table. Weak, COMDAT and common definitions are only reported if
+/// `include_weak` is true, as linkers accept more than one of them.
+pub fn find_duplicate_symbols(
+    members: &[NewArchiveMember<'_>],
+    include_weak: bool,
+) -> io::Result<Vec<DuplicateSymbol>> {
+    let mut symbols: Vec<DuplicateSymbol> = Vec::new();
+    let mut symbol_indices = HashMap::new();
+    for (index, member) in members.iter().enumerate() {
+        let object_reader = member.object_reader;
+        let buf = get_member_symbolic_data(
+            (*member.buf).as_ref(),
+            FatMembers::Ignore,
+            &member.member_name,
+        )?;
+        let weak_definitions = if include_weak {
+            HashSet::new()
+        } else {
+            get_weak_definitions(buf)
+        };
+        (object_reader.get_symbols)(buf, &mut |name| {
+            if weak_definitions.contains(name) {
+                return Ok(());
+            }
+            let symbol_index = *symbol_indices.entry(name.to_vec()).or_insert_with(|| {
+                symbols.push(DuplicateSymbol {
+                    name: name.to_vec(),
+                    definitions: Vec::new(),
+                });
+                symbols.len() - 1
+            });
+            let definitions = &mut symbols[symbol_index].definitions;
+            if definitions.last().map(|definition| definition.index) != Some(index) {
+                definitions.push(SymbolDefinition {
+                    index,
+                    member_name: member.member_name.clone(),
+                });
+            }
+            Ok(())
+        })?;
+    }
+    symbols.retain(|symbol| symbol.definitions.len() > 1);
+    Ok(symbols)
+}
+
+/// Checks that no symbol is defined by more than one of `members`, and
+/// otherwise returns an error that names the symbols and the members that
+/// define them. `include_weak` is as for [find_duplicate_symbols].
+pub fn check_duplicate_symbols(
+    members: &[NewArchiveMember<'_>],
+    include_weak: bool,
+) -> io::Result<()> {
+    let duplicates = find_duplicate_symbols(members, include_weak)?;
+    if duplicates.is_empty() {
+        return Ok(());
+    }
+    let descriptions = duplicates
+        .iter()
+        .map(|symbol| {
+            let definitions = symbol
+                .definitions
+                .iter()
+                .map(|definition| format!("{} at {}", definition.member_name, definition.index))
+                .collect::<Vec<_>>();
+            format!(
+                "{} ({})",
+                String::from_utf8_lossy(&symbol.name),
+                definitions.join(", ")
+            )
+        })
+        .collect::<Vec<_>>();
+    Err(Error::new(
+        ErrorKind::InvalidInput,
+        format!(
+            "symbols are defined by more than one archive member: {}",
+            descriptions.join("; ")
+        ),
+    ))
+}
@@ -6,17 +6,72 @@
 
 mod alignment;
 mod archive;
+mod archive_diff;
+mod archive_inspection;
 mod archive_writer;
+mod big_archive_editor;
 mod coff;
 mod coff_import_file;
+mod coff_module_definition;
+mod debug_stripping;
+mod decoration;
+mod duplicate_members;
+mod duplicate_symbols;
+mod macho_universal_writer;
 mod mangler;
 mod math_extras;
+mod member_selection;
+mod microsoft_demangle;
+mod mri_script;
+mod nested_archive;
+mod object_editing;
+mod object_merging;
+mod object_mode;
 mod object_reader;
+mod symbol_localization;
+mod symbol_renaming;
+mod thin_archive;
 
 pub use archive::ArchiveKind;
-pub use archive_writer::{write_archive_to_stream, NewArchiveMember};
+pub use archive_diff::{diff_archives, ArchiveDifference, MemberField};
+pub use archive_inspection::{
+    inspect_archive, ArchiveLayout, MemberLayout, SpecialMember, SpecialMemberKind,
+};
+pub use archive_writer::{
+    write_archive_to_stream, write_archive_to_stream_with_options, NewArchiveMember,
+    WriteArchiveOptions,
+};
+pub use big_archive_editor::{append_to_big_archive, remove_from_big_archive};
 pub use coff::MachineTypes;
-pub use coff_import_file::{write_import_library, COFFShortExport};
+pub use coff_import_file::{
+    merge_import_libraries, read_import_library, validate_exports, write_import_library,
+    write_import_library_with_options, COFFShortExport, ImportHints, ImportLibrary,
+    ImportLibraryOptions,
+};
+pub use coff_module_definition::write_module_definition;
+pub use debug_stripping::strip_debug_sections;
+pub use duplicate_members::{
+    find_duplicate_members, remove_duplicate_members, DuplicateMember, DuplicateMembers,
+};
+pub use duplicate_symbols::{
+    check_duplicate_symbols, find_duplicate_symbols, DuplicateSymbol, SymbolDefinition,
+};
+pub use macho_universal_writer::{
+    read_universal_binary, write_universal_archive, write_universal_binary, FatMembers,
+    UniversalSlice,
+};
+pub use member_selection::{
+    select_archive_members, select_members, MemberSelection, SelectedMember,
+};
+pub use microsoft_demangle::microsoft_demangle;
+pub use mri_script::{execute_mri_script, run_mri_script, MriArchive};
+pub use nested_archive::flatten_archive_members;
+pub use object_merging::merge_archive_members;
+pub use object_mode::{check_object_mode, filter_by_object_mode, ObjectMode};
+pub use object_reader::get_native_object_undefined_symbols;
+pub use symbol_localization::localize_archive_symbols;
+pub use symbol_renaming::{prefix_archive_symbols, redefine_archive_symbols};
+pub use thin_archive::{compute_archive_relative_path, read_thin_archive_members};
 
 pub type GetSymbolsFn =
     fn(buf: &[u8], f: &mut dyn FnMut(&[u8]) -> std::io::Result<()>) -> std::io::Result<bool>;
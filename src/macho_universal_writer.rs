@@ -0,0 +1,328 @@
+// Derived from code in LLVM, which is:
+// Part of the LLVM Project, under the Apache License v2.0 with LLVM Exceptions.
+// See https://llvm.org/LICENSE.txt for license information.
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+//! Writer and reader for Mach-O universal ("fat") binaries, which contain one
+//! slice per architecture.
+
+use std::collections::HashSet;
+use std::io::{self, Cursor, Error, ErrorKind, Write};
+use std::mem::size_of;
+
+use object::macho::{self, FatArch32, FatArch64, MachHeader32, MachHeader64};
+use object::read::macho::{FatArch, MachHeader, MachOFatFile};
+use object::{Endianness, FileKind};
+
+use crate::{write_archive_to_stream, ArchiveKind, NewArchiveMember};
+
+/// The largest alignment of a section in a Mach-O file, as a power of two.
+const MAX_SECTION_ALIGNMENT: u32 = 15;
+
+/// A slice of a Mach-O universal binary.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UniversalSlice<'a> {
+    /// The `CPU_TYPE_*` of the slice.
+    pub cpu_type: u32,
+    /// The `CPU_SUBTYPE_*` of the slice.
+    pub cpu_subtype: u32,
+    /// The alignment of the slice within the universal binary, as a power of
+    /// two.
+    pub p2_alignment: u32,
+    /// The contents of the slice: an object file, dylib or archive.
+    pub data: &'a [u8],
+}
+
+/// How [crate::write_archive_to_stream_with_options] handles archive members
+/// that are Mach-O universal binaries, which can't be read as a single object
+/// file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FatMembers {
+    /// Add the member without any symbols, as LLVM does.
+    #[default]
+    Ignore,
+    /// Fail to write the archive.
+    Reject,
+    /// Read the symbols of the member from the slice for the given
+    /// architecture, and fail to write the archive if there is no such slice.
+    /// The capability bits of the CPU subtypes are ignored.
+    Slice { cpu_type: u32, cpu_subtype: u32 },
+}
+
+fn invalid_input(message: String) -> Error {
+    Error::new(ErrorKind::InvalidInput, message)
+}
+
+/// Returns the CPU type and subtype of a Mach-O object, and whether it is
+/// 64-bit.
+fn get_macho_cpu_type(buf: &[u8]) -> object::read::Result<(u32, u32, bool)> {
+    if let Ok(header) = MachHeader32::<Endianness>::parse(buf, 0) {
+        let endian = header.endian()?;
+        return Ok((header.cputype(endian), header.cpusubtype(endian), false));
+    }
+    let header = MachHeader64::<Endianness>::parse(buf, 0)?;
+    let endian = header.endian()?;
+    Ok((header.cputype(endian), header.cpusubtype(endian), true))
+}
+
+pub(crate) fn is_bitcode(buf: &[u8]) -> bool {
+    buf.starts_with(b"BC\xc0\xde") || buf.starts_with(&[0xde, 0xc0, 0x17, 0x0b])
+}
+
+// Derived from Slice::create(const Archive &A, LLVMContext *LLVMCtx).
+/// Determines the CPU type of an archive from its members, which must all
+/// have the same CPU type and subtype.
+fn get_archive_slice_cpu_type(members: &[NewArchiveMember<'_>]) -> io::Result<(u32, u32, u32)> {
+    let mut cpu_type = None;
+    for member in members {
+        let buf = (*member.buf).as_ref();
+        let member_cpu_type = match FileKind::parse(buf) {
+            Ok(FileKind::MachO32 | FileKind::MachO64) => {
+                get_macho_cpu_type(buf).map_err(Error::other)?
+            }
+            Ok(FileKind::MachOFat32 | FileKind::MachOFat64) => {
+                return Err(invalid_input(format!(
+                    "archive member {} is a fat file (not allowed in an archive)",
+                    member.member_name
+                )));
+            }
+            // The architecture of bitcode can't be checked here, so leave that
+            // to the linker.
+            _ if is_bitcode(buf) => continue,
+            _ => {
+                return Err(invalid_input(format!(
+                    "archive member {} is neither a MachO file or an LLVM IR file (not allowed in an archive)",
+                    member.member_name
+                )));
+            }
+        };
+        match cpu_type {
+            None => cpu_type = Some(member_cpu_type),
+            Some((cpu_type, cpu_subtype, _))
+                if (cpu_type, cpu_subtype) != (member_cpu_type.0, member_cpu_type.1) =>
+            {
+                return Err(invalid_input(format!(
+                    "archive member {} cputype ({}) and cpusubtype({}) does not match previous archive members cputype ({cpu_type}) and cpusubtype({cpu_subtype}) (all members must match)",
+                    member.member_name, member_cpu_type.0, member_cpu_type.1
+                )));
+            }
+            Some(_) => {}
+        }
+    }
+
+    let (cpu_type, cpu_subtype, is_64_bit) = cpu_type.ok_or_else(|| {
+        invalid_input("could not determine cpu type and subtype for archive".to_string())
+    })?;
+    Ok((cpu_type, cpu_subtype, if is_64_bit { 3 } else { 2 }))
+}
+
+/// Writes a universal binary that contains `slices`, in the given order.
+///
+/// A 64-bit fat header is used only if a slice doesn't fit within the 4GB
+/// offsets of the regular header.
+pub fn write_universal_binary<W: Write>(
+    w: &mut W,
+    slices: &[UniversalSlice<'_>],
+) -> io::Result<()> {
+    let mut seen = HashSet::new();
+    for slice in slices {
+        if slice.p2_alignment > MAX_SECTION_ALIGNMENT {
+            return Err(invalid_input(format!(
+                "alignment 2^{} of slice with cputype ({}) is larger than 2^{MAX_SECTION_ALIGNMENT}",
+                slice.p2_alignment, slice.cpu_type
+            )));
+        }
+        if !seen.insert((slice.cpu_type, slice.cpu_subtype)) {
+            return Err(invalid_input(format!(
+                "slices with cputype ({}) and cpusubtype ({}) have the same architecture",
+                slice.cpu_type, slice.cpu_subtype
+            )));
+        }
+    }
+    let nfat_arch = u32::try_from(slices.len()).map_err(Error::other)?;
+
+    let header_size = |fat_arch_size: usize| {
+        (size_of::<macho::FatHeader>() + fat_arch_size * slices.len()) as u64
+    };
+    let build_offsets = |header_size: u64| {
+        let mut offset = header_size;
+        slices
+            .iter()
+            .map(|slice| {
+                offset = offset.next_multiple_of(1 << slice.p2_alignment);
+                let slice_offset = offset;
+                offset += slice.data.len() as u64;
+                (slice_offset, offset)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut pos = header_size(size_of::<FatArch32>());
+    let mut offsets = build_offsets(pos);
+    let is_fat64 = offsets
+        .last()
+        .is_some_and(|&(_, end)| end > u64::from(u32::MAX));
+    if is_fat64 {
+        pos = header_size(size_of::<FatArch64>());
+        offsets = build_offsets(pos);
+    }
+
+    let magic = if is_fat64 {
+        macho::FAT_MAGIC_64
+    } else {
+        macho::FAT_MAGIC
+    };
+    w.write_all(&magic.to_be_bytes())?;
+    w.write_all(&nfat_arch.to_be_bytes())?;
+
+    for (slice, &(offset, end)) in slices.iter().zip(&offsets) {
+        let size = end - offset;
+        w.write_all(&slice.cpu_type.to_be_bytes())?;
+        w.write_all(&slice.cpu_subtype.to_be_bytes())?;
+        if is_fat64 {
+            w.write_all(&offset.to_be_bytes())?;
+            w.write_all(&size.to_be_bytes())?;
+            w.write_all(&slice.p2_alignment.to_be_bytes())?;
+            // reserved
+            w.write_all(&0u32.to_be_bytes())?;
+        } else {
+            // Both fit, as the slices end in increasing order.
+            w.write_all(&(offset as u32).to_be_bytes())?;
+            w.write_all(&(size as u32).to_be_bytes())?;
+            w.write_all(&slice.p2_alignment.to_be_bytes())?;
+        }
+    }
+
+    for (slice, &(offset, end)) in slices.iter().zip(&offsets) {
+        // The padding is less than the largest alignment.
+        w.write_all(&vec![0; (offset - pos) as usize])?;
+        w.write_all(slice.data)?;
+        pos = end;
+    }
+
+    w.flush()
+}
+
+/// Writes a universal static library with one Darwin archive per element of
+/// `slices`, as `lipo -create` does for per-architecture archives.
+///
+/// The CPU type and subtype of each archive are taken from its Mach-O
+/// members, which must all agree. Slices are ordered like `lipo` orders them.
+pub fn write_universal_archive<'a, W: Write>(
+    w: &mut W,
+    slices: &[&'a [NewArchiveMember<'a>]],
+) -> io::Result<()> {
+    let mut archives = Vec::with_capacity(slices.len());
+    for members in slices {
+        let (cpu_type, cpu_subtype, p2_alignment) = get_archive_slice_cpu_type(members)?;
+        let mut archive = Cursor::new(Vec::new());
+        write_archive_to_stream(&mut archive, members, ArchiveKind::Darwin, false, false)?;
+        archives.push((cpu_type, cpu_subtype, p2_alignment, archive.into_inner()));
+    }
+
+    let mut universal_slices = archives
+        .iter()
+        .map(
+            |(cpu_type, cpu_subtype, p2_alignment, data)| UniversalSlice {
+                cpu_type: *cpu_type,
+                cpu_subtype: *cpu_subtype,
+                p2_alignment: *p2_alignment,
+                data,
+            },
+        )
+        .collect::<Vec<_>>();
+
+    // Sort slices by alignment, per cctools lipo, but force the arm64 family
+    // to follow all other slices for compatibility with it.
+    universal_slices.sort_by(|lhs, rhs| {
+        if lhs.cpu_type == rhs.cpu_type {
+            return lhs.cpu_subtype.cmp(&rhs.cpu_subtype);
+        }
+        let is_arm64 = |slice: &UniversalSlice<'_>| slice.cpu_type == macho::CPU_TYPE_ARM64;
+        is_arm64(lhs)
+            .cmp(&is_arm64(rhs))
+            .then(lhs.p2_alignment.cmp(&rhs.p2_alignment))
+    });
+
+    write_universal_binary(w, &universal_slices)
+}
+
+fn read_slices<'a, Fat: FatArch>(buf: &'a [u8]) -> object::read::Result<Vec<UniversalSlice<'a>>> {
+    let file = MachOFatFile::<Fat>::parse(buf)?;
+    file.arches()
+        .iter()
+        .map(|arch| {
+            Ok(UniversalSlice {
+                cpu_type: arch.cputype(),
+                cpu_subtype: arch.cpusubtype(),
+                p2_alignment: arch.align(),
+                data: arch.data(buf)?,
+            })
+        })
+        .collect()
+}
+
+/// Returns the data of `buf` that the symbols of an archive member are read
+/// from, which is the selected slice if `buf` is a universal binary.
+pub(crate) fn get_member_symbolic_data<'a>(
+    buf: &'a [u8],
+    fat_members: FatMembers,
+    member_name: &str,
+) -> io::Result<&'a [u8]> {
+    if !matches!(
+        FileKind::parse(buf),
+        Ok(FileKind::MachOFat32 | FileKind::MachOFat64)
+    ) {
+        return Ok(buf);
+    }
+
+    match fat_members {
+        FatMembers::Ignore => Ok(buf),
+        FatMembers::Reject => Err(invalid_input(format!(
+            "archive member {member_name} is a fat file (not allowed in an archive)"
+        ))),
+        FatMembers::Slice {
+            cpu_type,
+            cpu_subtype,
+        } => {
+            let slices = read_universal_binary(buf).map_err(|err| {
+                Error::new(
+                    err.kind(),
+                    format!("archive member {member_name} is not a valid fat file: {err}"),
+                )
+            })?;
+            slices
+                .iter()
+                .find(|slice| {
+                    slice.cpu_type == cpu_type
+                        && slice.cpu_subtype & !macho::CPU_SUBTYPE_MASK
+                            == cpu_subtype & !macho::CPU_SUBTYPE_MASK
+                })
+                .map(|slice| slice.data)
+                .ok_or_else(|| {
+                    invalid_input(format!(
+                        "archive member {member_name} is a fat file without a slice for cputype ({cpu_type}) and cpusubtype ({cpu_subtype})"
+                    ))
+                })
+        }
+    }
+}
+
+/// Reads the slices of a universal binary, such as one written by
+/// [write_universal_archive].
+pub fn read_universal_binary(buf: &[u8]) -> io::Result<Vec<UniversalSlice<'_>>> {
+    let magic = buf
+        .get(..4)
+        .map(|magic| u32::from_be_bytes(magic.try_into().unwrap()));
+    match magic {
+        Some(macho::FAT_MAGIC) => read_slices::<FatArch32>(buf),
+        Some(macho::FAT_MAGIC_64) => read_slices::<FatArch64>(buf),
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "not a Mach-O universal binary",
+            ))
+        }
+    }
+    .map_err(|err| Error::new(ErrorKind::InvalidData, err.to_string()))
+}
@@ -3,47 +3,90 @@
 // See https://llvm.org/LICENSE.txt for license information.
 // SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
 
-pub fn get_arm64ec_mangled_function_name(name: &str) -> Option<String> {
-    let first_char = name.chars().next().unwrap();
-    let is_cpp_fn = first_char == '?';
-    if is_cpp_fn && name.contains("$$h") {
-        return None;
-    }
-    if !is_cpp_fn && first_char == '#' {
-        return None;
+use std::io::{Error, ErrorKind, Result};
+use std::str::from_utf8;
+
+use crate::microsoft_demangle::get_arm64ec_insertion_point_in_mangled_name;
+
+const ARM64EC_TAG: &[u8] = b"$$h";
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn first_char(name: &[u8]) -> Result<u8> {
+    name.first().copied().ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            "ARM64EC function names cannot be empty",
+        )
+    })
+}
+
+/// Guesses where `$$h` goes in a C++ name that the demangler can't parse,
+/// as LLVM did before it used the demangler.
+fn get_legacy_insertion_point(name: &[u8]) -> usize {
+    match find(name, b"@@") {
+        Some(two_at_signs_idx) if Some(two_at_signs_idx) != find(name, b"@@@") => {
+            two_at_signs_idx + 2
+        }
+        _ => name
+            .iter()
+            .position(|&c| c == b'@')
+            .map_or(name.len(), |idx| idx + 1),
     }
+}
 
-    let mut prefix = "$$h";
-    let insert_idx = if is_cpp_fn {
-        match name.find("@@") {
-            Some(two_at_signs_idx) if Some(two_at_signs_idx) != name.find("@@@") => {
-                two_at_signs_idx + 2
-            }
-            _ => name.find('@').map_or(name.len(), |idx| idx + 1),
+/// Mangled names are ASCII, but an arbitrary name may not even be UTF-8.
+fn get_insertion_point(name: &[u8]) -> Option<usize> {
+    get_arm64ec_insertion_point_in_mangled_name(from_utf8(name).ok()?)
+}
+
+pub fn get_arm64ec_mangled_function_name(name: &[u8]) -> Result<Option<Vec<u8>>> {
+    let is_cpp_fn = first_char(name)? == b'?';
+    if !is_cpp_fn {
+        if name[0] == b'#' {
+            return Ok(None);
         }
-    } else {
-        prefix = "#";
-        0
-    };
-
-    Some(format!(
-        "{}{prefix}{}",
-        &name[..insert_idx],
-        &name[insert_idx..]
+        return Ok(Some([b"#", name].concat()));
+    }
+
+    // If the name contains $$h, then it is already mangled.
+    if find(name, ARM64EC_TAG).is_some() {
+        return Ok(None);
+    }
+
+    // Ask the demangler where we should insert "$$h".
+    let insert_idx = get_insertion_point(name).unwrap_or_else(|| get_legacy_insertion_point(name));
+
+    Ok(Some(
+        [&name[..insert_idx], ARM64EC_TAG, &name[insert_idx..]].concat(),
     ))
 }
 
-pub fn get_arm64ec_demangled_function_name(name: &str) -> Option<String> {
-    let first_char = name.chars().next().unwrap();
-    if first_char == '#' {
-        return Some(name[1..].to_string());
-    }
-    if first_char != '?' {
-        return None;
+pub fn get_arm64ec_demangled_function_name(name: &[u8]) -> Result<Option<Vec<u8>>> {
+    match first_char(name)? {
+        b'#' => return Ok(Some(name[1..].to_vec())),
+        b'?' => {}
+        _ => return Ok(None),
     }
 
-    match name.split_once("$$h") {
-        Some((first, second)) if !second.is_empty() => Some(format!("{first}{second}")),
-        _ => None,
+    // Drop the ARM64EC "$$h" tag. A name can contain "$$h" elsewhere (e.g. in
+    // a template argument), so prefer the one at the insertion point.
+    let mut fallback = None;
+    let mut start = 0;
+    while let Some(idx) = find(&name[start..], ARM64EC_TAG).map(|idx| start + idx) {
+        start = idx + ARM64EC_TAG.len();
+        if start == name.len() {
+            break;
+        }
+        let demangled = [&name[..idx], &name[start..]].concat();
+        if get_insertion_point(&demangled) == Some(idx) {
+            return Ok(Some(demangled));
+        }
+        fallback.get_or_insert(demangled);
     }
+    Ok(fallback)
 }
@@ -0,0 +1,153 @@
+//! Simulation of how a linker selects the members of a static library that
+//! define the symbols that are referenced, to find why each member is linked.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{self, Error, ErrorKind};
+
+use crate::macho_universal_writer::{get_member_symbolic_data, FatMembers};
+use crate::nested_archive::is_archive;
+use crate::{flatten_archive_members, GetSymbolsFn, NewArchiveMember, ObjectReader};
+
+/// A member that a linker would include.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SelectedMember {
+    /// The index of the member in the members of the archive.
+    pub index: usize,
+    /// The name of the member, as in [NewArchiveMember::member_name].
+    pub member_name: String,
+    /// The symbol that caused the member to be included, which is the first
+    /// reference to a symbol that the member defines.
+    pub symbol: Vec<u8>,
+    /// The index in [MemberSelection::selected] of the member that references
+    /// `symbol`, or `None` if `symbol` is one of the roots.
+    pub referenced_by: Option<usize>,
+}
+
+/// The result of [select_members].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemberSelection {
+    /// The included members, in the order that they are included.
+    pub selected: Vec<SelectedMember>,
+    /// The referenced symbols that no member defines, in the order that they
+    /// are first referenced.
+    pub unresolved: Vec<Vec<u8>>,
+}
+
+struct MemberSymbols {
+    defined: Vec<Vec<u8>>,
+    undefined: Vec<Vec<u8>>,
+}
+
+/// Reads the symbols that `member` defines and references.
+fn get_member_symbols(
+    member: &NewArchiveMember<'_>,
+    get_undefined_symbols: GetSymbolsFn,
+) -> io::Result<MemberSymbols> {
+    let object_reader = member.object_reader;
+    let buf = get_member_symbolic_data(
+        (*member.buf).as_ref(),
+        FatMembers::Ignore,
+        &member.member_name,
+    )?;
+    let mut defined = Vec::new();
+    (object_reader.get_symbols)(buf, &mut |name| {
+        defined.push(name.to_vec());
+        Ok(())
+    })?;
+    let mut undefined = Vec::new();
+    get_undefined_symbols(buf, &mut |name| {
+        undefined.push(name.to_vec());
+        Ok(())
+    })?;
+    Ok(MemberSymbols { defined, undefined })
+}
+
+/// Returns the members that a linker would include from an archive with
+/// `members` to resolve the undefined symbols `roots`.
+///
+/// Like a linker, the first member that defines a symbol in the archive symbol
+/// table is included for each undefined symbol, and the undefined symbols of
+/// included members are resolved in turn until no new members are included.
+///
+/// The defined symbols are read with the [ObjectReader] of each member, and
+/// the undefined symbols with `get_undefined_symbols`, such as
+/// [crate::get_native_object_undefined_symbols].
+pub fn select_members(
+    members: &[NewArchiveMember<'_>],
+    get_undefined_symbols: GetSymbolsFn,
+    roots: &[&[u8]],
+) -> io::Result<MemberSelection> {
+    let mut symbol_index = HashMap::new();
+    let mut member_symbols = Vec::with_capacity(members.len());
+    for (index, member) in members.iter().enumerate() {
+        let symbols = get_member_symbols(member, get_undefined_symbols)?;
+        for name in &symbols.defined {
+            symbol_index.entry(name.clone()).or_insert(index);
+        }
+        member_symbols.push(symbols);
+    }
+
+    let mut selection = MemberSelection::default();
+    let mut is_selected = vec![false; members.len()];
+    let mut defined = HashSet::new();
+    let mut unresolved = HashSet::new();
+    let mut worklist = roots
+        .iter()
+        .map(|root| (root.to_vec(), None))
+        .collect::<VecDeque<_>>();
+    while let Some((symbol, referenced_by)) = worklist.pop_front() {
+        if defined.contains(&symbol) {
+            continue;
+        }
+        let Some(&index) = symbol_index.get(&symbol) else {
+            if unresolved.insert(symbol.clone()) {
+                selection.unresolved.push(symbol);
+            }
+            continue;
+        };
+        if is_selected[index] {
+            continue;
+        }
+        is_selected[index] = true;
+        let symbols = &member_symbols[index];
+        defined.extend(symbols.defined.iter().cloned());
+        let selected_index = selection.selected.len();
+        worklist.extend(
+            symbols
+                .undefined
+                .iter()
+                .map(|name| (name.clone(), Some(selected_index))),
+        );
+        selection.selected.push(SelectedMember {
+            index,
+            member_name: members[index].member_name.clone(),
+            symbol,
+            referenced_by,
+        });
+    }
+    Ok(selection)
+}
+
+/// Returns the members that a linker would include from `archive` to resolve
+/// the undefined symbols `roots`, as [select_members] does.
+///
+/// Archives that are members of `archive` are flattened with
+/// [flatten_archive_members], and the indices of the selected members are
+/// indices into the flattened members. The members of thin archives are read
+/// relative to the current directory.
+pub fn select_archive_members(
+    archive: &[u8],
+    object_reader: &'static ObjectReader,
+    get_undefined_symbols: GetSymbolsFn,
+    roots: &[&[u8]],
+) -> io::Result<MemberSelection> {
+    if !is_archive(archive) {
+        return Err(Error::new(ErrorKind::InvalidInput, "not an archive"));
+    }
+    let members = flatten_archive_members(vec![NewArchiveMember::new(
+        archive,
+        object_reader,
+        String::new(),
+    )])?;
+    select_members(&members, get_undefined_symbols, roots)
+}
@@ -0,0 +1,1593 @@
+// Derived from code in LLVM, which is:
+// Part of the LLVM Project, under the Apache License v2.0 with LLVM Exceptions.
+// See https://llvm.org/LICENSE.txt for license information.
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+//! Parser for MSVC-style C++ mangled names, used to find where ARM64EC
+//! mangling inserts `$$h` and to demangle names for diagnostics.
+
+use std::mem;
+
+const Q_CONST: u8 = 1 << 0;
+const Q_VOLATILE: u8 = 1 << 1;
+const Q_RESTRICT: u8 = 1 << 2;
+const Q_UNALIGNED: u8 = 1 << 3;
+const Q_POINTER64: u8 = 1 << 4;
+
+const FC_PUBLIC: u16 = 1 << 0;
+const FC_PROTECTED: u16 = 1 << 1;
+const FC_PRIVATE: u16 = 1 << 2;
+const FC_GLOBAL: u16 = 1 << 3;
+const FC_STATIC: u16 = 1 << 4;
+const FC_VIRTUAL: u16 = 1 << 5;
+const FC_FAR: u16 = 1 << 6;
+const FC_EXTERN_C: u16 = 1 << 7;
+const FC_NO_PARAMETER_LIST: u16 = 1 << 8;
+const FC_VIRTUAL_THIS_ADJUST: u16 = 1 << 9;
+const FC_VIRTUAL_THIS_ADJUST_EX: u16 = 1 << 10;
+const FC_STATIC_THIS_ADJUST: u16 = 1 << 11;
+
+/// Names that can be referred to by a single digit.
+const MAX_BACKREFS: usize = 10;
+
+/// How deeply types, template instantiations and symbols can be nested before
+/// a name is rejected, so that malicious names can't overflow the stack.
+const MAX_DEPTH: usize = 256;
+
+#[derive(Clone, Debug)]
+enum IdentifierKind {
+    Named(String),
+    Structor { is_destructor: bool },
+    ConversionOperator { target: Option<Box<Type>> },
+}
+
+#[derive(Clone, Debug)]
+struct Identifier {
+    kind: IdentifierKind,
+    template_args: Option<Vec<TemplateArg>>,
+}
+
+impl Identifier {
+    fn named(name: impl Into<String>) -> Self {
+        Identifier {
+            kind: IdentifierKind::Named(name.into()),
+            template_args: None,
+        }
+    }
+}
+
+/// The components of a name, from the outermost scope to the unqualified
+/// name.
+type QualifiedName = Vec<Identifier>;
+
+#[derive(Clone, Debug)]
+enum TemplateArg {
+    Type(Type),
+    Integer {
+        value: u64,
+        is_negative: bool,
+    },
+    Symbol {
+        symbol: Option<Box<Symbol>>,
+        is_pointer: bool,
+        offsets: Vec<i64>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PointerAffinity {
+    Pointer,
+    Reference,
+    RValueReference,
+}
+
+#[derive(Clone, Debug)]
+enum TypeKind {
+    Primitive(&'static str),
+    Tag {
+        tag: &'static str,
+        name: QualifiedName,
+    },
+    Custom(Identifier),
+    Pointer {
+        affinity: PointerAffinity,
+        class_parent: Option<QualifiedName>,
+        pointee: Box<Type>,
+    },
+    Array {
+        dimensions: Vec<u64>,
+        element: Box<Type>,
+    },
+    Function(Box<FunctionSignature>),
+}
+
+#[derive(Clone, Debug)]
+struct Type {
+    kind: TypeKind,
+    quals: u8,
+}
+
+impl Type {
+    fn new(kind: TypeKind) -> Self {
+        Type { kind, quals: 0 }
+    }
+
+    fn add_quals(&mut self, quals: u8) {
+        match &mut self.kind {
+            TypeKind::Function(signature) => signature.quals |= quals,
+            _ => self.quals |= quals,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct ThisAdjustment {
+    static_offset: i32,
+    vbptr_offset: i32,
+    vboffset_offset: i32,
+    vtordisp_offset: i32,
+}
+
+#[derive(Clone, Debug)]
+struct FunctionSignature {
+    function_class: u16,
+    quals: u8,
+    ref_qualifier: Option<&'static str>,
+    calling_convention: &'static str,
+    return_type: Option<Type>,
+    params: Vec<Type>,
+    is_variadic: bool,
+    is_noexcept: bool,
+    this_adjustment: Option<ThisAdjustment>,
+}
+
+impl Default for FunctionSignature {
+    fn default() -> Self {
+        FunctionSignature {
+            function_class: FC_GLOBAL,
+            quals: 0,
+            ref_qualifier: None,
+            calling_convention: "",
+            return_type: None,
+            params: Vec::new(),
+            is_variadic: false,
+            is_noexcept: false,
+            this_adjustment: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Symbol {
+    Function {
+        name: QualifiedName,
+        signature: FunctionSignature,
+    },
+    Variable {
+        name: QualifiedName,
+        storage_class: u8,
+        ty: Type,
+    },
+    SpecialTable {
+        name: QualifiedName,
+        quals: u8,
+        target: Option<QualifiedName>,
+    },
+    StringLiteral,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum QualifierMangleMode {
+    Drop,
+    Mangle,
+    Result,
+}
+
+fn consume(s: &mut &str, prefix: &str) -> bool {
+    match s.strip_prefix(prefix) {
+        Some(rest) => {
+            *s = rest;
+            true
+        }
+        None => false,
+    }
+}
+
+fn consume_char(s: &mut &str, c: char) -> bool {
+    match s.strip_prefix(c) {
+        Some(rest) => {
+            *s = rest;
+            true
+        }
+        None => false,
+    }
+}
+
+fn take_char(s: &mut &str) -> Option<char> {
+    let c = s.chars().next()?;
+    *s = &s[c.len_utf8()..];
+    Some(c)
+}
+
+fn starts_with_digit(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// Returns true for `?<number>?`, which starts a name in the scope of a
+/// function.
+fn starts_with_local_scope_pattern(s: &str) -> bool {
+    let Some(s) = s.strip_prefix('?') else {
+        return false;
+    };
+    let Some(end) = s.find('?') else {
+        return false;
+    };
+    let candidate = &s.as_bytes()[..end];
+    match candidate {
+        [] => false,
+        // ?@? is the discriminator 0.
+        [c] => *c == b'@' || c.is_ascii_digit(),
+        // An encoded number starts with B-P and all subsequent digits are in
+        // A-P.
+        [first, rest @ .., b'@'] => {
+            (b'B'..=b'P').contains(first) && rest.iter().all(|c| (b'A'..=b'P').contains(c))
+        }
+        _ => false,
+    }
+}
+
+fn intrinsic_function_name(c: char) -> Option<&'static str> {
+    Some(match c {
+        '2' => "operator new",
+        '3' => "operator delete",
+        '4' => "operator=",
+        '5' => "operator>>",
+        '6' => "operator<<",
+        '7' => "operator!",
+        '8' => "operator==",
+        '9' => "operator!=",
+        'A' => "operator[]",
+        'C' => "operator->",
+        'D' => "operator*",
+        'E' => "operator++",
+        'F' => "operator--",
+        'G' => "operator-",
+        'H' => "operator+",
+        'I' => "operator&",
+        'J' => "operator->*",
+        'K' => "operator/",
+        'L' => "operator%",
+        'M' => "operator<",
+        'N' => "operator<=",
+        'O' => "operator>",
+        'P' => "operator>=",
+        'Q' => "operator,",
+        'R' => "operator()",
+        'S' => "operator~",
+        'T' => "operator^",
+        'U' => "operator|",
+        'V' => "operator&&",
+        'W' => "operator||",
+        'X' => "operator*=",
+        'Y' => "operator+=",
+        'Z' => "operator-=",
+        _ => return None,
+    })
+}
+
+fn under_intrinsic_function_name(c: char) -> Option<&'static str> {
+    Some(match c {
+        '0' => "operator/=",
+        '1' => "operator%=",
+        '2' => "operator>>=",
+        '3' => "operator<<=",
+        '4' => "operator&=",
+        '5' => "operator|=",
+        '6' => "operator^=",
+        '7' => "`vftable'",
+        '8' => "`vbtable'",
+        '9' => "`vcall'",
+        'A' => "`typeof'",
+        'B' => "`local static guard'",
+        'C' => "`string'",
+        'D' => "`vbase dtor'",
+        'E' => "`vector deleting dtor'",
+        'F' => "`default ctor closure'",
+        'G' => "`scalar deleting dtor'",
+        'H' => "`vector ctor iterator'",
+        'I' => "`vector dtor iterator'",
+        'J' => "`vector vbase ctor iterator'",
+        'K' => "`virtual displacement map'",
+        'L' => "`eh vector ctor iterator'",
+        'M' => "`eh vector dtor iterator'",
+        'N' => "`eh vector vbase ctor iterator'",
+        'O' => "`copy ctor closure'",
+        'S' => "`local vftable'",
+        'T' => "`local vftable ctor closure'",
+        'U' => "operator new[]",
+        'V' => "operator delete[]",
+        'X' => "`placement delete closure'",
+        'Y' => "`placement delete[] closure'",
+        _ => return None,
+    })
+}
+
+fn double_under_intrinsic_function_name(c: char) -> Option<&'static str> {
+    Some(match c {
+        'A' => "`managed vector ctor iterator'",
+        'B' => "`managed vector dtor iterator'",
+        'C' => "`EH vector copy ctor iterator'",
+        'D' => "`EH vector vbase copy ctor iterator'",
+        'E' => "`dynamic initializer'",
+        'F' => "`dynamic atexit destructor'",
+        'G' => "`vector copy ctor iterator'",
+        'H' => "`vector vbase copy constructor iterator'",
+        'I' => "`managed vector vbase copy constructor iterator'",
+        'J' => "`local static thread guard'",
+        'L' => "operator co_await",
+        'M' => "operator<=>",
+        _ => return None,
+    })
+}
+
+#[derive(Default)]
+struct Backrefs {
+    names: Vec<String>,
+    function_params: Vec<Type>,
+}
+
+#[derive(Default)]
+struct Demangler {
+    backrefs: Backrefs,
+    depth: usize,
+}
+
+impl Demangler {
+    /// Runs `f` one level of nesting deeper, or fails if that exceeds
+    /// [MAX_DEPTH].
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> Option<T>) -> Option<T> {
+        if self.depth >= MAX_DEPTH {
+            return None;
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn memorize_string(&mut self, s: &str) {
+        if self.backrefs.names.len() < MAX_BACKREFS && !self.backrefs.names.iter().any(|n| n == s) {
+            self.backrefs.names.push(s.to_string());
+        }
+    }
+
+    fn memorize_identifier(&mut self, identifier: &Identifier) {
+        // Render the name so that it can be referred to as a simple name.
+        let mut name = String::new();
+        output_identifier(&mut name, std::slice::from_ref(identifier), 0);
+        self.memorize_string(&name);
+    }
+
+    // <number> ::= [?] <non-negative integer>
+    //
+    // <non-negative integer> ::= <decimal digit> # when 1 <= Number <= 10
+    //                        ::= <hex digit>+ @  # when Number == 0 or >= 10
+    //
+    // <hex-digit>            ::= [A-P]           # A = 0, B = 1, ...
+    fn demangle_number(&mut self, s: &mut &str) -> Option<(u64, bool)> {
+        let is_negative = consume_char(s, '?');
+        if starts_with_digit(s) {
+            let value = u64::from(s.as_bytes()[0] - b'0') + 1;
+            *s = &s[1..];
+            return Some((value, is_negative));
+        }
+
+        let mut value: u64 = 0;
+        for (i, c) in s.bytes().enumerate() {
+            match c {
+                b'@' => {
+                    *s = &s[i + 1..];
+                    return Some((value, is_negative));
+                }
+                b'A'..=b'P' => {
+                    value = value.checked_mul(16)? + u64::from(c - b'A');
+                }
+                _ => break,
+            }
+        }
+        None
+    }
+
+    fn demangle_signed(&mut self, s: &mut &str) -> Option<i64> {
+        let (value, is_negative) = self.demangle_number(s)?;
+        let value = i64::try_from(value).ok()?;
+        Some(if is_negative { -value } else { value })
+    }
+
+    fn demangle_simple_string<'a>(&mut self, s: &mut &'a str, memorize: bool) -> Option<&'a str> {
+        let end = s.find('@').filter(|&end| end != 0)?;
+        let name = &s[..end];
+        *s = &s[end + 1..];
+        if memorize {
+            self.memorize_string(name);
+        }
+        Some(name)
+    }
+
+    fn demangle_simple_name(&mut self, s: &mut &str, memorize: bool) -> Option<Identifier> {
+        self.demangle_simple_string(s, memorize)
+            .map(Identifier::named)
+    }
+
+    fn demangle_back_ref_name(&mut self, s: &mut &str) -> Option<Identifier> {
+        let index = usize::from(s.as_bytes()[0] - b'0');
+        let name = self.backrefs.names.get(index)?;
+        *s = &s[1..];
+        Some(Identifier::named(name.clone()))
+    }
+
+    fn demangle_anonymous_namespace_name(&mut self, s: &mut &str) -> Option<Identifier> {
+        consume(s, "?A");
+        let end = s.find('@')?;
+        let key = &s[..end];
+        self.memorize_string(key);
+        *s = &s[end + 1..];
+        Some(Identifier::named("`anonymous namespace'"))
+    }
+
+    fn demangle_locally_scoped_name_piece(&mut self, s: &mut &str) -> Option<Identifier> {
+        consume_char(s, '?');
+        let (number, _) = self.demangle_number(s)?;
+        // One ? to terminate the number.
+        if !consume_char(s, '?') {
+            return None;
+        }
+        let scope = self.parse(s)?;
+
+        let mut name = String::from("`");
+        output_symbol(&mut name, &scope);
+        name.push_str(&format!("'::`{number}'"));
+        Some(Identifier::named(name))
+    }
+
+    fn demangle_template_instantiation_name(
+        &mut self,
+        s: &mut &str,
+        memorize: bool,
+    ) -> Option<Identifier> {
+        self.nested(|this| this.demangle_template_instantiation_name_inner(s, memorize))
+    }
+
+    fn demangle_template_instantiation_name_inner(
+        &mut self,
+        s: &mut &str,
+        memorize: bool,
+    ) -> Option<Identifier> {
+        consume(s, "?$");
+
+        // Template instantiations have their own back-references.
+        let outer = mem::take(&mut self.backrefs);
+        let identifier = self.demangle_unqualified_symbol_name(s, true);
+        let template_args = identifier
+            .is_some()
+            .then(|| self.demangle_template_parameter_list(s))
+            .flatten();
+        self.backrefs = outer;
+
+        let mut identifier = identifier?;
+        identifier.template_args = Some(template_args?);
+        if memorize {
+            // Structors and conversion operators only make sense as the
+            // unqualified name of a symbol.
+            if !matches!(identifier.kind, IdentifierKind::Named(_)) {
+                return None;
+            }
+            self.memorize_identifier(&identifier);
+        }
+        Some(identifier)
+    }
+
+    fn demangle_function_identifier_code(&mut self, s: &mut &str) -> Option<Identifier> {
+        consume_char(s, '?');
+        let name = if consume(s, "__") {
+            match take_char(s)? {
+                'K' => {
+                    let name = self.demangle_simple_string(s, false)?;
+                    return Some(Identifier::named(format!("operator \"\"{name}")));
+                }
+                c => double_under_intrinsic_function_name(c)?,
+            }
+        } else if consume_char(s, '_') {
+            under_intrinsic_function_name(take_char(s)?)?
+        } else {
+            match take_char(s)? {
+                c @ ('0' | '1') => {
+                    return Some(Identifier {
+                        kind: IdentifierKind::Structor {
+                            is_destructor: c == '1',
+                        },
+                        template_args: None,
+                    });
+                }
+                'B' => {
+                    return Some(Identifier {
+                        kind: IdentifierKind::ConversionOperator { target: None },
+                        template_args: None,
+                    });
+                }
+                c => intrinsic_function_name(c)?,
+            }
+        };
+        Some(Identifier::named(name))
+    }
+
+    fn demangle_unqualified_symbol_name(
+        &mut self,
+        s: &mut &str,
+        memorize: bool,
+    ) -> Option<Identifier> {
+        if starts_with_digit(s) {
+            self.demangle_back_ref_name(s)
+        } else if s.starts_with("?$") {
+            self.demangle_template_instantiation_name(s, false)
+        } else if s.starts_with('?') {
+            self.demangle_function_identifier_code(s)
+        } else {
+            self.demangle_simple_name(s, memorize)
+        }
+    }
+
+    fn demangle_unqualified_type_name(&mut self, s: &mut &str) -> Option<Identifier> {
+        // An inner-most name can be a back-reference, because a fully-qualified
+        // name (e.g. Scope + Inner) can contain other fully qualified names
+        // inside of them (for example template parameters), and these nested
+        // parameters can refer to previously mangled types.
+        if starts_with_digit(s) {
+            self.demangle_back_ref_name(s)
+        } else if s.starts_with("?$") {
+            self.demangle_template_instantiation_name(s, true)
+        } else {
+            self.demangle_simple_name(s, true)
+        }
+    }
+
+    fn demangle_name_scope_piece(&mut self, s: &mut &str) -> Option<Identifier> {
+        if starts_with_digit(s) {
+            self.demangle_back_ref_name(s)
+        } else if s.starts_with("?$") {
+            self.demangle_template_instantiation_name(s, true)
+        } else if s.starts_with("?A") {
+            self.demangle_anonymous_namespace_name(s)
+        } else if starts_with_local_scope_pattern(s) {
+            self.demangle_locally_scoped_name_piece(s)
+        } else {
+            self.demangle_simple_name(s, true)
+        }
+    }
+
+    fn demangle_name_scope_chain(
+        &mut self,
+        s: &mut &str,
+        unqualified: Identifier,
+    ) -> Option<QualifiedName> {
+        let mut name = vec![unqualified];
+        while !consume_char(s, '@') {
+            if s.is_empty() {
+                return None;
+            }
+            name.push(self.demangle_name_scope_piece(s)?);
+        }
+        name.reverse();
+        Some(name)
+    }
+
+    fn demangle_fully_qualified_type_name(&mut self, s: &mut &str) -> Option<QualifiedName> {
+        let identifier = self.demangle_unqualified_type_name(s)?;
+        self.demangle_name_scope_chain(s, identifier)
+    }
+
+    fn demangle_fully_qualified_symbol_name(&mut self, s: &mut &str) -> Option<QualifiedName> {
+        let identifier = self.demangle_unqualified_symbol_name(s, true)?;
+        let name = self.demangle_name_scope_chain(s, identifier)?;
+        // A constructor or destructor needs the name of its class.
+        if matches!(
+            name.last().map(|i| &i.kind),
+            Some(IdentifierKind::Structor { .. })
+        ) && name.len() < 2
+        {
+            return None;
+        }
+        Some(name)
+    }
+
+    fn demangle_pointer_ext_qualifiers(&mut self, s: &mut &str) -> u8 {
+        let mut quals = 0;
+        if consume_char(s, 'E') {
+            quals |= Q_POINTER64;
+        }
+        if consume_char(s, 'I') {
+            quals |= Q_RESTRICT;
+        }
+        if consume_char(s, 'F') {
+            quals |= Q_UNALIGNED;
+        }
+        quals
+    }
+
+    /// Returns the cv-qualifiers and whether they are for a member.
+    fn demangle_qualifiers(&mut self, s: &mut &str) -> Option<(u8, bool)> {
+        let quals = match take_char(s)? {
+            // Member qualifiers
+            'Q' => (0, true),
+            'R' => (Q_CONST, true),
+            'S' => (Q_VOLATILE, true),
+            'T' => (Q_CONST | Q_VOLATILE, true),
+            // Non-member qualifiers
+            'A' => (0, false),
+            'B' => (Q_CONST, false),
+            'C' => (Q_VOLATILE, false),
+            'D' => (Q_CONST | Q_VOLATILE, false),
+            _ => return None,
+        };
+        Some(quals)
+    }
+
+    fn demangle_calling_convention(&mut self, s: &mut &str) -> Option<&'static str> {
+        Some(match take_char(s)? {
+            'A' | 'B' => "__cdecl",
+            'C' | 'D' => "__pascal",
+            'E' | 'F' => "__thiscall",
+            'G' | 'H' => "__stdcall",
+            'I' | 'J' => "__fastcall",
+            'M' | 'N' => "__clrcall",
+            'O' | 'P' => "__eabi",
+            'Q' => "__vectorcall",
+            'S' => "__attribute__((__swiftcall__))",
+            'W' => "__attribute__((__swiftasynccall__))",
+            _ => return None,
+        })
+    }
+
+    fn demangle_function_class(&mut self, s: &mut &str) -> Option<u16> {
+        Some(match take_char(s)? {
+            '9' => FC_EXTERN_C | FC_NO_PARAMETER_LIST,
+            'A' => FC_PRIVATE,
+            'B' => FC_PRIVATE | FC_FAR,
+            'C' => FC_PRIVATE | FC_STATIC,
+            'D' => FC_PRIVATE | FC_STATIC | FC_FAR,
+            'E' => FC_PRIVATE | FC_VIRTUAL,
+            'F' => FC_PRIVATE | FC_VIRTUAL | FC_FAR,
+            'G' => FC_PRIVATE | FC_VIRTUAL | FC_STATIC_THIS_ADJUST,
+            'H' => FC_PRIVATE | FC_VIRTUAL | FC_STATIC_THIS_ADJUST | FC_FAR,
+            'I' => FC_PROTECTED,
+            'J' => FC_PROTECTED | FC_FAR,
+            'K' => FC_PROTECTED | FC_STATIC,
+            'L' => FC_PROTECTED | FC_STATIC | FC_FAR,
+            'M' => FC_PROTECTED | FC_VIRTUAL,
+            'N' => FC_PROTECTED | FC_VIRTUAL | FC_FAR,
+            'O' => FC_PROTECTED | FC_VIRTUAL | FC_STATIC_THIS_ADJUST,
+            'P' => FC_PROTECTED | FC_VIRTUAL | FC_STATIC_THIS_ADJUST | FC_FAR,
+            'Q' => FC_PUBLIC,
+            'R' => FC_PUBLIC | FC_FAR,
+            'S' => FC_PUBLIC | FC_STATIC,
+            'T' => FC_PUBLIC | FC_STATIC | FC_FAR,
+            'U' => FC_PUBLIC | FC_VIRTUAL,
+            'V' => FC_PUBLIC | FC_VIRTUAL | FC_FAR,
+            'W' => FC_PUBLIC | FC_VIRTUAL | FC_STATIC_THIS_ADJUST,
+            'X' => FC_PUBLIC | FC_VIRTUAL | FC_STATIC_THIS_ADJUST | FC_FAR,
+            'Y' => FC_GLOBAL,
+            'Z' => FC_GLOBAL | FC_FAR,
+            '$' => {
+                let mut flags = FC_VIRTUAL_THIS_ADJUST;
+                if consume_char(s, 'R') {
+                    flags |= FC_VIRTUAL_THIS_ADJUST_EX;
+                }
+                flags
+                    | match take_char(s)? {
+                        '0' => FC_PRIVATE | FC_VIRTUAL,
+                        '1' => FC_PRIVATE | FC_VIRTUAL | FC_FAR,
+                        '2' => FC_PROTECTED | FC_VIRTUAL,
+                        '3' => FC_PROTECTED | FC_VIRTUAL | FC_FAR,
+                        '4' => FC_PUBLIC | FC_VIRTUAL,
+                        '5' => FC_PUBLIC | FC_VIRTUAL | FC_FAR,
+                        _ => return None,
+                    }
+            }
+            _ => return None,
+        })
+    }
+
+    fn demangle_function_parameter_list(&mut self, s: &mut &str) -> Option<(Vec<Type>, bool)> {
+        // Empty parameter list.
+        if consume_char(s, 'X') {
+            return Some((Vec::new(), false));
+        }
+
+        let mut params = Vec::new();
+        while !s.is_empty() && !s.starts_with(['@', 'Z']) {
+            if starts_with_digit(s) {
+                let index = usize::from(s.as_bytes()[0] - b'0');
+                params.push(self.backrefs.function_params.get(index)?.clone());
+                *s = &s[1..];
+                continue;
+            }
+
+            let old_len = s.len();
+            let ty = self.demangle_type(s, QualifierMangleMode::Drop)?;
+            // Single-letter types are ignored for backreferences because
+            // memorizing them doesn't save anything.
+            if self.backrefs.function_params.len() < MAX_BACKREFS && old_len - s.len() > 1 {
+                self.backrefs.function_params.push(ty.clone());
+            }
+            params.push(ty);
+        }
+
+        // A non-empty parameter list is terminated by either 'Z' (variadic)
+        // parameter list or '@' (non variadic).  Careful not to consume "@Z",
+        // as in that case the actual parameter list is terminated by "@" and
+        // the "Z" is the throw spec.
+        if consume_char(s, '@') {
+            Some((params, false))
+        } else if consume_char(s, 'Z') {
+            Some((params, true))
+        } else {
+            None
+        }
+    }
+
+    fn demangle_function_type(
+        &mut self,
+        s: &mut &str,
+        has_this_quals: bool,
+    ) -> Option<FunctionSignature> {
+        let mut signature = FunctionSignature::default();
+        if has_this_quals {
+            signature.quals = self.demangle_pointer_ext_qualifiers(s);
+            if consume_char(s, 'G') {
+                signature.ref_qualifier = Some("&");
+            } else if consume_char(s, 'H') {
+                signature.ref_qualifier = Some("&&");
+            }
+            signature.quals |= self.demangle_qualifiers(s)?.0;
+        }
+
+        signature.calling_convention = self.demangle_calling_convention(s)?;
+
+        // <return-type> ::= <type>
+        //               ::= @ # structors (they have no declared return type)
+        if !consume_char(s, '@') {
+            signature.return_type = Some(self.demangle_type(s, QualifierMangleMode::Result)?);
+        }
+
+        (signature.params, signature.is_variadic) = self.demangle_function_parameter_list(s)?;
+
+        signature.is_noexcept = if consume(s, "_E") {
+            true
+        } else if consume_char(s, 'Z') {
+            false
+        } else {
+            return None;
+        };
+        Some(signature)
+    }
+
+    /// Reads a this-adjustment offset, which is a 32-bit value that may be
+    /// mangled as its unsigned representation.
+    fn demangle_offset(&mut self, s: &mut &str) -> Option<i32> {
+        self.demangle_signed(s).map(|offset| offset as i32)
+    }
+
+    fn demangle_function_encoding(&mut self, s: &mut &str) -> Option<FunctionSignature> {
+        let extra_flags = if consume(s, "$$J0") { FC_EXTERN_C } else { 0 };
+        let function_class = self.demangle_function_class(s)? | extra_flags;
+
+        let mut this_adjustment = None;
+        if function_class & FC_STATIC_THIS_ADJUST != 0 {
+            this_adjustment = Some(ThisAdjustment {
+                static_offset: self.demangle_offset(s)?,
+                ..Default::default()
+            });
+        } else if function_class & FC_VIRTUAL_THIS_ADJUST != 0 {
+            let mut adjustment = ThisAdjustment::default();
+            if function_class & FC_VIRTUAL_THIS_ADJUST_EX != 0 {
+                adjustment.vbptr_offset = self.demangle_offset(s)?;
+                adjustment.vboffset_offset = self.demangle_offset(s)?;
+            }
+            adjustment.vtordisp_offset = self.demangle_offset(s)?;
+            adjustment.static_offset = self.demangle_offset(s)?;
+            this_adjustment = Some(adjustment);
+        }
+
+        let mut signature = if function_class & FC_NO_PARAMETER_LIST != 0 {
+            // This is an extern "C" function whose full signature hasn't been
+            // mangled.
+            FunctionSignature::default()
+        } else {
+            let has_this_quals = function_class & (FC_GLOBAL | FC_STATIC) == 0;
+            self.demangle_function_type(s, has_this_quals)?
+        };
+        signature.function_class = function_class;
+        signature.this_adjustment = this_adjustment;
+        Some(signature)
+    }
+
+    fn demangle_primitive_type(&mut self, s: &mut &str) -> Option<Type> {
+        if consume(s, "$$T") {
+            return Some(Type::new(TypeKind::Primitive("std::nullptr_t")));
+        }
+        let name = match take_char(s)? {
+            'X' => "void",
+            'D' => "char",
+            'C' => "signed char",
+            'E' => "unsigned char",
+            'F' => "short",
+            'G' => "unsigned short",
+            'H' => "int",
+            'I' => "unsigned int",
+            'J' => "long",
+            'K' => "unsigned long",
+            'M' => "float",
+            'N' => "double",
+            'O' => "long double",
+            '_' => match take_char(s)? {
+                'N' => "bool",
+                'J' => "__int64",
+                'K' => "unsigned __int64",
+                'W' => "wchar_t",
+                'Q' => "char8_t",
+                'S' => "char16_t",
+                'U' => "char32_t",
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(Type::new(TypeKind::Primitive(name)))
+    }
+
+    fn demangle_class_type(&mut self, s: &mut &str) -> Option<Type> {
+        let tag = match take_char(s)? {
+            'T' => "union",
+            'U' => "struct",
+            'V' => "class",
+            'W' if consume_char(s, '4') => "enum",
+            _ => return None,
+        };
+        let name = self.demangle_fully_qualified_type_name(s)?;
+        Some(Type::new(TypeKind::Tag { tag, name }))
+    }
+
+    fn demangle_pointer_cv_qualifiers(&mut self, s: &mut &str) -> Option<(u8, PointerAffinity)> {
+        if consume(s, "$$Q") {
+            return Some((0, PointerAffinity::RValueReference));
+        }
+        if consume(s, "$$R") {
+            return Some((Q_VOLATILE, PointerAffinity::RValueReference));
+        }
+        Some(match take_char(s)? {
+            'A' => (0, PointerAffinity::Reference),
+            'B' => (Q_VOLATILE, PointerAffinity::Reference),
+            'P' => (0, PointerAffinity::Pointer),
+            'Q' => (Q_CONST, PointerAffinity::Pointer),
+            'R' => (Q_VOLATILE, PointerAffinity::Pointer),
+            'S' => (Q_CONST | Q_VOLATILE, PointerAffinity::Pointer),
+            _ => return None,
+        })
+    }
+
+    fn is_member_pointer(s: &str) -> Option<bool> {
+        match s.as_bytes()[0] {
+            b'$' | b'A' | b'B' => return Some(false),
+            b'P' | b'Q' | b'R' | b'S' => {}
+            _ => return None,
+        }
+
+        // If it starts with a number, then 6 indicates a non-member function
+        // pointer, and 8 indicates a member function pointer.
+        let mut s = &s[1..];
+        if starts_with_digit(s) {
+            return match s.as_bytes()[0] {
+                b'6' => Some(false),
+                b'8' => Some(true),
+                _ => None,
+            };
+        }
+
+        // Remove ext qualifiers since those can appear on either type and are
+        // therefore not indicative.
+        consume_char(&mut s, 'E');
+        consume_char(&mut s, 'I');
+        consume_char(&mut s, 'F');
+
+        // The next value should be either ABCD (non-member) or QRST (member).
+        match s.bytes().next()? {
+            b'A'..=b'D' => Some(false),
+            b'Q'..=b'T' => Some(true),
+            _ => None,
+        }
+    }
+
+    fn demangle_pointer_type(&mut self, s: &mut &str) -> Option<Type> {
+        let (mut quals, affinity) = self.demangle_pointer_cv_qualifiers(s)?;
+        let pointee = if consume_char(s, '6') {
+            Type::new(TypeKind::Function(Box::new(
+                self.demangle_function_type(s, false)?,
+            )))
+        } else {
+            quals |= self.demangle_pointer_ext_qualifiers(s);
+            self.demangle_type(s, QualifierMangleMode::Mangle)?
+        };
+        Some(Type {
+            kind: TypeKind::Pointer {
+                affinity,
+                class_parent: None,
+                pointee: Box::new(pointee),
+            },
+            quals,
+        })
+    }
+
+    fn demangle_member_pointer_type(&mut self, s: &mut &str) -> Option<Type> {
+        let (mut quals, affinity) = self.demangle_pointer_cv_qualifiers(s)?;
+        quals |= self.demangle_pointer_ext_qualifiers(s);
+
+        let (class_parent, pointee) = if consume_char(s, '8') {
+            let class_parent = self.demangle_fully_qualified_type_name(s)?;
+            let signature = self.demangle_function_type(s, true)?;
+            (
+                class_parent,
+                Type::new(TypeKind::Function(Box::new(signature))),
+            )
+        } else {
+            let (pointee_quals, _) = self.demangle_qualifiers(s)?;
+            let class_parent = self.demangle_fully_qualified_type_name(s)?;
+            let mut pointee = self.demangle_type(s, QualifierMangleMode::Drop)?;
+            pointee.quals = pointee_quals;
+            (class_parent, pointee)
+        };
+        Some(Type {
+            kind: TypeKind::Pointer {
+                affinity,
+                class_parent: Some(class_parent),
+                pointee: Box::new(pointee),
+            },
+            quals,
+        })
+    }
+
+    fn demangle_array_type(&mut self, s: &mut &str) -> Option<Type> {
+        consume_char(s, 'Y');
+        let (rank, is_negative) = self.demangle_number(s)?;
+        if is_negative || rank == 0 {
+            return None;
+        }
+        let dimensions = (0..rank)
+            .map(|_| match self.demangle_number(s)? {
+                (dimension, false) => Some(dimension),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let mut quals = 0;
+        if consume(s, "$$C") {
+            let is_member;
+            (quals, is_member) = self.demangle_qualifiers(s)?;
+            if is_member {
+                return None;
+            }
+        }
+
+        let element = self.demangle_type(s, QualifierMangleMode::Drop)?;
+        Some(Type {
+            kind: TypeKind::Array {
+                dimensions,
+                element: Box::new(element),
+            },
+            quals,
+        })
+    }
+
+    fn demangle_custom_type(&mut self, s: &mut &str) -> Option<Type> {
+        consume_char(s, '?');
+        let identifier = self.demangle_unqualified_type_name(s)?;
+        if !consume_char(s, '@') {
+            return None;
+        }
+        Some(Type::new(TypeKind::Custom(identifier)))
+    }
+
+    fn demangle_type(&mut self, s: &mut &str, mode: QualifierMangleMode) -> Option<Type> {
+        self.nested(|this| this.demangle_type_inner(s, mode))
+    }
+
+    fn demangle_type_inner(&mut self, s: &mut &str, mode: QualifierMangleMode) -> Option<Type> {
+        let mut quals = 0;
+        if mode == QualifierMangleMode::Mangle
+            || (mode == QualifierMangleMode::Result && consume_char(s, '?'))
+        {
+            quals = self.demangle_qualifiers(s)?.0;
+        }
+
+        let mut ty = match s.as_bytes().first()? {
+            b'T' | b'U' | b'V' | b'W' => self.demangle_class_type(s)?,
+            b'A' | b'B' | b'P' | b'Q' | b'R' | b'S' => {
+                if Self::is_member_pointer(s)? {
+                    self.demangle_member_pointer_type(s)?
+                } else {
+                    self.demangle_pointer_type(s)?
+                }
+            }
+            _ if s.starts_with("$$Q") || s.starts_with("$$R") => self.demangle_pointer_type(s)?,
+            b'Y' => self.demangle_array_type(s)?,
+            _ if consume(s, "$$A8@@") => Type::new(TypeKind::Function(Box::new(
+                self.demangle_function_type(s, true)?,
+            ))),
+            _ if consume(s, "$$A6") => Type::new(TypeKind::Function(Box::new(
+                self.demangle_function_type(s, false)?,
+            ))),
+            b'?' => self.demangle_custom_type(s)?,
+            _ => self.demangle_primitive_type(s)?,
+        };
+        ty.add_quals(quals);
+        Some(ty)
+    }
+
+    fn demangle_template_parameter_list(&mut self, s: &mut &str) -> Option<Vec<TemplateArg>> {
+        let mut args = Vec::new();
+        while !consume_char(s, '@') {
+            if s.is_empty() {
+                return None;
+            }
+            if consume(s, "$S") || consume(s, "$$V") || consume(s, "$$$V") || consume(s, "$$Z") {
+                // Empty parameter pack or pack separator.
+                continue;
+            }
+
+            let arg = if consume(s, "$$Y") {
+                // Template alias
+                let name = self.demangle_fully_qualified_type_name(s)?;
+                TemplateArg::Type(Type::new(TypeKind::Custom(Identifier::named({
+                    let mut out = String::new();
+                    output_qualified_name(&mut out, &name);
+                    out
+                }))))
+            } else if consume(s, "$$B") {
+                // Array
+                TemplateArg::Type(self.demangle_type(s, QualifierMangleMode::Drop)?)
+            } else if consume(s, "$$C") {
+                // Type has qualifiers.
+                TemplateArg::Type(self.demangle_type(s, QualifierMangleMode::Mangle)?)
+            } else if s.starts_with("$1")
+                || s.starts_with("$H")
+                || s.starts_with("$I")
+                || s.starts_with("$J")
+            {
+                // Pointer to member
+                //
+                // 1 - single inheritance       <name>
+                // H - multiple inheritance     <name> <number>
+                // I - virtual inheritance      <name> <number> <number>
+                // J - unspecified inheritance  <name> <number> <number> <number>
+                *s = &s[1..];
+                let offset_count = match take_char(s)? {
+                    'J' => 3,
+                    'I' => 2,
+                    'H' => 1,
+                    _ => 0,
+                };
+                let mut symbol = None;
+                if s.starts_with('?') {
+                    let parsed = self.parse(s)?;
+                    if let Some(identifier) = symbol_name(&parsed).and_then(|name| name.last()) {
+                        self.memorize_identifier(identifier);
+                    }
+                    symbol = Some(Box::new(parsed));
+                }
+                let offsets = (0..offset_count)
+                    .map(|_| self.demangle_signed(s))
+                    .collect::<Option<Vec<_>>>()?;
+                TemplateArg::Symbol {
+                    symbol,
+                    is_pointer: true,
+                    offsets,
+                }
+            } else if s.starts_with("$E?") {
+                // Reference to symbol
+                consume(s, "$E");
+                TemplateArg::Symbol {
+                    symbol: Some(Box::new(self.parse(s)?)),
+                    is_pointer: false,
+                    offsets: Vec::new(),
+                }
+            } else if s.starts_with("$F") || s.starts_with("$G") {
+                // Data member pointer.
+                *s = &s[1..];
+                let offset_count = if take_char(s)? == 'G' { 3 } else { 2 };
+                let offsets = (0..offset_count)
+                    .map(|_| self.demangle_signed(s))
+                    .collect::<Option<Vec<_>>>()?;
+                TemplateArg::Symbol {
+                    symbol: None,
+                    is_pointer: true,
+                    offsets,
+                }
+            } else if consume(s, "$0") {
+                // Integral non-type template parameter
+                let (value, is_negative) = self.demangle_number(s)?;
+                TemplateArg::Integer { value, is_negative }
+            } else {
+                TemplateArg::Type(self.demangle_type(s, QualifierMangleMode::Drop)?)
+            };
+            args.push(arg);
+        }
+        Some(args)
+    }
+
+    fn demangle_variable(&mut self, s: &mut &str, name: QualifiedName) -> Option<Symbol> {
+        let storage_class = s.as_bytes()[0];
+        *s = &s[1..];
+
+        let mut ty = self.demangle_type(s, QualifierMangleMode::Drop)?;
+        // <variable-type> ::= <type> <cvr-qualifiers>
+        //                 ::= <type> <pointee-cvr-qualifiers> # pointers, references
+        match &mut ty.kind {
+            TypeKind::Pointer {
+                class_parent,
+                pointee,
+                ..
+            } => {
+                let has_class_parent = class_parent.is_some();
+                ty.quals |= self.demangle_pointer_ext_qualifiers(s);
+                let (child_quals, _) = self.demangle_qualifiers(s)?;
+                if has_class_parent {
+                    self.demangle_fully_qualified_type_name(s)?;
+                }
+                pointee.add_quals(child_quals);
+            }
+            _ => ty.quals = self.demangle_qualifiers(s)?.0,
+        }
+
+        Some(Symbol::Variable {
+            name,
+            storage_class,
+            ty,
+        })
+    }
+
+    fn demangle_special_table(&mut self, s: &mut &str, table: &str) -> Option<Symbol> {
+        let name = self.demangle_name_scope_chain(s, Identifier::named(table))?;
+        if !consume_char(s, '6') && !consume_char(s, '7') {
+            return None;
+        }
+        let (quals, is_member) = self.demangle_qualifiers(s)?;
+        if is_member {
+            return None;
+        }
+        let target = if consume_char(s, '@') {
+            None
+        } else {
+            let target = self.demangle_fully_qualified_type_name(s)?;
+            consume_char(s, '@');
+            Some(target)
+        };
+        Some(Symbol::SpecialTable {
+            name,
+            quals,
+            target,
+        })
+    }
+
+    fn parse(&mut self, s: &mut &str) -> Option<Symbol> {
+        self.nested(|this| this.parse_inner(s))
+    }
+
+    fn parse_inner(&mut self, s: &mut &str) -> Option<Symbol> {
+        // MSVC-style mangled symbols must start with '?'. Names starting with
+        // ??@ are hashes of names that were too long, so can't be demangled.
+        if !consume_char(s, '?') || s.starts_with("?@") {
+            return None;
+        }
+
+        if consume(s, "?_7") {
+            return self.demangle_special_table(s, "`vftable'");
+        }
+        if consume(s, "?_8") {
+            return self.demangle_special_table(s, "`vbtable'");
+        }
+        if consume(s, "?_C@_") {
+            // The string's contents are only encoded as a hash and a prefix.
+            *s = "";
+            return Some(Symbol::StringLiteral);
+        }
+
+        // What follows is a main symbol name. This may include namespaces or
+        // class back references.
+        let mut name = self.demangle_fully_qualified_symbol_name(s)?;
+
+        // ARM64EC adds $$h after the name of C++ functions.
+        consume(s, "$$h");
+
+        if matches!(s.as_bytes().first()?, b'0'..=b'4') {
+            return self.demangle_variable(s, name);
+        }
+
+        let signature = self.demangle_function_encoding(s)?;
+        if let Some(Identifier {
+            kind: IdentifierKind::ConversionOperator { target },
+            ..
+        }) = name.last_mut()
+        {
+            *target = Some(Box::new(signature.return_type.clone()?));
+        }
+        Some(Symbol::Function { name, signature })
+    }
+}
+
+fn symbol_name(symbol: &Symbol) -> Option<&QualifiedName> {
+    match symbol {
+        Symbol::Function { name, .. }
+        | Symbol::Variable { name, .. }
+        | Symbol::SpecialTable { name, .. } => Some(name),
+        Symbol::StringLiteral => None,
+    }
+}
+
+fn output_space_if_necessary(out: &mut String) {
+    if out.ends_with(|c: char| c.is_ascii_alphanumeric() || c == '>') {
+        out.push(' ');
+    }
+}
+
+fn output_qualifiers(out: &mut String, quals: u8, mut space_before: bool, space_after: bool) {
+    let start = out.len();
+    for (mask, name) in [
+        (Q_CONST, "const"),
+        (Q_VOLATILE, "volatile"),
+        (Q_RESTRICT, "__restrict"),
+    ] {
+        if quals & mask != 0 {
+            if space_before {
+                out.push(' ');
+            }
+            out.push_str(name);
+            space_before = true;
+        }
+    }
+    if space_after && out.len() > start {
+        out.push(' ');
+    }
+}
+
+fn output_template_args(out: &mut String, args: &Option<Vec<TemplateArg>>) {
+    let Some(args) = args else {
+        return;
+    };
+    out.push('<');
+    for (i, arg) in args.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        match arg {
+            TemplateArg::Type(ty) => output_type(out, ty),
+            TemplateArg::Integer { value, is_negative } => {
+                if *is_negative {
+                    out.push('-');
+                }
+                out.push_str(&value.to_string());
+            }
+            TemplateArg::Symbol {
+                symbol,
+                is_pointer,
+                offsets,
+            } => {
+                if !offsets.is_empty() {
+                    out.push('{');
+                } else if *is_pointer {
+                    out.push('&');
+                }
+                if let Some(symbol) = symbol {
+                    output_symbol(out, symbol);
+                    if !offsets.is_empty() {
+                        out.push_str(", ");
+                    }
+                }
+                if !offsets.is_empty() {
+                    let offsets = offsets.iter().map(|o| o.to_string()).collect::<Vec<_>>();
+                    out.push_str(&offsets.join(", "));
+                    out.push('}');
+                }
+            }
+        }
+    }
+    out.push('>');
+}
+
+/// Outputs the component at `index` of `name`. Constructors and destructors
+/// are named after the preceding component.
+fn output_identifier(out: &mut String, name: &[Identifier], index: usize) {
+    let identifier = &name[index];
+    match &identifier.kind {
+        IdentifierKind::Named(n) => out.push_str(n),
+        IdentifierKind::Structor { is_destructor } => {
+            if *is_destructor {
+                out.push('~');
+            }
+            if index > 0 {
+                output_identifier(out, name, index - 1);
+            }
+        }
+        IdentifierKind::ConversionOperator { target } => {
+            out.push_str("operator");
+            output_template_args(out, &identifier.template_args);
+            out.push(' ');
+            if let Some(target) = target {
+                output_type(out, target);
+            }
+            return;
+        }
+    }
+    output_template_args(out, &identifier.template_args);
+}
+
+fn output_qualified_name(out: &mut String, name: &[Identifier]) {
+    for index in 0..name.len() {
+        if index > 0 {
+            out.push_str("::");
+        }
+        output_identifier(out, name, index);
+    }
+}
+
+fn output_signature_pre(out: &mut String, signature: &FunctionSignature, calling_convention: bool) {
+    let function_class = signature.function_class;
+    if signature.this_adjustment.is_some() {
+        out.push_str("[thunk]: ");
+    }
+    if function_class & FC_PUBLIC != 0 {
+        out.push_str("public: ");
+    }
+    if function_class & FC_PROTECTED != 0 {
+        out.push_str("protected: ");
+    }
+    if function_class & FC_PRIVATE != 0 {
+        out.push_str("private: ");
+    }
+    if function_class & FC_GLOBAL == 0 && function_class & FC_STATIC != 0 {
+        out.push_str("static ");
+    }
+    if function_class & FC_VIRTUAL != 0 {
+        out.push_str("virtual ");
+    }
+    if function_class & FC_EXTERN_C != 0 {
+        out.push_str("extern \"C\" ");
+    }
+    if let Some(return_type) = &signature.return_type {
+        output_type_pre(out, return_type);
+        out.push(' ');
+    }
+    if calling_convention {
+        out.push_str(signature.calling_convention);
+    }
+}
+
+fn output_signature_post(out: &mut String, signature: &FunctionSignature) {
+    let function_class = signature.function_class;
+    if let Some(adjustment) = &signature.this_adjustment {
+        if function_class & FC_STATIC_THIS_ADJUST != 0 {
+            out.push_str(&format!("`adjustor{{{}}}'", adjustment.static_offset));
+        } else if function_class & FC_VIRTUAL_THIS_ADJUST_EX != 0 {
+            out.push_str(&format!(
+                "`vtordispex{{{}, {}, {}, {}}}'",
+                adjustment.vbptr_offset,
+                adjustment.vboffset_offset,
+                adjustment.vtordisp_offset,
+                adjustment.static_offset
+            ));
+        } else {
+            out.push_str(&format!(
+                "`vtordisp{{{}, {}}}'",
+                adjustment.vtordisp_offset, adjustment.static_offset
+            ));
+        }
+    }
+
+    if function_class & FC_NO_PARAMETER_LIST == 0 {
+        out.push('(');
+        for (i, param) in signature.params.iter().enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+            output_type(out, param);
+        }
+        if signature.is_variadic {
+            if !out.ends_with('(') {
+                out.push_str(", ");
+            }
+            out.push_str("...");
+        } else if signature.params.is_empty() {
+            out.push_str("void");
+        }
+        out.push(')');
+    }
+
+    for (mask, name) in [
+        (Q_CONST, " const"),
+        (Q_VOLATILE, " volatile"),
+        (Q_RESTRICT, " __restrict"),
+        (Q_UNALIGNED, " __unaligned"),
+    ] {
+        if signature.quals & mask != 0 {
+            out.push_str(name);
+        }
+    }
+    if signature.is_noexcept {
+        out.push_str(" noexcept");
+    }
+    if let Some(ref_qualifier) = signature.ref_qualifier {
+        out.push(' ');
+        out.push_str(ref_qualifier);
+    }
+    if let Some(return_type) = &signature.return_type {
+        output_type_post(out, return_type);
+    }
+}
+
+fn output_type_pre(out: &mut String, ty: &Type) {
+    match &ty.kind {
+        TypeKind::Primitive(name) => {
+            out.push_str(name);
+            output_qualifiers(out, ty.quals, true, false);
+        }
+        TypeKind::Tag { tag, name } => {
+            out.push_str(tag);
+            out.push(' ');
+            output_qualified_name(out, name);
+            output_qualifiers(out, ty.quals, true, false);
+        }
+        TypeKind::Custom(identifier) => {
+            output_identifier(out, std::slice::from_ref(identifier), 0);
+            output_qualifiers(out, ty.quals, true, false);
+        }
+        TypeKind::Array { element, .. } => {
+            output_type_pre(out, element);
+            output_qualifiers(out, ty.quals, true, false);
+        }
+        TypeKind::Function(signature) => output_signature_pre(out, signature, true),
+        TypeKind::Pointer {
+            affinity,
+            class_parent,
+            pointee,
+        } => {
+            // The calling convention of a function pointer goes inside the
+            // parentheses.
+            match &pointee.kind {
+                TypeKind::Function(signature) => output_signature_pre(out, signature, false),
+                _ => output_type_pre(out, pointee),
+            }
+            output_space_if_necessary(out);
+            if ty.quals & Q_UNALIGNED != 0 {
+                out.push_str("__unaligned ");
+            }
+            match &pointee.kind {
+                TypeKind::Array { .. } => out.push('('),
+                TypeKind::Function(signature) => {
+                    out.push('(');
+                    out.push_str(signature.calling_convention);
+                    out.push(' ');
+                }
+                _ => {}
+            }
+            if let Some(class_parent) = class_parent {
+                output_qualified_name(out, class_parent);
+                out.push_str("::");
+            }
+            out.push_str(match affinity {
+                PointerAffinity::Pointer => "*",
+                PointerAffinity::Reference => "&",
+                PointerAffinity::RValueReference => "&&",
+            });
+            output_qualifiers(out, ty.quals, false, false);
+        }
+    }
+}
+
+fn output_type_post(out: &mut String, ty: &Type) {
+    match &ty.kind {
+        TypeKind::Array {
+            dimensions,
+            element,
+        } => {
+            let dimensions = dimensions.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+            out.push('[');
+            out.push_str(&dimensions.join("]["));
+            out.push(']');
+            output_type_post(out, element);
+        }
+        TypeKind::Function(signature) => output_signature_post(out, signature),
+        TypeKind::Pointer { pointee, .. } => {
+            if matches!(pointee.kind, TypeKind::Array { .. } | TypeKind::Function(_)) {
+                out.push(')');
+            }
+            output_type_post(out, pointee);
+        }
+        TypeKind::Primitive(_) | TypeKind::Tag { .. } | TypeKind::Custom(_) => {}
+    }
+}
+
+fn output_type(out: &mut String, ty: &Type) {
+    output_type_pre(out, ty);
+    output_type_post(out, ty);
+}
+
+fn output_symbol(out: &mut String, symbol: &Symbol) {
+    match symbol {
+        Symbol::Function { name, signature } => {
+            output_signature_pre(out, signature, true);
+            output_space_if_necessary(out);
+            output_qualified_name(out, name);
+            output_signature_post(out, signature);
+        }
+        Symbol::Variable {
+            name,
+            storage_class,
+            ty,
+        } => {
+            let access = match storage_class {
+                b'0' => Some("private"),
+                b'1' => Some("protected"),
+                b'2' => Some("public"),
+                _ => None,
+            };
+            if let Some(access) = access {
+                out.push_str(access);
+                out.push_str(": static ");
+            }
+            output_type_pre(out, ty);
+            output_space_if_necessary(out);
+            output_qualified_name(out, name);
+            output_type_post(out, ty);
+        }
+        Symbol::SpecialTable {
+            name,
+            quals,
+            target,
+        } => {
+            output_qualifiers(out, *quals, false, true);
+            output_qualified_name(out, name);
+            if let Some(target) = target {
+                out.push_str("{for `");
+                output_qualified_name(out, target);
+                out.push_str("'}");
+            }
+        }
+        Symbol::StringLiteral => out.push_str("`string'"),
+    }
+}
+
+// Derived from getArm64ECInsertionPointInMangledName in MicrosoftDemangle.cpp.
+/// Returns the offset just after the fully qualified name of the MSVC C++
+/// symbol `mangled_name`, which is where ARM64EC mangling inserts `$$h`.
+pub(crate) fn get_arm64ec_insertion_point_in_mangled_name(mangled_name: &str) -> Option<usize> {
+    // We only support this for MSVC-style C++ symbols.
+    let mut processed_name = mangled_name.strip_prefix('?')?;
+
+    // The insertion point is just after the name of the symbol, so parse that
+    // to remove it from the processed name.
+    Demangler::default().demangle_fully_qualified_symbol_name(&mut processed_name)?;
+    Some(mangled_name.len() - processed_name.len())
+}
+
+/// Demangles an MSVC-style C++ symbol name into a human-readable declaration,
+/// such as `public: int __cdecl A::f(int) const`. ARM64EC names with `$$h`
+/// are demangled like the name without it.
+///
+/// Returns `None` if `mangled_name` is not a valid MSVC C++ symbol name.
+pub fn microsoft_demangle(mangled_name: &str) -> Option<String> {
+    let mut s = mangled_name;
+    let symbol = Demangler::default().parse(&mut s)?;
+    if !s.is_empty() {
+        return None;
+    }
+    let mut out = String::new();
+    output_symbol(&mut out, &symbol);
+    Some(out)
+}
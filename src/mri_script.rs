@@ -0,0 +1,255 @@
+// Derived from code in LLVM, which is:
+// Part of the LLVM Project, under the Apache License v2.0 with LLVM Exceptions.
+// See https://llvm.org/LICENSE.txt for license information.
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+//! An interpreter for the MRI scripts that are read by `ar -M`.
+
+use std::fs;
+use std::io::{self, Cursor, Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::nested_archive::is_archive;
+use crate::{
+    compute_archive_relative_path, flatten_archive_members, read_thin_archive_members,
+    write_archive_to_stream, ArchiveKind, NewArchiveMember, ObjectReader,
+};
+
+enum MriCommand<'s> {
+    AddLib(&'s str),
+    AddMod(&'s str),
+    Create(&'s str),
+    CreateThin(&'s str),
+    Delete(&'s str),
+    Replace(&'s str),
+    Save,
+    End,
+}
+
+fn parse_command(line: &str) -> io::Result<MriCommand<'_>> {
+    let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+    let rest = rest.trim();
+    let rest = rest
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .unwrap_or(rest);
+    Ok(match command.to_ascii_lowercase().as_str() {
+        "addlib" => MriCommand::AddLib(rest),
+        "addmod" => MriCommand::AddMod(rest),
+        "create" => MriCommand::Create(rest),
+        "createthin" => MriCommand::CreateThin(rest),
+        "delete" => MriCommand::Delete(rest),
+        "replace" => MriCommand::Replace(rest),
+        "save" => MriCommand::Save,
+        "end" => MriCommand::End,
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("unknown command: {command}"),
+            ))
+        }
+    })
+}
+
+/// The archive that an MRI script creates.
+pub struct MriArchive {
+    /// The path of the archive, from `CREATE` or `CREATETHIN`.
+    pub archive_path: PathBuf,
+    /// Whether the archive was opened with `CREATETHIN`.
+    pub thin: bool,
+    /// The members of the archive when it was saved, in archive order.
+    pub members: Vec<NewArchiveMember<'static>>,
+}
+
+impl MriArchive {
+    fn read_file(path: &str) -> io::Result<Vec<u8>> {
+        fs::read(path).map_err(|err| Error::new(err.kind(), format!("{path}: {err}")))
+    }
+
+    /// Reads the file at `path` into members, named by their path relative to
+    /// the archive for thin archives and by their file name otherwise.
+    fn read_members(
+        &self,
+        path: &str,
+        object_reader: &'static ObjectReader,
+    ) -> io::Result<Vec<NewArchiveMember<'static>>> {
+        if self.thin {
+            return read_thin_archive_members(&self.archive_path, &[path], object_reader);
+        }
+        let member_name = Path::new(path)
+            .file_name()
+            .map_or(path.into(), |name| name.to_string_lossy());
+        Ok(vec![NewArchiveMember::new(
+            Self::read_file(path)?,
+            object_reader,
+            member_name.into_owned(),
+        )])
+    }
+
+    fn add_lib(&mut self, path: &str, object_reader: &'static ObjectReader) -> io::Result<()> {
+        let buf = Self::read_file(path)?;
+        if !is_archive(&buf) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("{path}: not an archive"),
+            ));
+        }
+        // The members of a regular archive aren't files that a thin archive
+        // could refer to.
+        if self.thin && !buf.starts_with(&object::archive::THIN_MAGIC) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Cannot convert a regular archive to a thin one",
+            ));
+        }
+        let mut members =
+            flatten_archive_members(vec![NewArchiveMember::new(buf, object_reader, path.into())])?;
+        if self.thin {
+            for member in &mut members {
+                member.member_name = compute_archive_relative_path(
+                    &self.archive_path,
+                    Path::new(&member.member_name),
+                )?;
+            }
+        }
+        self.members.append(&mut members);
+        Ok(())
+    }
+
+    /// Replaces the member with the same name as the file at `path`.
+    fn replace(&mut self, path: &str, object_reader: &'static ObjectReader) -> io::Result<()> {
+        for new_member in self.read_members(path, object_reader)? {
+            let Some(member) = self
+                .members
+                .iter_mut()
+                .find(|member| member.member_name == new_member.member_name)
+            else {
+                return Err(Error::new(
+                    ErrorKind::NotFound,
+                    format!("no member named {} in archive", new_member.member_name),
+                ));
+            };
+            *member = new_member;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct MriState {
+    archive: Option<MriArchive>,
+    saved: bool,
+}
+
+impl MriState {
+    fn open_archive(&mut self) -> io::Result<&mut MriArchive> {
+        self.archive
+            .as_mut()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "no output archive has been opened"))
+    }
+
+    fn run_command(
+        &mut self,
+        command: MriCommand<'_>,
+        object_reader: &'static ObjectReader,
+    ) -> io::Result<()> {
+        match command {
+            MriCommand::AddLib(path) => self.open_archive()?.add_lib(path, object_reader),
+            MriCommand::AddMod(path) => {
+                let archive = self.open_archive()?;
+                let mut members = archive.read_members(path, object_reader)?;
+                archive.members.append(&mut members);
+                Ok(())
+            }
+            MriCommand::Create(path) | MriCommand::CreateThin(path) => {
+                if self.archive.is_some() {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "editing multiple archives not supported",
+                    ));
+                }
+                if path.is_empty() {
+                    return Err(Error::new(ErrorKind::InvalidInput, "missing archive name"));
+                }
+                self.archive = Some(MriArchive {
+                    archive_path: path.into(),
+                    thin: matches!(command, MriCommand::CreateThin(_)),
+                    members: Vec::new(),
+                });
+                Ok(())
+            }
+            MriCommand::Delete(name) => {
+                self.open_archive()?
+                    .members
+                    .retain(|member| member.member_name != name);
+                Ok(())
+            }
+            MriCommand::Replace(path) => self.open_archive()?.replace(path, object_reader),
+            MriCommand::Save => {
+                self.open_archive()?;
+                self.saved = true;
+                Ok(())
+            }
+            MriCommand::End => Ok(()),
+        }
+    }
+}
+
+// Derived from runMRIScript in llvm-ar.
+/// Runs the MRI script `script`, and returns the archive that it creates, or
+/// `None` if the script doesn't `SAVE` the archive.
+///
+/// The supported commands are `CREATE`, `CREATETHIN`, `ADDMOD`, `ADDLIB`,
+/// `DELETE`, `REPLACE`, `SAVE` and `END`. Commands are case insensitive, lines
+/// starting with `*` or `;` are comments, and relative paths are relative to
+/// the current directory. Archives added with `ADDLIB` are flattened with
+/// [flatten_archive_members].
+pub fn execute_mri_script(
+    script: &str,
+    object_reader: &'static ObjectReader,
+) -> io::Result<Option<MriArchive>> {
+    let mut state = MriState::default();
+    for (line_number, line) in script.lines().enumerate() {
+        // Comments start with `;` or `*` and run to the end of the line.
+        let line = line.split([';', '*']).next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let is_end = parse_command(line)
+            .and_then(|command| {
+                let is_end = matches!(command, MriCommand::End);
+                state.run_command(command, object_reader).map(|()| is_end)
+            })
+            .map_err(|err| {
+                Error::new(
+                    err.kind(),
+                    format!("script line {}: {err}", line_number + 1),
+                )
+            })?;
+        if is_end {
+            break;
+        }
+    }
+    Ok(state.archive.filter(|_| state.saved))
+}
+
+/// Runs the MRI script `script`, as `ar -M` does, and writes the archive that
+/// it saves with the format `kind`.
+pub fn run_mri_script(
+    script: &str,
+    kind: ArchiveKind,
+    object_reader: &'static ObjectReader,
+) -> io::Result<()> {
+    let Some(archive) = execute_mri_script(script, object_reader)? else {
+        return Ok(());
+    };
+    let mut output_bytes = Cursor::new(Vec::new());
+    write_archive_to_stream(
+        &mut output_bytes,
+        &archive.members,
+        kind,
+        archive.thin,
+        false,
+    )?;
+    fs::write(&archive.archive_path, output_bytes.into_inner())
+}
@@ -0,0 +1,164 @@
+// Derived from code in LLVM, which is:
+// Part of the LLVM Project, under the Apache License v2.0 with LLVM Exceptions.
+// See https://llvm.org/LICENSE.txt for license information.
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+//! Flattening of archive members that are themselves archives, as done by the
+//! `L` modifier of `llvm-ar` and the `ADDLIB` command of MRI scripts.
+
+use std::fs;
+use std::io::{self, Error, ErrorKind};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use object::archive;
+use object::read::archive::{ArchiveFile, ArchiveMember};
+
+use crate::thin_archive::get_dotless_absolute_path;
+use crate::{NewArchiveMember, ObjectReader};
+
+/// The data of a member of an archive, which shares the buffer of the archive.
+struct ChildBuf<'a> {
+    archive: Rc<Box<dyn AsRef<[u8]> + 'a>>,
+    range: Range<usize>,
+}
+
+impl AsRef<[u8]> for ChildBuf<'_> {
+    fn as_ref(&self) -> &[u8] {
+        &(**self.archive).as_ref()[self.range.clone()]
+    }
+}
+
+/// Returns true if `buf` is a GNU, BSD, COFF, thin or AIX big archive.
+pub(crate) fn is_archive(buf: &[u8]) -> bool {
+    [archive::MAGIC, archive::THIN_MAGIC, archive::AIX_BIG_MAGIC]
+        .iter()
+        .any(|magic| buf.starts_with(magic))
+}
+
+fn invalid_archive(member_name: &str, err: impl std::fmt::Display) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("{member_name}: {err}"))
+}
+
+// Derived from NewArchiveMember::getOldMember.
+/// Creates a member with the metadata of `child`.
+fn get_old_member<'a, T: AsRef<[u8]> + 'a>(
+    buf: T,
+    object_reader: &'static ObjectReader,
+    member_name: String,
+    child: &ArchiveMember<'_>,
+) -> NewArchiveMember<'a> {
+    let mut member = NewArchiveMember::new(buf, object_reader, member_name);
+    if let Some(mtime) = child.date() {
+        member.mtime = mtime;
+    }
+    if let Some(uid) = child.uid().and_then(|uid| u32::try_from(uid).ok()) {
+        member.uid = uid;
+    }
+    if let Some(gid) = child.gid().and_then(|gid| u32::try_from(gid).ok()) {
+        member.gid = gid;
+    }
+    if let Some(perms) = child.mode().and_then(|perms| u32::try_from(perms).ok()) {
+        member.perms = perms;
+    }
+    member
+}
+
+fn flatten_member_into<'a>(
+    member: NewArchiveMember<'a>,
+    parents: &mut Vec<PathBuf>,
+    members: &mut Vec<NewArchiveMember<'a>>,
+) -> io::Result<()> {
+    if !is_archive((*member.buf).as_ref()) {
+        members.push(member);
+        return Ok(());
+    }
+
+    let NewArchiveMember {
+        buf,
+        object_reader,
+        member_name,
+        ..
+    } = member;
+    let buf = Rc::new(buf);
+    let data: &[u8] = (**buf).as_ref();
+    let archive = ArchiveFile::parse(data).map_err(|err| invalid_archive(&member_name, err))?;
+
+    if !archive.is_thin() {
+        for child in archive.members() {
+            let child = child.map_err(|err| invalid_archive(&member_name, err))?;
+            // `object` returns the EC symbol table as a member, but the writer
+            // creates it from the symbols of the members.
+            if child.name() == b"/<ECSYMBOLS>/" {
+                continue;
+            }
+            let child_name = std::str::from_utf8(child.name())
+                .map_err(|err| invalid_archive(&member_name, err))?;
+            let (offset, size) = child.file_range();
+            let start = offset as usize;
+            let child_buf = ChildBuf {
+                archive: Rc::clone(&buf),
+                range: start..start + size as usize,
+            };
+            let child_member =
+                get_old_member(child_buf, object_reader, child_name.to_string(), &child);
+            flatten_member_into(child_member, parents, members)?;
+        }
+        return Ok(());
+    }
+
+    // The members of a thin archive are files relative to the archive, which
+    // may in turn be thin archives that contain the archive.
+    let archive_path = get_dotless_absolute_path(Path::new(&member_name))?;
+    if parents.contains(&archive_path) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("thin archive {member_name} contains itself"),
+        ));
+    }
+    let archive_dir = Path::new(&member_name).parent().unwrap_or(Path::new(""));
+    parents.push(archive_path);
+    for child in archive.members() {
+        let child = child.map_err(|err| invalid_archive(&member_name, err))?;
+        if child.name() == b"/<ECSYMBOLS>/" {
+            continue;
+        }
+        let child_name =
+            std::str::from_utf8(child.name()).map_err(|err| invalid_archive(&member_name, err))?;
+        let child_path = archive_dir.join(child_name);
+        let child_buf = fs::read(&child_path).map_err(|err| {
+            Error::new(
+                err.kind(),
+                format!("{}: {err}", child_path.to_string_lossy()),
+            )
+        })?;
+        let child_member = get_old_member(
+            child_buf,
+            object_reader,
+            child_path.to_string_lossy().into_owned(),
+            &child,
+        );
+        flatten_member_into(child_member, parents, members)?;
+    }
+    parents.pop();
+    Ok(())
+}
+
+/// Replaces the members of `members` that are archives with the members of
+/// those archives, so that their symbols are added to the symbol table of the
+/// archive that is written, as `llvm-ar` does for archives added with `L`.
+///
+/// The nested members keep their name, timestamp, owner and mode, and are
+/// flattened recursively. The members of thin archives are read from the
+/// paths they are stored with, relative to the directory of the name of the
+/// thin archive member, and are named by that path.
+pub fn flatten_archive_members<'a>(
+    members: Vec<NewArchiveMember<'a>>,
+) -> io::Result<Vec<NewArchiveMember<'a>>> {
+    let mut flattened = Vec::with_capacity(members.len());
+    for member in members {
+        flatten_member_into(member, &mut Vec::new(), &mut flattened)?;
+    }
+    Ok(flattened)
+}
@@ -0,0 +1,65 @@
+//! Helpers for patching the object files of archive members in place, shared
+//! by the transforms that rename, localize, strip and merge their symbols.
+
+use std::collections::HashMap;
+use std::io::{self, Error, ErrorKind};
+
+use object::pod::bytes_of;
+use object::{Endianness, U16Bytes, U32Bytes, U64Bytes};
+
+use crate::alignment::align_to;
+
+pub(crate) fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+pub(crate) fn read_error(err: object::read::Error) -> Error {
+    Error::new(ErrorKind::InvalidData, err.to_string())
+}
+
+pub(crate) fn write_u16(out: &mut [u8], offset: u64, endian: Endianness, value: u16) {
+    let offset = offset as usize;
+    out[offset..offset + 2].copy_from_slice(bytes_of(&U16Bytes::new(endian, value)));
+}
+
+pub(crate) fn write_u32(out: &mut [u8], offset: u64, endian: Endianness, value: u32) {
+    let offset = offset as usize;
+    out[offset..offset + 4].copy_from_slice(bytes_of(&U32Bytes::new(endian, value)));
+}
+
+pub(crate) fn write_u64(out: &mut [u8], offset: u64, endian: Endianness, value: u64) {
+    let offset = offset as usize;
+    out[offset..offset + 8].copy_from_slice(bytes_of(&U64Bytes::new(endian, value)));
+}
+
+pub(crate) fn pad_to(out: &mut Vec<u8>, alignment: u64) {
+    out.resize(align_to(out.len() as u64, alignment.max(1)) as usize, 0);
+}
+
+/// A string table that keeps the existing strings at their offsets and
+/// appends the new names.
+pub(crate) struct StringTableBuilder {
+    pub(crate) data: Vec<u8>,
+    offsets: HashMap<Vec<u8>, u32>,
+}
+
+impl StringTableBuilder {
+    pub(crate) fn new(data: &[u8]) -> Self {
+        Self {
+            data: data.to_vec(),
+            offsets: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn add(&mut self, name: &[u8]) -> io::Result<u32> {
+        if let Some(&offset) = self.offsets.get(name) {
+            return Ok(offset);
+        }
+        let offset = u32::try_from(self.data.len())
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "string table is too large"))?;
+        self.data.extend_from_slice(name);
+        self.data.push(0);
+        self.offsets.insert(name.to_vec(), offset);
+        Ok(offset)
+    }
+}
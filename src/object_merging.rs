@@ -0,0 +1,734 @@
+//! Merging of the relocatable object members of an archive into a single
+//! relocatable object, like `ld -r` applied to all members.
+
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Error, ErrorKind};
+use std::mem::size_of;
+
+use object::read::elf::{FileHeader, SectionHeader as _, SectionTable, Sym as _, SymbolTable};
+use object::{elf, Endianness, FileKind, SymbolIndex, U32, U64};
+
+use crate::object_editing::{
+    invalid_data, pad_to, read_error, write_u16, write_u32, write_u64, StringTableBuilder,
+};
+use crate::symbol_localization::{remap_elf_relocations, SHT_LLVM_ADDRSIG};
+use crate::NewArchiveMember;
+
+/// How a global symbol is defined by an object, in increasing order of
+/// precedence when the objects are merged.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Definition {
+    Undefined,
+    Weak,
+    Common,
+    Strong,
+}
+
+/// A global symbol of the merged object, and the object symbol that it is
+/// copied from.
+struct GlobalSymbol<'data> {
+    name: &'data [u8],
+    object: usize,
+    index: usize,
+    definition: Definition,
+    /// Whether any object has a non-weak reference or definition.
+    is_strong: bool,
+    visibility: u8,
+    /// The largest size and alignment of the common symbols.
+    common_size: u64,
+    common_align: u64,
+}
+
+/// Returns how constraining a symbol visibility is, since the merged symbol
+/// has the most constraining visibility.
+fn get_visibility_rank(visibility: u8) -> u8 {
+    match visibility {
+        elf::STV_PROTECTED => 1,
+        elf::STV_HIDDEN => 2,
+        elf::STV_INTERNAL => 3,
+        _ => 0,
+    }
+}
+
+impl<'data> GlobalSymbol<'data> {
+    fn merge(&mut self, other: Self, object_names: &[&str]) -> io::Result<()> {
+        if self.definition == Definition::Strong && other.definition == Definition::Strong {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "symbol {} is defined by both {} and {}",
+                    String::from_utf8_lossy(self.name),
+                    object_names[self.object],
+                    object_names[other.object]
+                ),
+            ));
+        }
+        self.is_strong |= other.is_strong;
+        if get_visibility_rank(other.visibility) > get_visibility_rank(self.visibility) {
+            self.visibility = other.visibility;
+        }
+        if self.definition == Definition::Common && other.definition == Definition::Common {
+            self.common_size = self.common_size.max(other.common_size);
+            self.common_align = self.common_align.max(other.common_align);
+        } else if other.definition > self.definition {
+            self.object = other.object;
+            self.index = other.index;
+            self.definition = other.definition;
+            self.common_size = other.common_size;
+            self.common_align = other.common_align;
+        }
+        Ok(())
+    }
+}
+
+/// An ELF object that is merged, with the indices of its sections and symbols
+/// in the merged object.
+struct ElfObject<'data, Elf: FileHeader> {
+    data: &'data [u8],
+    header: &'data Elf,
+    sections: SectionTable<'data, Elf>,
+    symbols: SymbolTable<'data, Elf>,
+    /// The new index of each section, or `None` if it isn't copied.
+    new_section_indices: Vec<Option<u32>>,
+    /// The new index of each symbol, or `None` if it is defined in a section
+    /// that isn't copied.
+    new_symbol_indices: Vec<Option<u32>>,
+}
+
+impl<'data, Elf: FileHeader<Endian = Endianness>> ElfObject<'data, Elf> {
+    fn parse(data: &'data [u8]) -> io::Result<Self> {
+        let header = Elf::parse(data).map_err(read_error)?;
+        let endian = header.endian().map_err(read_error)?;
+        if header.e_type(endian) != elf::ET_REL {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "only relocatable objects can be merged",
+            ));
+        }
+        let sections = header.sections(endian, data).map_err(read_error)?;
+        if sections.len() >= elf::SHN_LORESERVE.into() {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "ELF objects with extended section numbering are not supported",
+            ));
+        }
+        let symbols = sections
+            .symbols(endian, data, elf::SHT_SYMTAB)
+            .map_err(read_error)?;
+        Ok(Self {
+            data,
+            header,
+            sections,
+            symbols,
+            new_section_indices: Vec::new(),
+            new_symbol_indices: Vec::new(),
+        })
+    }
+
+    fn endian(&self) -> Endianness {
+        // The endianness was checked when parsing.
+        self.header.endian().unwrap()
+    }
+
+    fn new_section_index(&self, index: u32) -> io::Result<u32> {
+        self.new_section_indices
+            .get(index as usize)
+            .copied()
+            .flatten()
+            .ok_or_else(|| invalid_data("section refers to a section that isn't merged"))
+    }
+
+    fn new_symbol_index(&self, index: u32) -> io::Result<u32> {
+        self.new_relocation_symbol_index(index)?
+            .ok_or_else(|| invalid_data("section group refers to a symbol in a discarded section"))
+    }
+
+    /// Returns the new index of the symbol of a relocation, or `None` if the
+    /// symbol is a local symbol in a discarded section, such as the section
+    /// symbol of a duplicate COMDAT group that `.eh_frame` refers to.
+    fn new_relocation_symbol_index(&self, index: u32) -> io::Result<Option<u32>> {
+        self.new_symbol_indices
+            .get(index as usize)
+            .copied()
+            .ok_or_else(|| invalid_data("relocation refers to an invalid symbol"))
+    }
+
+    /// Returns whether a symbol is defined in a section that isn't copied.
+    fn is_discarded(&self, symbol: &Elf::Sym, index: usize) -> io::Result<bool> {
+        let section = self
+            .symbols
+            .symbol_section(self.endian(), symbol, SymbolIndex(index))
+            .map_err(read_error)?;
+        Ok(section.is_some_and(|section| {
+            !matches!(self.new_section_indices.get(section.0), Some(Some(_)))
+        }))
+    }
+
+    /// Finds the sections that aren't copied: the symbol and string tables,
+    /// which are merged, the address-significance table, which is optional,
+    /// and the COMDAT groups whose signature is already defined by a previous
+    /// object, with their members and relocations.
+    fn find_discarded_sections(
+        &self,
+        comdat_signatures: &mut HashSet<&'data [u8]>,
+    ) -> io::Result<Vec<bool>> {
+        let endian = self.endian();
+        let mut discarded = vec![false; self.sections.len()];
+        discarded[0] = true;
+        for (index, section) in self.sections.iter().enumerate() {
+            let is_merged_table = match section.sh_type(endian) {
+                elf::SHT_SYMTAB | elf::SHT_SYMTAB_SHNDX | SHT_LLVM_ADDRSIG => true,
+                elf::SHT_STRTAB => {
+                    index == self.symbols.string_section().0
+                        || index == usize::from(self.header.e_shstrndx(endian))
+                }
+                _ => false,
+            };
+            discarded[index] |= is_merged_table;
+            let Some((flags, members)) = section.group(endian, self.data).map_err(read_error)?
+            else {
+                continue;
+            };
+            if flags & elf::GRP_COMDAT == 0 {
+                continue;
+            }
+            let signature = self
+                .symbols
+                .symbol(SymbolIndex(section.sh_info(endian) as usize))
+                .and_then(|symbol| symbol.name(endian, self.symbols.strings()))
+                .map_err(read_error)?;
+            if !comdat_signatures.insert(signature) {
+                discarded[index] = true;
+                for member in members {
+                    let member = discarded
+                        .get_mut(member.get(endian) as usize)
+                        .ok_or_else(|| invalid_data("invalid ELF section group member"))?;
+                    *member = true;
+                }
+            }
+        }
+        for (index, section) in self.sections.iter().enumerate() {
+            if matches!(section.sh_type(endian), elf::SHT_REL | elf::SHT_RELA) {
+                let target = section.sh_info(endian) as usize;
+                discarded[index] |= target != 0 && discarded.get(target) != Some(&false);
+            }
+        }
+        Ok(discarded)
+    }
+}
+
+/// Merges ELF relocatable objects into a single relocatable object.
+///
+/// The sections of the objects are copied unchanged, so the symbol values and
+/// relocation offsets stay valid, and only the symbol tables are merged.
+/// Global symbols with the same name are resolved like a linker does: a
+/// definition takes precedence over a common symbol, which takes precedence
+/// over a weak definition, which takes precedence over a reference.
+fn merge_elf_objects<Elf: FileHeader<Endian = Endianness>>(
+    object_names: &[&str],
+    object_data: &[&[u8]],
+) -> io::Result<Vec<u8>> {
+    let with_name = |index: usize| {
+        move |err: Error| Error::new(err.kind(), format!("{}: {err}", object_names[index]))
+    };
+    let mut objects = Vec::with_capacity(object_data.len());
+    for (index, &data) in object_data.iter().enumerate() {
+        objects.push(ElfObject::<Elf>::parse(data).map_err(with_name(index))?);
+    }
+    let first_header = objects[0].header;
+    let endian = objects[0].endian();
+    let is_64 = first_header.is_class_64();
+    for (index, object) in objects.iter().enumerate().skip(1) {
+        let header = object.header;
+        if object.endian() != endian
+            || header.e_ident().os_abi != first_header.e_ident().os_abi
+            || header.e_machine(endian) != first_header.e_machine(endian)
+            || header.e_flags(endian) != first_header.e_flags(endian)
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "{}: the target of the object doesn't match {}",
+                    object_names[index], object_names[0]
+                ),
+            ));
+        }
+    }
+
+    // Number the copied sections in order, followed by the merged symbol
+    // table, string table and section name string table.
+    let mut comdat_signatures = HashSet::new();
+    let mut section_count = 1;
+    for (index, object) in objects.iter_mut().enumerate() {
+        let discarded = object
+            .find_discarded_sections(&mut comdat_signatures)
+            .map_err(with_name(index))?;
+        object.new_section_indices = discarded
+            .iter()
+            .map(|&discarded| {
+                (!discarded).then(|| {
+                    section_count += 1;
+                    section_count - 1
+                })
+            })
+            .collect();
+    }
+    let symbol_table_index = section_count;
+    let string_table_index = section_count + 1;
+    let section_names_index = section_count + 2;
+    if section_names_index >= u32::from(elf::SHN_LORESERVE) {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            "the merged object needs extended section numbering, which is not supported",
+        ));
+    }
+
+    // Local symbols must precede the global symbols, so the local symbols of
+    // every object are numbered first.
+    let mut local_symbols = Vec::new();
+    let mut symbol_count = 1;
+    for (index, object) in objects.iter_mut().enumerate() {
+        object.new_symbol_indices = vec![None; object.symbols.len()];
+        if let Some(null_symbol) = object.new_symbol_indices.first_mut() {
+            *null_symbol = Some(0);
+        }
+        for (symbol_index, symbol) in object.symbols.iter().enumerate().skip(1) {
+            if symbol.st_bind() != elf::STB_LOCAL
+                || object
+                    .is_discarded(symbol, symbol_index)
+                    .map_err(with_name(index))?
+            {
+                continue;
+            }
+            object.new_symbol_indices[symbol_index] = Some(symbol_count);
+            local_symbols.push((index, symbol_index));
+            symbol_count += 1;
+        }
+    }
+    let first_global_index = symbol_count;
+
+    let mut global_symbols: Vec<GlobalSymbol<'_>> = Vec::new();
+    let mut global_symbol_indices: HashMap<&[u8], usize> = HashMap::new();
+    let mut new_global_indices = Vec::new();
+    for (index, object) in objects.iter().enumerate() {
+        for (symbol_index, symbol) in object.symbols.iter().enumerate().skip(1) {
+            if symbol.st_bind() == elf::STB_LOCAL {
+                continue;
+            }
+            let name = symbol
+                .name(endian, object.symbols.strings())
+                .map_err(read_error)
+                .map_err(with_name(index))?;
+            let is_discarded = object
+                .is_discarded(symbol, symbol_index)
+                .map_err(with_name(index))?;
+            let definition = if symbol.is_undefined(endian) || is_discarded {
+                Definition::Undefined
+            } else if symbol.is_common(endian) {
+                Definition::Common
+            } else if symbol.is_weak() {
+                Definition::Weak
+            } else {
+                Definition::Strong
+            };
+            let global_symbol = GlobalSymbol {
+                name,
+                object: index,
+                index: symbol_index,
+                definition,
+                is_strong: !symbol.is_weak(),
+                visibility: symbol.st_visibility(),
+                common_size: symbol.st_size(endian).into(),
+                common_align: symbol.st_value(endian).into(),
+            };
+            let global_index = match global_symbol_indices.entry(name) {
+                Entry::Occupied(entry) => {
+                    let global_index = *entry.get();
+                    global_symbols[global_index].merge(global_symbol, object_names)?;
+                    global_index
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(global_symbols.len());
+                    global_symbols.push(global_symbol);
+                    global_symbols.len() - 1
+                }
+            };
+            new_global_indices.push((index, symbol_index, global_index as u32));
+        }
+    }
+    for (index, symbol_index, global_index) in new_global_indices {
+        objects[index].new_symbol_indices[symbol_index] = Some(first_global_index + global_index);
+    }
+
+    // Copy the sections after the file header.
+    let mut out = object_data[0][..size_of::<Elf>()].to_vec();
+    let mut section_headers = vec![0; size_of::<Elf::SectionHeader>()];
+    let mut section_names = StringTableBuilder::new(&[0]);
+    let too_large = || invalid_data("ELF object is too large");
+    // The offsets of `sh_offset`, `sh_size`, `sh_link` and `sh_info`.
+    let (sh_offset, sh_size, sh_link, sh_info) = if is_64 {
+        (24, 32, 40, 44)
+    } else {
+        (16, 20, 24, 28)
+    };
+    for (index, object) in objects.iter().enumerate() {
+        let mut copy_sections = |out: &mut Vec<u8>, section_headers: &mut Vec<u8>| {
+            for (section_index, section) in object.sections.iter().enumerate() {
+                if object.new_section_indices[section_index].is_none() {
+                    continue;
+                }
+                let links_symbol_table = !object.symbols.is_empty()
+                    && section.sh_link(endian) as usize == object.symbols.section().0;
+                let contents = match section.sh_type(endian) {
+                    elf::SHT_NOBITS => Cow::Borrowed(&[][..]),
+                    elf::SHT_REL | elf::SHT_RELA if links_symbol_table => {
+                        let contents = section.data(endian, object.data).map_err(read_error)?;
+                        let mut new_contents = contents.to_vec();
+                        remap_elf_relocations(
+                            object.header,
+                            object.data,
+                            section,
+                            &mut new_contents,
+                            &|index| object.new_relocation_symbol_index(index),
+                        )?;
+                        Cow::Owned(new_contents)
+                    }
+                    elf::SHT_GROUP => {
+                        // The group flags are followed by the indices of the
+                        // members.
+                        let (flags, members) = section
+                            .group(endian, object.data)
+                            .map_err(read_error)?
+                            .ok_or_else(|| invalid_data("invalid ELF section group"))?;
+                        let mut new_contents = vec![0; 4 * (members.len() + 1)];
+                        write_u32(&mut new_contents, 0, endian, flags);
+                        for (i, member) in members.iter().enumerate() {
+                            let member = object.new_section_index(member.get(endian))?;
+                            write_u32(&mut new_contents, 4 * (i as u64 + 1), endian, member);
+                        }
+                        Cow::Owned(new_contents)
+                    }
+                    _ => Cow::Borrowed(section.data(endian, object.data).map_err(read_error)?),
+                };
+                if section.sh_type(endian) != elf::SHT_NOBITS {
+                    pad_to(out, section.sh_addralign(endian).into());
+                }
+                let section_offset = out.len() as u64;
+                out.extend_from_slice(&contents);
+
+                let offset = section_headers.len() as u64;
+                section_headers.extend_from_slice(object::pod::bytes_of(section));
+                let name = object
+                    .sections
+                    .section_name(endian, section)
+                    .map_err(read_error)?;
+                // `sh_name` is the first field of both `SectionHeader32` and
+                // `SectionHeader64`.
+                write_u32(section_headers, offset, endian, section_names.add(name)?);
+                let new_size = if section.sh_type(endian) == elf::SHT_NOBITS {
+                    section.sh_size(endian).into()
+                } else {
+                    contents.len() as u64
+                };
+                if is_64 {
+                    write_u64(section_headers, offset + sh_offset, endian, section_offset);
+                    write_u64(section_headers, offset + sh_size, endian, new_size);
+                } else {
+                    let section_offset = u32::try_from(section_offset).map_err(|_| too_large())?;
+                    write_u32(section_headers, offset + sh_offset, endian, section_offset);
+                    write_u32(section_headers, offset + sh_size, endian, new_size as u32);
+                }
+                let link = section.sh_link(endian);
+                let new_link = if links_symbol_table {
+                    symbol_table_index
+                } else if link != 0 {
+                    object.new_section_index(link)?
+                } else {
+                    0
+                };
+                write_u32(section_headers, offset + sh_link, endian, new_link);
+                let info = section.sh_info(endian);
+                let new_info = match section.sh_type(endian) {
+                    elf::SHT_GROUP => object.new_symbol_index(info)?,
+                    elf::SHT_REL | elf::SHT_RELA if info != 0 => object.new_section_index(info)?,
+                    _ if section.sh_flags(endian).into() & u64::from(elf::SHF_INFO_LINK) != 0 => {
+                        object.new_section_index(info)?
+                    }
+                    _ => info,
+                };
+                write_u32(section_headers, offset + sh_info, endian, new_info);
+            }
+            Ok(())
+        };
+        copy_sections(&mut out, &mut section_headers).map_err(with_name(index))?;
+    }
+
+    // Write the merged symbol table, which starts with the null symbol.
+    let symbol_size = size_of::<Elf::Sym>();
+    let mut symbol_table = vec![0; symbol_size];
+    let mut strings = StringTableBuilder::new(&[0]);
+    // The offsets of `st_info`, `st_other`, `st_shndx`, `st_value` and
+    // `st_size`.
+    let (st_info, st_other, st_shndx, st_value, st_size) = if is_64 {
+        (4, 5, 6, 8, 16)
+    } else {
+        (12, 13, 14, 4, 8)
+    };
+    let mut copy_symbol = |symbol_table: &mut Vec<u8>, index: usize, symbol_index: usize| {
+        let object = &objects[index];
+        let symbol = object
+            .symbols
+            .symbol(SymbolIndex(symbol_index))
+            .map_err(read_error)?;
+        let offset = symbol_table.len() as u64;
+        symbol_table.extend_from_slice(object::pod::bytes_of(symbol));
+        let name = symbol
+            .name(endian, object.symbols.strings())
+            .map_err(read_error)?;
+        let name = if name.is_empty() {
+            0
+        } else {
+            strings.add(name)?
+        };
+        // `st_name` is the first field of both `Sym32` and `Sym64`.
+        write_u32(symbol_table, offset, endian, name);
+        let shndx = symbol.st_shndx(endian);
+        if shndx != elf::SHN_UNDEF && shndx < elf::SHN_LORESERVE {
+            let shndx = object.new_section_index(shndx.into())? as u16;
+            write_u16(symbol_table, offset + st_shndx, endian, shndx);
+        }
+        Ok::<_, Error>(offset)
+    };
+    for &(index, symbol_index) in &local_symbols {
+        copy_symbol(&mut symbol_table, index, symbol_index).map_err(with_name(index))?;
+    }
+    for global_symbol in &global_symbols {
+        let index = global_symbol.object;
+        let offset =
+            copy_symbol(&mut symbol_table, index, global_symbol.index).map_err(with_name(index))?;
+        let symbol = objects[index]
+            .symbols
+            .symbol(SymbolIndex(global_symbol.index))
+            .map_err(read_error)?;
+        let st_other_value = (symbol.st_other() & !0x3) | global_symbol.visibility;
+        symbol_table[(offset + st_other) as usize] = st_other_value;
+        match global_symbol.definition {
+            Definition::Undefined => {
+                // The symbol may be copied from a definition in a discarded
+                // COMDAT group, and is only weak if all references are weak.
+                let binding = if global_symbol.is_strong {
+                    elf::STB_GLOBAL
+                } else {
+                    elf::STB_WEAK
+                };
+                symbol_table[(offset + st_info) as usize] = (binding << 4) | symbol.st_type();
+                write_u16(&mut symbol_table, offset + st_shndx, endian, elf::SHN_UNDEF);
+                if is_64 {
+                    write_u64(&mut symbol_table, offset + st_value, endian, 0);
+                    write_u64(&mut symbol_table, offset + st_size, endian, 0);
+                } else {
+                    write_u32(&mut symbol_table, offset + st_value, endian, 0);
+                    write_u32(&mut symbol_table, offset + st_size, endian, 0);
+                }
+            }
+            Definition::Common => {
+                let (size, align) = (global_symbol.common_size, global_symbol.common_align);
+                if is_64 {
+                    write_u64(&mut symbol_table, offset + st_value, endian, align);
+                    write_u64(&mut symbol_table, offset + st_size, endian, size);
+                } else {
+                    write_u32(&mut symbol_table, offset + st_value, endian, align as u32);
+                    write_u32(&mut symbol_table, offset + st_size, endian, size as u32);
+                }
+            }
+            Definition::Weak | Definition::Strong => {}
+        }
+    }
+
+    // Write the symbol table, string table and section name string table,
+    // followed by the section headers.
+    let word_size = if is_64 { 8 } else { 4 };
+    let symbol_table_name = section_names.add(b".symtab")?;
+    let string_table_name = section_names.add(b".strtab")?;
+    let section_names_name = section_names.add(b".shstrtab")?;
+    let push_section = |out: &mut Vec<u8>,
+                        section_headers: &mut Vec<u8>,
+                        name: u32,
+                        sh_type: u32,
+                        (contents, align, entsize): (&[u8], u64, u64),
+                        (link, info): (u32, u32)| {
+        pad_to(out, align);
+        let offset = u64::try_from(out.len()).map_err(|_| too_large())?;
+        out.extend_from_slice(contents);
+        let size = contents.len() as u64;
+        if is_64 {
+            section_headers.extend_from_slice(object::pod::bytes_of(&elf::SectionHeader64 {
+                sh_name: U32::new(endian, name),
+                sh_type: U32::new(endian, sh_type),
+                sh_flags: U64::new(endian, 0),
+                sh_addr: U64::new(endian, 0),
+                sh_offset: U64::new(endian, offset),
+                sh_size: U64::new(endian, size),
+                sh_link: U32::new(endian, link),
+                sh_info: U32::new(endian, info),
+                sh_addralign: U64::new(endian, align),
+                sh_entsize: U64::new(endian, entsize),
+            }));
+        } else {
+            let offset = u32::try_from(offset).map_err(|_| too_large())?;
+            section_headers.extend_from_slice(object::pod::bytes_of(&elf::SectionHeader32 {
+                sh_name: U32::new(endian, name),
+                sh_type: U32::new(endian, sh_type),
+                sh_flags: U32::new(endian, 0),
+                sh_addr: U32::new(endian, 0),
+                sh_offset: U32::new(endian, offset),
+                sh_size: U32::new(endian, size as u32),
+                sh_link: U32::new(endian, link),
+                sh_info: U32::new(endian, info),
+                sh_addralign: U32::new(endian, align as u32),
+                sh_entsize: U32::new(endian, entsize as u32),
+            }));
+        }
+        Ok::<_, Error>(())
+    };
+    push_section(
+        &mut out,
+        &mut section_headers,
+        symbol_table_name,
+        elf::SHT_SYMTAB,
+        (&symbol_table, word_size, symbol_size as u64),
+        (string_table_index, first_global_index),
+    )?;
+    push_section(
+        &mut out,
+        &mut section_headers,
+        string_table_name,
+        elf::SHT_STRTAB,
+        (&strings.data, 1, 0),
+        (0, 0),
+    )?;
+    let section_names = section_names.data;
+    push_section(
+        &mut out,
+        &mut section_headers,
+        section_names_name,
+        elf::SHT_STRTAB,
+        (&section_names, 1, 0),
+        (0, 0),
+    )?;
+    pad_to(&mut out, word_size);
+    let section_headers_offset = out.len() as u64;
+    out.extend_from_slice(&section_headers);
+
+    // The offsets of `e_shoff`, `e_shentsize`, `e_shnum` and `e_shstrndx`.
+    let (e_shoff, e_shentsize) = if is_64 { (40, 58) } else { (32, 46) };
+    if is_64 {
+        write_u64(&mut out, e_shoff, endian, section_headers_offset);
+    } else {
+        let section_headers_offset =
+            u32::try_from(section_headers_offset).map_err(|_| too_large())?;
+        write_u32(&mut out, e_shoff, endian, section_headers_offset);
+    }
+    let section_header_size = size_of::<Elf::SectionHeader>() as u16;
+    write_u16(&mut out, e_shentsize, endian, section_header_size);
+    write_u16(
+        &mut out,
+        e_shentsize + 2,
+        endian,
+        section_names_index as u16 + 1,
+    );
+    write_u16(
+        &mut out,
+        e_shentsize + 4,
+        endian,
+        section_names_index as u16,
+    );
+    Ok(out)
+}
+
+/// Merges the relocatable object members into a single relocatable object
+/// member named `member_name`, like the single-object static libraries that
+/// Apple's `libtool` produces.
+///
+/// The sections of the objects are concatenated, their symbol tables are
+/// merged, and their relocations are adjusted to the merged symbol table.
+/// COMDAT groups that are already defined by a previous member are discarded,
+/// and relocations that refer to their sections, such as those of `.eh_frame`
+/// and the debug sections, are replaced by `R_*_NONE` relocations, as `ld -r`
+/// does. The merged member replaces the first object member, and members that
+/// aren't objects are kept unchanged. Only ELF objects are supported, and all
+/// objects must have the same target.
+pub fn merge_archive_members<'a>(
+    members: Vec<NewArchiveMember<'a>>,
+    member_name: &str,
+) -> io::Result<Vec<NewArchiveMember<'a>>> {
+    let mut kind = None;
+    let mut is_object = Vec::with_capacity(members.len());
+    for member in &members {
+        match FileKind::parse((*member.buf).as_ref()) {
+            Ok(member_kind @ (FileKind::Elf32 | FileKind::Elf64)) => {
+                if kind.is_some_and(|kind| kind != member_kind) {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!(
+                            "{}: {member_kind:?} files can't be merged with {:?} files",
+                            member.member_name,
+                            kind.unwrap()
+                        ),
+                    ));
+                }
+                kind = Some(member_kind);
+                is_object.push(true);
+            }
+            Err(_) => is_object.push(false),
+            Ok(kind) => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    format!(
+                        "{}: merging members is not supported for {kind:?} files",
+                        member.member_name
+                    ),
+                ));
+            }
+        }
+    }
+    let Some(kind) = kind else {
+        return Ok(members);
+    };
+
+    let objects = members
+        .iter()
+        .zip(&is_object)
+        .filter(|(_, &is_object)| is_object);
+    let object_names = objects
+        .clone()
+        .map(|(member, _)| member.member_name.as_str())
+        .collect::<Vec<_>>();
+    let object_data = objects
+        .map(|(member, _)| (*member.buf).as_ref())
+        .collect::<Vec<_>>();
+    let merged = match kind {
+        FileKind::Elf32 => {
+            merge_elf_objects::<elf::FileHeader32<Endianness>>(&object_names, &object_data)?
+        }
+        _ => merge_elf_objects::<elf::FileHeader64<Endianness>>(&object_names, &object_data)?,
+    };
+
+    let mut merged = Some(merged);
+    let mut merged_members = Vec::with_capacity(members.len());
+    for (mut member, is_object) in members.into_iter().zip(is_object) {
+        if is_object {
+            // The merged object takes the place and metadata of the first
+            // object.
+            let Some(merged) = merged.take() else {
+                continue;
+            };
+            member.buf = Box::new(merged);
+            member.member_name = member_name.to_string();
+        }
+        merged_members.push(member);
+    }
+    Ok(merged_members)
+}
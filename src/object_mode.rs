@@ -0,0 +1,118 @@
+// Derived from code in LLVM, which is:
+// Part of the LLVM Project, under the Apache License v2.0 with LLVM Exceptions.
+// See https://llvm.org/LICENSE.txt for license information.
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+//! Selection of archive members by object file bitness, as done by the `-X`
+//! option and `OBJECT_MODE` environment variable of AIX `ar`.
+
+use std::fmt;
+use std::io::{self, Error, ErrorKind};
+use std::str::FromStr;
+
+use crate::macho_universal_writer::{get_member_symbolic_data, FatMembers};
+use crate::NewArchiveMember;
+
+/// The object files that are processed, by bitness.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ObjectMode {
+    /// `-X32`: only 32-bit object files.
+    #[default]
+    Bit32,
+    /// `-X64`: only 64-bit object files.
+    Bit64,
+    /// `-X32_64`: both 32-bit and 64-bit object files.
+    Bit32_64,
+    /// `-Xany`: all object files.
+    Any,
+}
+
+impl FromStr for ObjectMode {
+    type Err = Error;
+
+    /// Parses the value of `-X` or `OBJECT_MODE`, such as `32_64`.
+    fn from_str(s: &str) -> io::Result<Self> {
+        match s {
+            "32" => Ok(ObjectMode::Bit32),
+            "64" => Ok(ObjectMode::Bit64),
+            "32_64" => Ok(ObjectMode::Bit32_64),
+            "any" => Ok(ObjectMode::Any),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid object mode: {s}"),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ObjectMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ObjectMode::Bit32 => "32",
+            ObjectMode::Bit64 => "64",
+            ObjectMode::Bit32_64 => "32_64",
+            ObjectMode::Any => "any",
+        })
+    }
+}
+
+impl ObjectMode {
+    // Derived from isValidInBitMode in llvm-ar.
+    /// Returns true if `member` is processed in this mode. Members that aren't
+    /// object files are always processed.
+    pub fn is_valid(self, member: &NewArchiveMember<'_>) -> io::Result<bool> {
+        if matches!(self, ObjectMode::Bit32_64 | ObjectMode::Any) {
+            return Ok(true);
+        }
+        let object_reader = member.object_reader;
+        let buf = get_member_symbolic_data(
+            (*member.buf).as_ref(),
+            FatMembers::Ignore,
+            &member.member_name,
+        )?;
+        if !(object_reader.get_symbols)(buf, &mut |_| Ok(()))? {
+            return Ok(true);
+        }
+        let is_64_bit = (object_reader.is_64_bit_object_file)(buf);
+        Ok(is_64_bit == (self == ObjectMode::Bit64))
+    }
+}
+
+/// Splits `members` into those that are processed in `mode` and those that
+/// aren't, such as the 32-bit object files with [ObjectMode::Bit64].
+pub fn filter_by_object_mode<'a>(
+    members: Vec<NewArchiveMember<'a>>,
+    mode: ObjectMode,
+) -> io::Result<(Vec<NewArchiveMember<'a>>, Vec<NewArchiveMember<'a>>)> {
+    let mut valid = Vec::with_capacity(members.len());
+    let mut invalid = Vec::new();
+    for member in members {
+        if mode.is_valid(&member)? {
+            valid.push(member);
+        } else {
+            invalid.push(member);
+        }
+    }
+    Ok((valid, invalid))
+}
+
+/// Checks that all of `members` are processed in `mode`, and otherwise returns
+/// an error that names the members that aren't.
+pub fn check_object_mode(members: &[NewArchiveMember<'_>], mode: ObjectMode) -> io::Result<()> {
+    let mut invalid_names = Vec::new();
+    for member in members {
+        if !mode.is_valid(member)? {
+            invalid_names.push(member.member_name.as_str());
+        }
+    }
+    if invalid_names.is_empty() {
+        return Ok(());
+    }
+    Err(Error::new(
+        ErrorKind::InvalidInput,
+        format!(
+            "archive members are not valid with the object mode -X{mode}: {}",
+            invalid_names.join(", ")
+        ),
+    ))
+}
@@ -7,6 +7,7 @@
 
 use std::{io, mem::offset_of};
 
+use object::read::xcoff as xcoff_read;
 use object::{pe::ImportObjectHeader, xcoff, Object, ObjectSymbol};
 
 use crate::coff_import_file;
@@ -25,12 +26,122 @@ fn is_archive_symbol(sym: &object::read::Symbol<'_, '_>) -> bool {
     true
 }
 
+// The loader section symbol flags from AIX's <loader.h>.
+const L_EXPORT: u8 = 0x10;
+const L_IMPORT: u8 = 0x40;
+
+fn invalid_loader_section() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "Invalid XCOFF loader section")
+}
+
+fn read_bytes<const N: usize>(data: &[u8], offset: u64) -> io::Result<[u8; N]> {
+    usize::try_from(offset)
+        .ok()
+        .and_then(|offset| data.get(offset..)?.first_chunk::<N>())
+        .copied()
+        .ok_or_else(invalid_loader_section)
+}
+
+fn read_u32(data: &[u8], offset: u64) -> io::Result<u32> {
+    read_bytes(data, offset).map(u32::from_be_bytes)
+}
+
+fn read_u64(data: &[u8], offset: u64) -> io::Result<u64> {
+    read_bytes(data, offset).map(u64::from_be_bytes)
+}
+
+/// Iterates over the symbols that an XCOFF shared object exports through its
+/// loader section, as AIX `ar` puts those in the global symbol table instead
+/// of the symbols in the symbol table. Returns false if `buf` isn't a shared
+/// object with a loader section.
+fn get_xcoff_loader_symbols<Xcoff: xcoff_read::FileHeader>(
+    buf: &[u8],
+    f: &mut dyn FnMut(&[u8]) -> io::Result<()>,
+) -> io::Result<bool> {
+    use xcoff_read::SectionHeader;
+
+    let mut offset = 0;
+    let Ok(header) = Xcoff::parse(buf, &mut offset) else {
+        return Ok(false);
+    };
+    if header.f_flags() & xcoff::F_SHROBJ == 0 {
+        return Ok(false);
+    }
+    let sections = header
+        .aux_header(buf, &mut offset)
+        .and_then(|_| header.sections(buf, &mut offset))
+        .map_err(|_| invalid_loader_section())?;
+    let Some(loader) = sections
+        .iter()
+        .find(|section| section.s_flags() as u16 & xcoff::STYP_LOADER != 0)
+    else {
+        return Ok(false);
+    };
+    let loader = usize::try_from(loader.s_scnptr().into())
+        .ok()
+        .zip(usize::try_from(loader.s_size().into()).ok())
+        .and_then(|(start, size)| buf.get(start..start.checked_add(size)?))
+        .ok_or_else(invalid_loader_section)?;
+
+    // See LoaderSectionHeader32/64 in LLVM's XCOFF.h.
+    let num_syms = read_u32(loader, 4)?;
+    let (string_table_size, string_table_offset, symbols_offset) = if header.is_type_64() {
+        (
+            read_u32(loader, 20)?,
+            read_u64(loader, 32)?,
+            read_u64(loader, 40)?,
+        )
+    } else {
+        (read_u32(loader, 24)?, read_u32(loader, 28)?.into(), 32)
+    };
+    let string_table = usize::try_from(string_table_offset)
+        .ok()
+        .and_then(|start| loader.get(start..)?.get(..string_table_size as usize))
+        .ok_or_else(invalid_loader_section)?;
+
+    const SYMBOL_SIZE: u64 = 24;
+    for index in 0..u64::from(num_syms) {
+        let symbol = symbols_offset + index * SYMBOL_SIZE;
+        let [smtype] = read_bytes(loader, symbol + 14)?;
+        if smtype & L_EXPORT == 0 || smtype & L_IMPORT != 0 {
+            continue;
+        }
+        let name = if header.is_type_64() {
+            None
+        } else {
+            // Names of up to 8 bytes are stored in the entry itself.
+            Some(read_bytes::<8>(loader, symbol)?).filter(|name| name[..4] != [0; 4])
+        };
+        if let Some(name) = name {
+            let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+            f(&name[..len])?;
+        } else {
+            let name_offset = read_u32(loader, symbol + if header.is_type_64() { 8 } else { 4 })?;
+            let name = string_table
+                .get(name_offset as usize..)
+                .and_then(|name| name.split(|&c| c == 0).next())
+                .ok_or_else(invalid_loader_section)?;
+            f(name)?;
+        }
+    }
+    Ok(true)
+}
+
 pub fn get_native_object_symbols(
     buf: &[u8],
     f: &mut dyn FnMut(&[u8]) -> io::Result<()>,
 ) -> io::Result<bool> {
     // FIXME match what LLVM does
 
+    let has_loader_symbols = match object::FileKind::parse(buf) {
+        Ok(object::FileKind::Xcoff32) => get_xcoff_loader_symbols::<xcoff::FileHeader32>(buf, f)?,
+        Ok(object::FileKind::Xcoff64) => get_xcoff_loader_symbols::<xcoff::FileHeader64>(buf, f)?,
+        _ => false,
+    };
+    if has_loader_symbols {
+        return Ok(true);
+    }
+
     match object::File::parse(buf) {
         Ok(file) => {
             for sym in file.symbols() {
@@ -53,6 +164,37 @@ pub fn get_native_object_symbols(
     }
 }
 
+/// Iterates over the undefined symbols that the object file references, for
+/// [crate::select_members]. Weak undefined ELF symbols are skipped, as a linker
+/// doesn't include archive members to resolve them.
+pub fn get_native_object_undefined_symbols(
+    buf: &[u8],
+    f: &mut dyn FnMut(&[u8]) -> io::Result<()>,
+) -> io::Result<bool> {
+    let Ok(file) = object::File::parse(buf) else {
+        // COFF import objects and other files don't reference any symbols.
+        return Ok(false);
+    };
+    for sym in file.symbols() {
+        if sym.kind() == object::SymbolKind::File || sym.kind() == object::SymbolKind::Section {
+            continue;
+        }
+        if !sym.is_undefined() || !sym.is_global() {
+            continue;
+        }
+        if file.format() == object::BinaryFormat::Elf && sym.is_weak() {
+            continue;
+        }
+        let name = sym
+            .name_bytes()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+        if !name.is_empty() {
+            f(name)?;
+        }
+    }
+    Ok(true)
+}
+
 pub fn is_ec_object(obj: &[u8]) -> bool {
     match object::FileKind::parse(obj) {
         Ok(object::FileKind::Coff) => {
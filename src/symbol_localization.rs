@@ -0,0 +1,572 @@
+//! Localization of the global symbols of archive members that aren't in an
+//! allowlist, like `objcopy --keep-global-symbols` applied to every member.
+
+use std::collections::HashSet;
+use std::io::{self, Error, ErrorKind};
+use std::mem::size_of;
+
+use object::pod::bytes_of;
+use object::read::coff::{CoffHeader, ImageSymbol as _};
+use object::read::elf::{FileHeader, Rel as _, Rela as _, SectionHeader as _, Sym as _};
+use object::read::macho::{MachHeader, Nlist as _, Section as _, Segment as _};
+use object::{elf, macho, pe, Endian as _, Endianness, FileKind};
+
+use crate::coff_import_file::get_short_import_symbol;
+use crate::macho_universal_writer::is_bitcode;
+use crate::object_editing::{invalid_data, read_error, write_u32, write_u64};
+use crate::NewArchiveMember;
+
+/// The section type of the LLVM address-significance table, which holds the
+/// indices of symbols as ULEB128 values.
+pub(crate) const SHT_LLVM_ADDRSIG: u32 = 0x6fff_4c03;
+
+/// The offsets of section header fields in `SectionHeader32` and
+/// `SectionHeader64`. `sh_info` is 32-bit in both.
+const SH_OFFSET: (u64, u64) = (16, 24);
+const SH_SIZE: (u64, u64) = (20, 32);
+const SH_INFO: (u64, u64) = (28, 44);
+
+fn common_symbol_error(name: &[u8]) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!(
+            "common symbol {} can't be made local",
+            String::from_utf8_lossy(name)
+        ),
+    )
+}
+
+/// Returns the order of symbols that puts the local symbols first, followed by
+/// the localized symbols and then the remaining global symbols, and the new
+/// index of each symbol in that order.
+fn get_symbol_order(groups: &[&[usize]]) -> (Vec<usize>, Vec<u32>) {
+    let order = groups.concat();
+    let mut new_indices = vec![0; order.len()];
+    for (new_index, &index) in order.iter().enumerate() {
+        new_indices[index] = new_index as u32;
+    }
+    (order, new_indices)
+}
+
+fn get_new_index(new_indices: &[u32], index: u32) -> io::Result<u32> {
+    new_indices
+        .get(index as usize)
+        .copied()
+        .ok_or_else(|| invalid_data("invalid symbol index"))
+}
+
+fn read_uleb128(data: &mut &[u8]) -> io::Result<u64> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let (&byte, rest) = data
+            .split_first()
+            .ok_or_else(|| invalid_data("truncated ULEB128 value"))?;
+        *data = rest;
+        if shift >= 64 {
+            return Err(invalid_data("ULEB128 value is too large"));
+        }
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+fn write_uleb128(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Rewrites the ULEB128 symbol indices of the `SHT_LLVM_ADDRSIG` section
+/// `data` with `new_index`. Symbols for which `new_index` returns `None` are
+/// removed from the table.
+pub(crate) fn remap_elf_addrsig(
+    mut data: &[u8],
+    new_index: &dyn Fn(u32) -> io::Result<Option<u32>>,
+) -> io::Result<Vec<u8>> {
+    let mut new_data = Vec::new();
+    while !data.is_empty() {
+        let index = u32::try_from(read_uleb128(&mut data)?)
+            .map_err(|_| invalid_data("invalid symbol index"))?;
+        if let Some(index) = new_index(index)? {
+            write_uleb128(&mut new_data, index.into());
+        }
+    }
+    Ok(new_data)
+}
+
+/// Rewrites the symbol indices of the relocations of the ELF relocation
+/// section `section` with `new_index`, in `out`, which starts with a copy of
+/// the section data.
+///
+/// If `new_index` returns `None`, the symbol is in a section that is
+/// discarded, and the relocation is replaced by a `R_*_NONE` relocation of the
+/// null symbol without an addend, as `ld -r` does.
+pub(crate) fn remap_elf_relocations<Elf: FileHeader<Endian = Endianness>>(
+    header: &Elf,
+    data: &[u8],
+    section: &Elf::SectionHeader,
+    out: &mut [u8],
+    new_index: &dyn Fn(u32) -> io::Result<Option<u32>>,
+) -> io::Result<()> {
+    let endian = header.endian().map_err(read_error)?;
+    let is_64 = header.is_class_64();
+    let is_mips64el = header.is_mips64el(endian);
+    if let Some((relocations, _)) = section.rel(endian, data).map_err(read_error)? {
+        for (i, relocation) in relocations.iter().enumerate() {
+            let (symbol, r_type) = match new_index(relocation.r_sym(endian))? {
+                Some(symbol) => (symbol, relocation.r_type(endian)),
+                None => (0, 0),
+            };
+            let offset = (i * size_of::<Elf::Rel>()) as u64;
+            if is_64 {
+                let r_info = elf::Rel64::r_info(endian, symbol, r_type);
+                write_u64(out, offset + 8, endian, r_info.get(endian));
+            } else {
+                let r_info = elf::Rel32::r_info(endian, symbol, r_type as u8);
+                write_u32(out, offset + 4, endian, r_info.get(endian));
+            }
+        }
+    }
+    if let Some((relocations, _)) = section.rela(endian, data).map_err(read_error)? {
+        for (i, relocation) in relocations.iter().enumerate() {
+            let new_symbol = new_index(relocation.r_sym(endian, is_mips64el))?;
+            let (symbol, r_type) = match new_symbol {
+                Some(symbol) => (symbol, relocation.r_type(endian, is_mips64el)),
+                None => (0, 0),
+            };
+            let offset = (i * size_of::<Elf::Rela>()) as u64;
+            // `r_addend` follows `r_offset` and `r_info`.
+            if is_64 {
+                let r_info = elf::Rela64::r_info(endian, is_mips64el, symbol, r_type);
+                write_u64(out, offset + 8, endian, r_info.get(endian));
+                if new_symbol.is_none() {
+                    write_u64(out, offset + 16, endian, 0);
+                }
+            } else {
+                let r_info = elf::Rela32::r_info(endian, symbol, r_type as u8);
+                write_u32(out, offset + 4, endian, r_info.get(endian));
+                if new_symbol.is_none() {
+                    write_u32(out, offset + 8, endian, 0);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Makes the global definitions of an ELF object that aren't exported local.
+///
+/// Local symbols must precede the global symbols, so the symbol table is
+/// reordered, and the relocations, section groups, extended section indices
+/// and address-significance table are updated with the new symbol indices.
+fn localize_elf_symbols<Elf: FileHeader<Endian = Endianness>>(
+    data: &[u8],
+    is_exported: &dyn Fn(&[u8]) -> bool,
+) -> io::Result<Option<Vec<u8>>> {
+    let header = Elf::parse(data).map_err(read_error)?;
+    let endian = header.endian().map_err(read_error)?;
+    let is_64 = header.is_class_64();
+    let sections = header.sections(endian, data).map_err(read_error)?;
+    let symbols = sections
+        .symbols(endian, data, elf::SHT_SYMTAB)
+        .map_err(read_error)?;
+    if symbols.is_empty() {
+        return Ok(None);
+    }
+
+    let mut locals = Vec::new();
+    let mut localized = Vec::new();
+    let mut globals = Vec::new();
+    for (index, symbol) in symbols.iter().enumerate() {
+        if symbol.st_bind() == elf::STB_LOCAL {
+            locals.push(index);
+            continue;
+        }
+        if symbol.st_shndx(endian) == elf::SHN_UNDEF
+            || matches!(symbol.st_type(), elf::STT_FILE | elf::STT_SECTION)
+        {
+            globals.push(index);
+            continue;
+        }
+        let name = symbol.name(endian, symbols.strings()).map_err(read_error)?;
+        if is_exported(name) {
+            globals.push(index);
+        } else if symbol.st_shndx(endian) == elf::SHN_COMMON {
+            return Err(common_symbol_error(name));
+        } else {
+            localized.push(index);
+        }
+    }
+    if localized.is_empty() {
+        return Ok(None);
+    }
+    let local_count = locals.len() + localized.len();
+    let (order, new_indices) = get_symbol_order(&[&locals, &localized, &globals]);
+
+    let section_header_offset = |index: usize| {
+        header.e_shoff(endian).into() + index as u64 * u64::from(header.e_shentsize(endian))
+    };
+    let write_section_field = |out: &mut [u8], index: usize, field: (u64, u64), value: u64| {
+        if is_64 {
+            write_u64(out, section_header_offset(index) + field.1, endian, value);
+        } else {
+            let value =
+                u32::try_from(value).map_err(|_| invalid_data("ELF object is too large"))?;
+            write_u32(out, section_header_offset(index) + field.0, endian, value);
+        }
+        io::Result::Ok(())
+    };
+
+    // Reorder the symbols, and change the binding of the localized symbols.
+    let mut out = data.to_vec();
+    let symbol_size = size_of::<Elf::Sym>();
+    let symbol_table_offset: usize = sections
+        .section(symbols.section())
+        .map_err(read_error)?
+        .sh_offset(endian)
+        .into() as usize;
+    // `st_info` follows `st_name`, `st_value` and `st_size` in `Sym32`, and
+    // `st_name` in `Sym64`.
+    let st_info_offset = if is_64 { 4 } else { 12 };
+    for (new_index, &index) in order.iter().enumerate() {
+        let symbol = &data[symbol_table_offset + index * symbol_size..][..symbol_size];
+        let new_offset = symbol_table_offset + new_index * symbol_size;
+        out[new_offset..new_offset + symbol_size].copy_from_slice(symbol);
+        if new_index >= locals.len() && new_index < local_count {
+            out[new_offset + st_info_offset] =
+                (elf::STB_LOCAL << 4) | (symbol[st_info_offset] & 0xf);
+        }
+    }
+    // `sh_info` of a symbol table is the index of the first global symbol.
+    let sh_info_offset =
+        |index: usize| section_header_offset(index) + if is_64 { SH_INFO.1 } else { SH_INFO.0 };
+    write_u32(
+        &mut out,
+        sh_info_offset(symbols.section().0),
+        endian,
+        local_count as u32,
+    );
+
+    for (section_index, section) in sections.iter().enumerate() {
+        if section.sh_link(endian) as usize != symbols.section().0 {
+            continue;
+        }
+        let section_offset: u64 = section.sh_offset(endian).into();
+        match section.sh_type(endian) {
+            elf::SHT_SYMTAB_SHNDX => {
+                let section_data = section.data(endian, data).map_err(read_error)?;
+                for (new_index, &index) in order.iter().enumerate() {
+                    let entry = section_data
+                        .get(index * 4..index * 4 + 4)
+                        .ok_or_else(|| invalid_data("invalid extended section index table"))?;
+                    let new_offset = section_offset as usize + new_index * 4;
+                    out[new_offset..new_offset + 4].copy_from_slice(entry);
+                }
+            }
+            elf::SHT_REL | elf::SHT_RELA => {
+                remap_elf_relocations(
+                    header,
+                    data,
+                    section,
+                    &mut out[section_offset as usize..],
+                    &|index| get_new_index(&new_indices, index).map(Some),
+                )?;
+            }
+            elf::SHT_GROUP => {
+                // `sh_info` of a section group is the index of its signature
+                // symbol.
+                let symbol = get_new_index(&new_indices, section.sh_info(endian))?;
+                write_u32(&mut out, sh_info_offset(section_index), endian, symbol);
+            }
+            SHT_LLVM_ADDRSIG => {
+                let section_data = section.data(endian, data).map_err(read_error)?;
+                let new_data = remap_elf_addrsig(section_data, &|index| {
+                    get_new_index(&new_indices, index).map(Some)
+                })?;
+                // The new indices may need more bytes, in which case the table
+                // is moved to the end of the file.
+                let section_size: u64 = section.sh_size(endian).into();
+                let new_offset = if new_data.len() as u64 <= section_size {
+                    section_offset
+                } else {
+                    let new_offset = out.len() as u64;
+                    out.resize(out.len() + new_data.len(), 0);
+                    write_section_field(&mut out, section_index, SH_OFFSET, new_offset)?;
+                    new_offset
+                };
+                out[new_offset as usize..][..new_data.len()].copy_from_slice(&new_data);
+                write_section_field(&mut out, section_index, SH_SIZE, new_data.len() as u64)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(Some(out))
+}
+
+/// Makes the external definitions of a Mach-O object that aren't exported
+/// local.
+///
+/// The dynamic symbol table command groups the local, external and undefined
+/// symbols, so the symbol table is reordered, and the relocations and the
+/// indirect symbol table are updated with the new symbol indices.
+fn localize_macho_symbols<Mach: MachHeader<Endian = Endianness>>(
+    data: &[u8],
+    is_exported: &dyn Fn(&[u8]) -> bool,
+) -> io::Result<Option<Vec<u8>>> {
+    let header = Mach::parse(data, 0).map_err(read_error)?;
+    let endian = header.endian().map_err(read_error)?;
+    let cputype = header.cputype(endian);
+    let mut commands = header.load_commands(endian, data, 0).map_err(read_error)?;
+    let mut command_offset = size_of::<Mach>() as u64;
+    let mut symtab = None;
+    let mut dysymtab = None;
+    let mut relocations = Vec::new();
+    while let Some(command) = commands.next().map_err(read_error)? {
+        if let Some(command) = command.symtab().map_err(read_error)? {
+            symtab = Some(command);
+        } else if let Some(command) = command.dysymtab().map_err(read_error)? {
+            dysymtab = Some((command_offset, command));
+        } else if let Some((segment, section_data)) =
+            Mach::Segment::from_command(command).map_err(read_error)?
+        {
+            for section in segment.sections(endian, section_data).map_err(read_error)? {
+                let section_relocations = section.relocations(endian, data).map_err(read_error)?;
+                let offset = u64::from(section.reloff(endian));
+                relocations.push((offset, section_relocations));
+            }
+        }
+        command_offset += u64::from(command.cmdsize());
+    }
+    let Some(symtab) = symtab else {
+        return Ok(None);
+    };
+    let symbols = symtab
+        .symbols::<Mach, _>(endian, data)
+        .map_err(read_error)?;
+
+    let mut locals = Vec::new();
+    let mut localized = Vec::new();
+    let mut defined = Vec::new();
+    let mut undefined = Vec::new();
+    for (index, symbol) in symbols.iter().enumerate() {
+        if symbol.is_stab() || symbol.n_type() & macho::N_EXT == 0 {
+            locals.push(index);
+            continue;
+        }
+        let name = symbol.name(endian, symbols.strings()).map_err(read_error)?;
+        if symbol.n_type() & macho::N_TYPE == macho::N_UNDF {
+            if symbol.n_value(endian).into() != 0 && !is_exported(name) {
+                return Err(common_symbol_error(name));
+            }
+            undefined.push(index);
+        } else if is_exported(name) {
+            defined.push(index);
+        } else {
+            localized.push(index);
+        }
+    }
+    if localized.is_empty() {
+        return Ok(None);
+    }
+    let local_count = locals.len() + localized.len();
+    let (order, new_indices) = get_symbol_order(&[&locals, &localized, &defined, &undefined]);
+
+    let mut out = data.to_vec();
+    let symbol_size = size_of::<Mach::Nlist>();
+    let symbol_table_offset = symtab.symoff.get(endian) as usize;
+    for (new_index, &index) in order.iter().enumerate() {
+        let symbol = &data[symbol_table_offset + index * symbol_size..][..symbol_size];
+        let new_offset = symbol_table_offset + new_index * symbol_size;
+        out[new_offset..new_offset + symbol_size].copy_from_slice(symbol);
+        if new_index >= locals.len() && new_index < local_count {
+            // `n_type` follows `n_strx` in both `Nlist32` and `Nlist64`.
+            out[new_offset + 4] &= !macho::N_EXT;
+        }
+    }
+
+    for (offset, section_relocations) in relocations {
+        for (i, relocation) in section_relocations.iter().enumerate() {
+            if relocation.r_scattered(endian, cputype) {
+                continue;
+            }
+            let mut info = relocation.info(endian);
+            if !info.r_extern {
+                continue;
+            }
+            info.r_symbolnum = get_new_index(&new_indices, info.r_symbolnum)?;
+            let offset = offset as usize + i * size_of::<macho::Relocation<Endianness>>();
+            let relocation = info.relocation(endian);
+            out[offset..offset + 8].copy_from_slice(bytes_of(&relocation));
+        }
+    }
+
+    if let Some((dysymtab_offset, dysymtab)) = dysymtab {
+        let indirect_symbols_offset = u64::from(dysymtab.indirectsymoff.get(endian));
+        for i in 0..u64::from(dysymtab.nindirectsyms.get(endian)) {
+            let offset = (indirect_symbols_offset + i * 4) as usize;
+            let index = data
+                .get(offset..offset + 4)
+                .ok_or_else(|| invalid_data("invalid Mach-O indirect symbol table"))?;
+            let index = endian.read_u32_bytes(index.try_into().unwrap());
+            if index & (macho::INDIRECT_SYMBOL_LOCAL | macho::INDIRECT_SYMBOL_ABS) == 0 {
+                let new_index = get_new_index(&new_indices, index)?;
+                write_u32(&mut out, offset as u64, endian, new_index);
+            }
+        }
+        // `ilocalsym`, `nlocalsym`, `iextdefsym`, `nextdefsym`, `iundefsym`
+        // and `nundefsym` follow `cmd` and `cmdsize`.
+        for (i, value) in [
+            0,
+            local_count,
+            local_count,
+            defined.len(),
+            local_count + defined.len(),
+            undefined.len(),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            write_u32(
+                &mut out,
+                dysymtab_offset + 8 + i as u64 * 4,
+                endian,
+                value as u32,
+            );
+        }
+    }
+    Ok(Some(out))
+}
+
+/// Makes the external definitions of a COFF object that aren't exported
+/// static.
+fn localize_coff_symbols<Coff: CoffHeader>(
+    data: &[u8],
+    is_exported: &dyn Fn(&[u8]) -> bool,
+) -> io::Result<Option<Vec<u8>>> {
+    let header = Coff::parse(data, &mut 0).map_err(read_error)?;
+    let symbols = header.symbols(data).map_err(read_error)?;
+    let symbols_offset = header.pointer_to_symbol_table() as usize;
+    let symbol_size = size_of::<Coff::ImageSymbol>();
+
+    let mut out = data.to_vec();
+    let mut localized = false;
+    for (index, symbol) in symbols.iter() {
+        if symbol.storage_class() != pe::IMAGE_SYM_CLASS_EXTERNAL {
+            continue;
+        }
+        let name = symbol.name(symbols.strings()).map_err(read_error)?;
+        if is_exported(name) || symbol.section_number() < pe::IMAGE_SYM_UNDEFINED {
+            continue;
+        }
+        if symbol.section_number() == pe::IMAGE_SYM_UNDEFINED {
+            if symbol.value() != 0 {
+                return Err(common_symbol_error(name));
+            }
+            continue;
+        }
+        // The storage class is followed by the number of auxiliary symbols at
+        // the end of the symbol.
+        out[symbols_offset + (index.0 + 1) * symbol_size - 2] = pe::IMAGE_SYM_CLASS_STATIC;
+        localized = true;
+    }
+    Ok(localized.then_some(out))
+}
+
+/// Checks that the symbols of a COFF short import file are exported, as the
+/// symbols of short import files can't be made local.
+fn check_short_import_symbols(data: &[u8], is_exported: &dyn Fn(&[u8]) -> bool) -> io::Result<()> {
+    get_short_import_symbol(data, &mut |name| {
+        if is_exported(name) {
+            Ok(())
+        } else {
+            Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "short import symbol {} can't be made local",
+                    String::from_utf8_lossy(name)
+                ),
+            ))
+        }
+    })?;
+    Ok(())
+}
+
+/// Makes the global symbols that are defined by `members` and aren't in
+/// `exported` local, so that an archive written with
+/// [crate::write_archive_to_stream] only has the exported symbols in its
+/// symbol table.
+///
+/// Like Apple's `-exported_symbols_list`, this is meant for objects that were
+/// merged with `ld -r`: references from other members to the localized
+/// symbols are no longer resolved. The names are the raw symbol names, as in
+/// the archive symbol table, so they include the leading underscore of Mach-O
+/// symbols. Common symbols and the symbols of COFF short import members can't
+/// be made local, so they must be exported; short import members are then kept
+/// unchanged. LLVM bitcode members aren't supported.
+pub fn localize_archive_symbols<'a>(
+    members: Vec<NewArchiveMember<'a>>,
+    exported: &[&[u8]],
+) -> io::Result<Vec<NewArchiveMember<'a>>> {
+    let exported = exported.iter().copied().collect::<HashSet<_>>();
+    let is_exported = |name: &[u8]| exported.contains(name);
+    let mut localized_members = Vec::with_capacity(members.len());
+    for mut member in members {
+        let data = (*member.buf).as_ref();
+        let localized = match FileKind::parse(data) {
+            Ok(FileKind::Elf32) => {
+                localize_elf_symbols::<elf::FileHeader32<Endianness>>(data, &is_exported)
+            }
+            Ok(FileKind::Elf64) => {
+                localize_elf_symbols::<elf::FileHeader64<Endianness>>(data, &is_exported)
+            }
+            Ok(FileKind::MachO32) => {
+                localize_macho_symbols::<macho::MachHeader32<Endianness>>(data, &is_exported)
+            }
+            Ok(FileKind::MachO64) => {
+                localize_macho_symbols::<macho::MachHeader64<Endianness>>(data, &is_exported)
+            }
+            Ok(FileKind::Coff) => localize_coff_symbols::<pe::ImageFileHeader>(data, &is_exported),
+            Ok(FileKind::CoffBig) => {
+                localize_coff_symbols::<pe::AnonObjectHeaderBigobj>(data, &is_exported)
+            }
+            Ok(FileKind::CoffImport) => {
+                check_short_import_symbols(data, &is_exported).map(|()| None)
+            }
+            Err(_) if is_bitcode(data) => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    format!(
+                        "{}: localizing symbols is not supported for LLVM bitcode files",
+                        member.member_name
+                    ),
+                ));
+            }
+            Err(_) => Ok(None),
+            Ok(kind) => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    format!(
+                        "{}: localizing symbols is not supported for {kind:?} files",
+                        member.member_name
+                    ),
+                ));
+            }
+        }
+        .map_err(|err| Error::new(err.kind(), format!("{}: {err}", member.member_name)))?;
+        if let Some(localized) = localized {
+            member.buf = Box::new(localized);
+        }
+        localized_members.push(member);
+    }
+    Ok(localized_members)
+}
@@ -0,0 +1,335 @@
+//! Renaming of the global symbols of archive members, like
+//! `objcopy --prefix-symbols` and `objcopy --redefine-syms` applied to every
+//! member.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Error, ErrorKind};
+use std::mem::size_of;
+
+use object::read::coff::{CoffHeader, ImageSymbol as _};
+use object::read::elf::{FileHeader, SectionHeader as _, Sym as _};
+use object::read::macho::{MachHeader, Nlist as _};
+use object::{elf, macho, pe, Endianness, FileKind};
+
+use crate::alignment::align_to;
+use crate::macho_universal_writer::{get_member_symbolic_data, is_bitcode, FatMembers};
+use crate::object_editing::{invalid_data, read_error, write_u32, write_u64, StringTableBuilder};
+use crate::NewArchiveMember;
+
+/// Renames the global symbols of an ELF object, by appending a new string
+/// table to the end of the file.
+fn rename_elf_symbols<Elf: FileHeader<Endian = Endianness>>(
+    data: &[u8],
+    rename: &dyn Fn(&[u8]) -> Option<Vec<u8>>,
+) -> io::Result<Option<Vec<u8>>> {
+    let header = Elf::parse(data).map_err(read_error)?;
+    let endian = header.endian().map_err(read_error)?;
+    let sections = header.sections(endian, data).map_err(read_error)?;
+    let symbols = sections
+        .symbols(endian, data, elf::SHT_SYMTAB)
+        .map_err(read_error)?;
+    if symbols.is_empty() {
+        return Ok(None);
+    }
+    let symbol_table_offset: u64 = sections
+        .section(symbols.section())
+        .map_err(read_error)?
+        .sh_offset(endian)
+        .into();
+    let string_section = sections
+        .section(symbols.string_section())
+        .map_err(read_error)?;
+    let mut strings =
+        StringTableBuilder::new(string_section.data(endian, data).map_err(read_error)?);
+
+    let mut out = data.to_vec();
+    let mut renamed = false;
+    for (index, symbol) in symbols.iter().enumerate() {
+        if symbol.st_bind() == elf::STB_LOCAL
+            || matches!(symbol.st_type(), elf::STT_FILE | elf::STT_SECTION)
+        {
+            continue;
+        }
+        let name = symbol.name(endian, symbols.strings()).map_err(read_error)?;
+        if let Some(new_name) = rename(name) {
+            // `st_name` is the first field of both `Sym32` and `Sym64`.
+            let offset = symbol_table_offset + (index * size_of::<Elf::Sym>()) as u64;
+            write_u32(&mut out, offset, endian, strings.add(&new_name)?);
+            renamed = true;
+        }
+    }
+    if !renamed {
+        return Ok(None);
+    }
+
+    let string_table_offset = out.len() as u64;
+    let string_table_size = strings.data.len() as u64;
+    out.extend_from_slice(&strings.data);
+    let section_header_offset = header.e_shoff(endian).into()
+        + symbols.string_section().0 as u64 * u64::from(header.e_shentsize(endian));
+    if header.is_class_64() {
+        write_u64(
+            &mut out,
+            section_header_offset + 24,
+            endian,
+            string_table_offset,
+        );
+        write_u64(
+            &mut out,
+            section_header_offset + 32,
+            endian,
+            string_table_size,
+        );
+    } else {
+        let too_large = || invalid_data("ELF object is too large");
+        write_u32(
+            &mut out,
+            section_header_offset + 16,
+            endian,
+            u32::try_from(string_table_offset).map_err(|_| too_large())?,
+        );
+        write_u32(
+            &mut out,
+            section_header_offset + 20,
+            endian,
+            u32::try_from(string_table_size).map_err(|_| too_large())?,
+        );
+    }
+    Ok(Some(out))
+}
+
+/// Renames the external symbols of a Mach-O object, by appending a new string
+/// table to the end of the file.
+fn rename_macho_symbols<Mach: MachHeader<Endian = Endianness>>(
+    data: &[u8],
+    rename: &dyn Fn(&[u8]) -> Option<Vec<u8>>,
+) -> io::Result<Option<Vec<u8>>> {
+    let header = Mach::parse(data, 0).map_err(read_error)?;
+    let endian = header.endian().map_err(read_error)?;
+    let mut commands = header.load_commands(endian, data, 0).map_err(read_error)?;
+    let mut command_offset = size_of::<Mach>() as u64;
+    let (symtab_offset, symtab) = loop {
+        let Some(command) = commands.next().map_err(read_error)? else {
+            return Ok(None);
+        };
+        if let Some(symtab) = command.symtab().map_err(read_error)? {
+            break (command_offset, symtab);
+        }
+        command_offset += u64::from(command.cmdsize());
+    };
+    let symbols = symtab
+        .symbols::<Mach, _>(endian, data)
+        .map_err(read_error)?;
+    let symbols_offset = u64::from(symtab.symoff.get(endian));
+    let string_table_offset = symtab.stroff.get(endian) as usize;
+    let string_table = data
+        .get(string_table_offset..)
+        .and_then(|strings| strings.get(..symtab.strsize.get(endian) as usize))
+        .ok_or_else(|| invalid_data("invalid Mach-O string table"))?;
+    let mut strings = StringTableBuilder::new(string_table);
+
+    let mut out = data.to_vec();
+    let mut renamed = false;
+    for (index, symbol) in symbols.iter().enumerate() {
+        if symbol.is_stab() || symbol.n_type() & macho::N_EXT == 0 {
+            continue;
+        }
+        let name = symbol.name(endian, symbols.strings()).map_err(read_error)?;
+        if let Some(new_name) = rename(name) {
+            // `n_strx` is the first field of both `Nlist32` and `Nlist64`.
+            let offset = symbols_offset + (index * size_of::<Mach::Nlist>()) as u64;
+            write_u32(&mut out, offset, endian, strings.add(&new_name)?);
+            renamed = true;
+        }
+    }
+    if !renamed {
+        return Ok(None);
+    }
+
+    let alignment = if header.is_type_64() { 8 } else { 4 };
+    out.resize(align_to(out.len() as u64, alignment) as usize, 0);
+    let too_large = || invalid_data("Mach-O object is too large");
+    let string_table_offset = u32::try_from(out.len()).map_err(|_| too_large())?;
+    let string_table_size = u32::try_from(strings.data.len()).map_err(|_| too_large())?;
+    out.extend_from_slice(&strings.data);
+    // `stroff` and `strsize` follow `cmd`, `cmdsize`, `symoff` and `nsyms`.
+    write_u32(&mut out, symtab_offset + 16, endian, string_table_offset);
+    write_u32(&mut out, symtab_offset + 20, endian, string_table_size);
+    Ok(Some(out))
+}
+
+/// Renames the external symbols of a COFF object, by extending the string
+/// table at the end of the file.
+fn rename_coff_symbols<Coff: CoffHeader>(
+    data: &[u8],
+    rename: &dyn Fn(&[u8]) -> Option<Vec<u8>>,
+) -> io::Result<Option<Vec<u8>>> {
+    let header = Coff::parse(data, &mut 0).map_err(read_error)?;
+    let symbols = header.symbols(data).map_err(read_error)?;
+    if symbols.is_empty() {
+        return Ok(None);
+    }
+    let symbols_offset = u64::from(header.pointer_to_symbol_table());
+    let string_table_offset =
+        symbols_offset + (symbols.len() * size_of::<Coff::ImageSymbol>()) as u64;
+    let string_table = &data[string_table_offset as usize..];
+    let string_table_size = u32::from_le_bytes(string_table[..4].try_into().unwrap()) as usize;
+    if string_table_size != string_table.len() {
+        return Err(invalid_data(
+            "COFF string table isn't at the end of the file",
+        ));
+    }
+    let mut strings = StringTableBuilder::new(string_table);
+
+    let mut out = data.to_vec();
+    let mut renamed = false;
+    for (index, symbol) in symbols.iter() {
+        if !matches!(
+            symbol.storage_class(),
+            pe::IMAGE_SYM_CLASS_EXTERNAL | pe::IMAGE_SYM_CLASS_WEAK_EXTERNAL
+        ) || symbol.section_number() == pe::IMAGE_SYM_DEBUG
+        {
+            continue;
+        }
+        let Some(new_name) = rename(symbol.name(symbols.strings()).map_err(read_error)?) else {
+            continue;
+        };
+        // Short names are stored in the symbol, and long names are stored in
+        // the string table and referenced by a zero followed by their offset.
+        let mut raw_name = [0; 8];
+        if new_name.len() <= raw_name.len() {
+            raw_name[..new_name.len()].copy_from_slice(&new_name);
+        } else {
+            raw_name[4..].copy_from_slice(&strings.add(&new_name)?.to_le_bytes());
+        }
+        let offset = symbols_offset as usize + index.0 * size_of::<Coff::ImageSymbol>();
+        out[offset..offset + raw_name.len()].copy_from_slice(&raw_name);
+        renamed = true;
+    }
+    if !renamed {
+        return Ok(None);
+    }
+
+    let string_table_size = u32::try_from(strings.data.len())
+        .map_err(|_| invalid_data("COFF string table is too large"))?;
+    strings.data[..4].copy_from_slice(&string_table_size.to_le_bytes());
+    out.truncate(string_table_offset as usize);
+    out.extend_from_slice(&strings.data);
+    Ok(Some(out))
+}
+
+/// Renames the global symbols of each member with `rename`, which returns the
+/// new name of a symbol or `None` to keep its name.
+///
+/// Members that aren't object files are kept unchanged, and so are short
+/// import members, as the name of their symbol is also the name that is
+/// imported from the DLL. LLVM bitcode members are rejected, as their symbols
+/// can't be renamed.
+fn rename_symbols<'a>(
+    members: Vec<NewArchiveMember<'a>>,
+    rename: &dyn Fn(&[u8]) -> Option<Vec<u8>>,
+) -> io::Result<Vec<NewArchiveMember<'a>>> {
+    let mut renamed_members = Vec::with_capacity(members.len());
+    for mut member in members {
+        let data = (*member.buf).as_ref();
+        let renamed = match FileKind::parse(data) {
+            Ok(FileKind::Elf32) => {
+                rename_elf_symbols::<elf::FileHeader32<Endianness>>(data, rename)
+            }
+            Ok(FileKind::Elf64) => {
+                rename_elf_symbols::<elf::FileHeader64<Endianness>>(data, rename)
+            }
+            Ok(FileKind::MachO32) => {
+                rename_macho_symbols::<macho::MachHeader32<Endianness>>(data, rename)
+            }
+            Ok(FileKind::MachO64) => {
+                rename_macho_symbols::<macho::MachHeader64<Endianness>>(data, rename)
+            }
+            Ok(FileKind::Coff) => rename_coff_symbols::<pe::ImageFileHeader>(data, rename),
+            Ok(FileKind::CoffBig) => {
+                rename_coff_symbols::<pe::AnonObjectHeaderBigobj>(data, rename)
+            }
+            Ok(FileKind::CoffImport) => Ok(None),
+            Err(_) if is_bitcode(data) => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    format!(
+                        "{}: renaming symbols is not supported for LLVM bitcode files",
+                        member.member_name
+                    ),
+                ));
+            }
+            Err(_) => Ok(None),
+            Ok(kind) => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    format!(
+                        "{}: renaming symbols is not supported for {kind:?} files",
+                        member.member_name
+                    ),
+                ));
+            }
+        }
+        .map_err(|err| Error::new(err.kind(), format!("{}: {err}", member.member_name)))?;
+        if let Some(renamed) = renamed {
+            member.buf = Box::new(renamed);
+        }
+        renamed_members.push(member);
+    }
+    Ok(renamed_members)
+}
+
+/// Renames the global symbols in `renames`, like `objcopy --redefine-syms`
+/// applied to every member, so that both the definitions and the references
+/// are renamed.
+///
+/// Short import members are kept unchanged, so renaming a symbol that they
+/// define only renames the references to it. Members that are LLVM bitcode are
+/// rejected.
+///
+/// The names are the raw symbol names, as in the archive symbol table, so
+/// they include the leading underscore of Mach-O symbols. The returned members
+/// can be written with [crate::write_archive_to_stream], which regenerates the
+/// archive symbol table with the new names.
+pub fn redefine_archive_symbols<'a>(
+    members: Vec<NewArchiveMember<'a>>,
+    renames: &HashMap<Vec<u8>, Vec<u8>>,
+) -> io::Result<Vec<NewArchiveMember<'a>>> {
+    rename_symbols(members, &|name| renames.get(name).cloned())
+}
+
+/// Prefixes the symbols that are defined by the members with `prefix`, and
+/// the references to them, so that two versions of a library can be linked
+/// into one binary.
+///
+/// Unlike `objcopy --prefix-symbols`, references to symbols that no member
+/// defines, such as those of the C library, are kept unchanged. So are the
+/// symbols of short import members, which are the names that DLLs export, and
+/// the references to them. Members that are LLVM bitcode are rejected.
+///
+/// The returned members can be written with [crate::write_archive_to_stream],
+/// which regenerates the archive symbol table with the new names.
+pub fn prefix_archive_symbols<'a>(
+    members: Vec<NewArchiveMember<'a>>,
+    prefix: &[u8],
+) -> io::Result<Vec<NewArchiveMember<'a>>> {
+    let mut defined = HashSet::new();
+    for member in &members {
+        let object_reader = member.object_reader;
+        let buf = get_member_symbolic_data(
+            (*member.buf).as_ref(),
+            FatMembers::Ignore,
+            &member.member_name,
+        )?;
+        if FileKind::parse(buf) == Ok(FileKind::CoffImport) {
+            continue;
+        }
+        (object_reader.get_symbols)(buf, &mut |name| {
+            defined.insert(name.to_vec());
+            Ok(())
+        })?;
+    }
+    rename_symbols(members, &|name| {
+        defined.contains(name).then(|| [prefix, name].concat())
+    })
+}
@@ -0,0 +1,141 @@
+// Derived from code in LLVM, which is:
+// Part of the LLVM Project, under the Apache License v2.0 with LLVM Exceptions.
+// See https://llvm.org/LICENSE.txt for license information.
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+//! Helpers for creating the members of thin archives, whose member names are
+//! paths relative to the archive.
+
+use std::fs;
+use std::io::{self, Error, ErrorKind};
+use std::path::{Component, Path, PathBuf};
+
+use object::read::archive::ArchiveFile;
+
+use crate::{NewArchiveMember, ObjectReader};
+
+/// Makes `path` absolute and removes `.` and `..` components, without
+/// accessing the file system.
+pub(crate) fn get_dotless_absolute_path(path: &Path) -> io::Result<PathBuf> {
+    let mut ret = PathBuf::new();
+    for component in std::path::absolute(path)?.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                ret.pop();
+            }
+            _ => ret.push(component),
+        }
+    }
+    Ok(ret)
+}
+
+// Derived from computeArchiveRelativePath.
+/// Returns the name of the member at `member_path` in a thin archive written
+/// to `archive_path`, which is the path of the member relative to the
+/// directory of the archive, using `/` as the separator.
+pub fn compute_archive_relative_path(
+    archive_path: &Path,
+    member_path: &Path,
+) -> io::Result<String> {
+    let abs_to = get_dotless_absolute_path(member_path)?;
+    let abs_from = get_dotless_absolute_path(archive_path)?;
+    let abs_from = abs_from.parent().unwrap_or(&abs_from);
+
+    let mut from_components = abs_from.components().peekable();
+    let mut to_components = abs_to.components().peekable();
+
+    // There is no relative path between different Windows drives.
+    if matches!(to_components.peek(), Some(Component::Prefix(_)))
+        && from_components.peek() != to_components.peek()
+    {
+        return Ok(abs_to.to_string_lossy().replace('\\', "/"));
+    }
+
+    // Skip common prefixes.
+    while from_components.peek().is_some() && from_components.peek() == to_components.peek() {
+        from_components.next();
+        to_components.next();
+    }
+
+    // Construct the relative path.
+    let relative = from_components
+        .map(|_| "..".into())
+        .chain(to_components.map(|component| component.as_os_str().to_string_lossy()))
+        .collect::<Vec<_>>();
+    Ok(relative.join("/"))
+}
+
+fn read_thin_archive_members_into(
+    archive_path: &Path,
+    member_path: &Path,
+    object_reader: &'static ObjectReader,
+    parents: &mut Vec<PathBuf>,
+    members: &mut Vec<NewArchiveMember<'static>>,
+) -> io::Result<()> {
+    let buf = fs::read(member_path).map_err(|err| {
+        Error::new(
+            err.kind(),
+            format!("{}: {err}", member_path.to_string_lossy()),
+        )
+    })?;
+
+    let thin_archive = ArchiveFile::parse(buf.as_slice())
+        .ok()
+        .filter(|archive| archive.is_thin());
+    let Some(thin_archive) = thin_archive else {
+        members.push(NewArchiveMember::new(
+            buf,
+            object_reader,
+            compute_archive_relative_path(archive_path, member_path)?,
+        ));
+        return Ok(());
+    };
+
+    // Add the members of nested thin archives instead of the archive, as a
+    // thin archive can't contain another thin archive.
+    let member_path = get_dotless_absolute_path(member_path)?;
+    if parents.contains(&member_path) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "thin archive {} contains itself",
+                member_path.to_string_lossy()
+            ),
+        ));
+    }
+    let nested_dir = member_path.parent().unwrap_or(&member_path).to_path_buf();
+    parents.push(member_path);
+    for child in thin_archive.members() {
+        let child = child.map_err(|err| Error::new(ErrorKind::InvalidData, err.to_string()))?;
+        let child_name = std::str::from_utf8(child.name())
+            .map_err(|err| Error::new(ErrorKind::InvalidData, err.to_string()))?;
+        let child_path = nested_dir.join(child_name);
+        read_thin_archive_members_into(archive_path, &child_path, object_reader, parents, members)?;
+    }
+    parents.pop();
+    Ok(())
+}
+
+/// Reads the files at `member_paths` into members of a thin archive that is
+/// written to `archive_path`, as `llvm-ar --thin` does.
+///
+/// The members are named by their path relative to the archive. Thin archives
+/// in `member_paths` are replaced by their members.
+pub fn read_thin_archive_members<P: AsRef<Path>>(
+    archive_path: &Path,
+    member_paths: &[P],
+    object_reader: &'static ObjectReader,
+) -> io::Result<Vec<NewArchiveMember<'static>>> {
+    let mut members = Vec::new();
+    for member_path in member_paths {
+        read_thin_archive_members_into(
+            archive_path,
+            member_path.as_ref(),
+            object_reader,
+            &mut Vec::new(),
+            &mut members,
+        )?;
+    }
+    Ok(members)
+}
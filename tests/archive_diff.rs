@@ -0,0 +1,146 @@
+use std::io::Cursor;
+
+use ar_archive_writer::{ArchiveDifference, ArchiveKind, MemberField, NewArchiveMember};
+use object::{Architecture, BinaryFormat};
+use pretty_assertions::assert_eq;
+
+mod common;
+
+fn create_object(binary_format: BinaryFormat, func_name: &[u8]) -> Vec<u8> {
+    let architecture = match binary_format {
+        BinaryFormat::Xcoff => Architecture::PowerPc64,
+        _ => Architecture::X86_64,
+    };
+    common::create_object(binary_format, architecture, &[func_name])
+}
+
+fn write_archive(objects: &[(&str, u64, Vec<u8>)], kind: ArchiveKind) -> Vec<u8> {
+    let members = objects
+        .iter()
+        .map(|(name, mtime, bytes)| {
+            let mut member = NewArchiveMember::new(
+                bytes.as_slice(),
+                &ar_archive_writer::DEFAULT_OBJECT_READER,
+                name.to_string(),
+            );
+            member.mtime = *mtime;
+            member
+        })
+        .collect::<Vec<_>>();
+    let mut output_bytes = Cursor::new(Vec::new());
+    ar_archive_writer::write_archive_to_stream(&mut output_bytes, &members, kind, false, false)
+        .unwrap();
+    output_bytes.into_inner()
+}
+
+/// Tests that identical archives have no differences.
+#[test]
+fn diff_identical_archives() {
+    for (kind, binary_format) in [
+        (ArchiveKind::Gnu, BinaryFormat::Elf),
+        (ArchiveKind::Coff, BinaryFormat::Coff),
+        (ArchiveKind::Darwin, BinaryFormat::MachO),
+        (ArchiveKind::AixBig, BinaryFormat::Xcoff),
+    ] {
+        let objects = [
+            ("file1.o", 0, create_object(binary_format, b"func1")),
+            (
+                "a_very_long_file_name.o",
+                0,
+                create_object(binary_format, b"func2"),
+            ),
+        ];
+        let archive = write_archive(&objects, kind);
+        assert_eq!(
+            ar_archive_writer::diff_archives(&archive, &archive).unwrap(),
+            [],
+            "kind: {kind:?}"
+        );
+    }
+}
+
+/// Tests that added, removed and moved members, header fields, data, symbols
+/// and the string table are compared.
+#[test]
+fn diff_archives() {
+    let object1 = create_object(BinaryFormat::Elf, b"func1");
+    let object2 = create_object(BinaryFormat::Elf, b"func2");
+    let object3 = create_object(BinaryFormat::Elf, b"func3");
+    let object4 = create_object(BinaryFormat::Elf, b"func4");
+    let left = write_archive(
+        &[
+            ("a.o", 0, object1.clone()),
+            ("b.o", 0, object2.clone()),
+            ("c.o", 0, object3.clone()),
+        ],
+        ArchiveKind::Gnu,
+    );
+    let right = write_archive(
+        &[
+            ("c.o", 0, object3),
+            ("a.o", 1234, object1),
+            ("a_very_long_file_name.o", 0, object4),
+            ("b.o", 0, object2[..object2.len() - 1].to_vec()),
+        ],
+        ArchiveKind::Gnu,
+    );
+
+    assert_eq!(
+        ar_archive_writer::diff_archives(&left, &right).unwrap(),
+        [
+            ArchiveDifference::SpecialMemberAdded {
+                kind: ar_archive_writer::SpecialMemberKind::StringTable,
+            },
+            ArchiveDifference::MemberField {
+                name: "a.o".to_string(),
+                field: MemberField::Mtime,
+                left: 0,
+                right: 1234,
+            },
+            ArchiveDifference::MemberField {
+                name: "b.o".to_string(),
+                field: MemberField::Size,
+                left: object2.len() as u64,
+                right: object2.len() as u64 - 1,
+            },
+            ArchiveDifference::MemberField {
+                name: "b.o".to_string(),
+                field: MemberField::Padding,
+                left: 0,
+                right: 1,
+            },
+            ArchiveDifference::MemberData {
+                name: "b.o".to_string(),
+            },
+            ArchiveDifference::MemberAdded {
+                name: "a_very_long_file_name.o".to_string(),
+                right_index: 2,
+            },
+            ArchiveDifference::MemberMoved {
+                name: "c.o".to_string(),
+                left_index: 2,
+                right_index: 0,
+            },
+            // The truncated object can't be read, so its symbols are missing.
+            ArchiveDifference::SymbolRemoved {
+                symbol: b"func2".to_vec(),
+                member_name: "b.o".to_string(),
+            },
+            ArchiveDifference::SymbolAdded {
+                symbol: b"func4".to_vec(),
+                member_name: "a_very_long_file_name.o".to_string(),
+            },
+            ArchiveDifference::StringTable {
+                left: vec![],
+                right: vec!["a_very_long_file_name.o/".to_string()],
+            },
+        ]
+    );
+    assert_eq!(
+        ar_archive_writer::diff_archives(&right, &left).unwrap()[1].to_string(),
+        "member a.o mtime differs: 1234 != 0"
+    );
+
+    let err = ar_archive_writer::diff_archives(&left, b"not an archive").unwrap_err();
+    assert_eq!(err.to_string(), "not an archive");
+}
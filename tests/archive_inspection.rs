@@ -0,0 +1,188 @@
+use std::io::Cursor;
+
+use ar_archive_writer::{ArchiveKind, ArchiveLayout, NewArchiveMember, SpecialMemberKind};
+use object::{Architecture, BinaryFormat};
+use pretty_assertions::assert_eq;
+
+mod common;
+
+fn create_object(binary_format: BinaryFormat, func_name: &[u8]) -> Vec<u8> {
+    let architecture = match binary_format {
+        BinaryFormat::Xcoff => Architecture::PowerPc64,
+        _ => Architecture::X86_64,
+    };
+    common::create_object(binary_format, architecture, &[func_name])
+}
+
+fn write_archive(objects: &[(&str, Vec<u8>)], kind: ArchiveKind, thin: bool) -> Vec<u8> {
+    let members = objects
+        .iter()
+        .map(|(name, bytes)| {
+            let mut member = NewArchiveMember::new(
+                bytes.as_slice(),
+                &ar_archive_writer::DEFAULT_OBJECT_READER,
+                name.to_string(),
+            );
+            member.mtime = 1234;
+            member.uid = 5;
+            member.gid = 6;
+            member.perms = 0o600;
+            member
+        })
+        .collect::<Vec<_>>();
+    let mut output_bytes = Cursor::new(Vec::new());
+    ar_archive_writer::write_archive_to_stream(&mut output_bytes, &members, kind, thin, false)
+        .unwrap();
+    output_bytes.into_inner()
+}
+
+fn get_special_member_kinds(layout: &ArchiveLayout) -> Vec<SpecialMemberKind> {
+    layout
+        .special_members
+        .iter()
+        .map(|member| member.kind)
+        .collect()
+}
+
+/// Tests that the special members of each archive kind are found, and that the
+/// members are found at their offsets with their metadata.
+#[test]
+fn inspect_archive() {
+    for (kind, binary_format, thin, magic, expected_special_members) in [
+        (
+            ArchiveKind::Gnu,
+            BinaryFormat::Elf,
+            false,
+            "!<arch>\n",
+            &[
+                SpecialMemberKind::SymbolTable,
+                SpecialMemberKind::StringTable,
+            ][..],
+        ),
+        (
+            ArchiveKind::Gnu,
+            BinaryFormat::Elf,
+            true,
+            "!<thin>\n",
+            &[
+                SpecialMemberKind::SymbolTable,
+                SpecialMemberKind::StringTable,
+            ],
+        ),
+        (
+            ArchiveKind::Gnu64,
+            BinaryFormat::Elf,
+            false,
+            "!<arch>\n",
+            &[
+                SpecialMemberKind::SymbolTable64,
+                SpecialMemberKind::StringTable,
+            ],
+        ),
+        (
+            ArchiveKind::Coff,
+            BinaryFormat::Coff,
+            false,
+            "!<arch>\n",
+            &[
+                SpecialMemberKind::SymbolTable,
+                SpecialMemberKind::CoffLinkerMember,
+                SpecialMemberKind::StringTable,
+            ],
+        ),
+        (
+            ArchiveKind::Darwin,
+            BinaryFormat::MachO,
+            false,
+            "!<arch>\n",
+            &[SpecialMemberKind::BsdSymbolTable],
+        ),
+        (
+            ArchiveKind::Darwin64,
+            BinaryFormat::MachO,
+            false,
+            "!<arch>\n",
+            &[SpecialMemberKind::BsdSymbolTable64],
+        ),
+        (
+            ArchiveKind::AixBig,
+            BinaryFormat::Xcoff,
+            false,
+            "<bigaf>\n",
+            &[
+                SpecialMemberKind::AixMemberTable,
+                SpecialMemberKind::AixGlobalSymbolTable64,
+            ],
+        ),
+    ] {
+        let objects = [
+            ("file1.o", create_object(binary_format, b"func1")),
+            (
+                "a_very_long_file_name.o",
+                create_object(binary_format, b"func2"),
+            ),
+        ];
+        let archive = write_archive(&objects, kind, thin);
+        let layout = ar_archive_writer::inspect_archive(&archive).unwrap();
+        let message = format!("kind: {kind:?}, thin: {thin}");
+        assert_eq!(layout.magic, magic, "{message}");
+        assert_eq!(
+            get_special_member_kinds(&layout),
+            expected_special_members,
+            "{message}"
+        );
+        for special_member in &layout.special_members {
+            assert!(
+                special_member.data_offset + special_member.size <= archive.len() as u64,
+                "{message}"
+            );
+        }
+
+        assert_eq!(layout.members.len(), objects.len(), "{message}");
+        let is_darwin = matches!(kind, ArchiveKind::Darwin | ArchiveKind::Darwin64);
+        for (member, (name, object)) in layout.members.iter().zip(&objects) {
+            assert_eq!(member.name, *name, "{message}");
+            // Darwin uses unique timestamps for members with the same name.
+            let mtime = if is_darwin { 0 } else { 1234 };
+            assert_eq!(
+                (member.mtime, member.uid, member.gid, member.perms),
+                (mtime, 5, 6, 0o600),
+                "{message}"
+            );
+            // Darwin pads the members to 8 bytes.
+            if is_darwin {
+                assert_eq!(member.size, object.len().next_multiple_of(8) as u64);
+            } else {
+                assert_eq!(member.size, object.len() as u64, "{message}");
+            }
+            match member.data_offset {
+                Some(offset) => {
+                    let data = &archive[offset as usize..][..object.len()];
+                    assert_eq!(data, object, "{message}");
+                }
+                None => assert!(thin, "{message}"),
+            }
+        }
+    }
+
+    let err = ar_archive_writer::inspect_archive(b"not an archive").unwrap_err();
+    assert_eq!(err.to_string(), "not an archive");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn inspect_archive_json() {
+    let objects = [("file1.o", create_object(BinaryFormat::Elf, b"func1"))];
+    let archive = write_archive(&objects, ArchiveKind::Gnu, false);
+    let layout = ar_archive_writer::inspect_archive(&archive).unwrap();
+    let json = serde_json::to_value(&layout).unwrap();
+    assert_eq!(json["magic"], "!<arch>\n");
+    assert_eq!(json["special_members"][0]["kind"], "SymbolTable");
+    assert_eq!(json["special_members"][0]["header_offset"], 8);
+    assert_eq!(json["members"][0]["name"], "file1.o");
+    assert_eq!(json["members"][0]["perms"], 0o600);
+    assert_eq!(
+        json["members"][0]["data_offset"],
+        layout.members[0].data_offset.unwrap()
+    );
+}
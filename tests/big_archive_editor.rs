@@ -0,0 +1,220 @@
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use std::process::Command;
+
+use ar_archive_writer::ArchiveKind;
+use object::read::archive::ArchiveFile;
+use object::{Architecture, BinaryFormat};
+use pretty_assertions::assert_eq;
+
+mod common;
+
+fn create_big_archive(objects: &[(&str, &[u8])]) -> Vec<u8> {
+    common::write_archive(&common::create_members(objects), ArchiveKind::AixBig)
+}
+
+fn get_members(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+    ArchiveFile::parse(archive)
+        .unwrap()
+        .members()
+        .map(|member| {
+            let member = member.unwrap();
+            (
+                String::from_utf8(member.name().to_vec()).unwrap(),
+                member.data(archive).unwrap().to_vec(),
+            )
+        })
+        .collect()
+}
+
+/// Lists the members and the symbol tables of an archive with LLVM, which
+/// follows the linked list of members.
+fn list_with_llvm(tmpdir: &Path, archive: &[u8]) -> (String, String) {
+    let archive_path = tmpdir.join("archive.a");
+    fs::write(&archive_path, archive).unwrap();
+    let run = |path: std::path::PathBuf, args: &[&str]| {
+        let output = Command::new(path)
+            .args(args)
+            .arg(&archive_path)
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    (
+        run(cargo_binutils::Tool::Ar.path().unwrap(), &["t"]),
+        run(cargo_binutils::Tool::Nm.path().unwrap(), &["--print-armap"]),
+    )
+}
+
+fn get_member_ranges(archive: &[u8]) -> Vec<(u64, u64)> {
+    ArchiveFile::parse(archive)
+        .unwrap()
+        .members()
+        .map(|member| member.unwrap().file_range())
+        .collect()
+}
+
+fn parse_offset(field: &[u8]) -> u64 {
+    std::str::from_utf8(field)
+        .unwrap()
+        .trim_end()
+        .parse()
+        .unwrap()
+}
+
+/// Returns the offsets of the member table and the global symbol tables.
+fn get_table_offsets(archive: &[u8]) -> [u64; 3] {
+    [
+        parse_offset(&archive[8..28]),
+        parse_offset(&archive[28..48]),
+        parse_offset(&archive[48..68]),
+    ]
+}
+
+/// Returns the offsets of the members in the member table.
+fn get_member_offsets(archive: &[u8]) -> Vec<u64> {
+    let member_table = usize::try_from(get_table_offsets(archive)[0]).unwrap() + 114;
+    let num_members = parse_offset(&archive[member_table..member_table + 20]);
+    (1..=usize::try_from(num_members).unwrap())
+        .map(|i| parse_offset(&archive[member_table + i * 20..member_table + (i + 1) * 20]))
+        .collect()
+}
+
+/// Returns the offsets on the free list, which is linked through the next
+/// member offsets of the freed blocks.
+fn get_free_list(archive: &[u8]) -> Vec<u64> {
+    let mut free_list = Vec::new();
+    let mut offset = parse_offset(&archive[108..128]);
+    while offset != 0 {
+        free_list.push(offset);
+        let next_offset = usize::try_from(offset).unwrap() + 20;
+        offset = parse_offset(&archive[next_offset..next_offset + 20]);
+    }
+    free_list
+}
+
+/// Tests that appending and removing members produces the same members and
+/// symbol tables as writing the archive from scratch.
+#[test]
+fn append_and_remove() {
+    let tmpdir = common::create_tmp_dir("big_archive_append_and_remove");
+    let object1 = common::create_object(BinaryFormat::Xcoff, Architecture::PowerPc64, &[b"func1"]);
+    let object2 = common::create_object(BinaryFormat::Xcoff, Architecture::PowerPc, &[b"func2"]);
+    let object3 = common::create_object(BinaryFormat::Xcoff, Architecture::PowerPc64, &[b"func3"]);
+    let object4 = common::create_object(BinaryFormat::Xcoff, Architecture::PowerPc, &[b"func4"]);
+    let objects = [
+        ("file1.o", object1.as_slice()),
+        ("file2.o", object2.as_slice()),
+        ("file3.o", object3.as_slice()),
+        ("file4.o", object4.as_slice()),
+    ];
+
+    let original = create_big_archive(&objects[..2]);
+    let mut archive = Cursor::new(original.clone());
+    ar_archive_writer::append_to_big_archive(&mut archive, &common::create_members(&objects[2..]))
+        .unwrap();
+    let archive = archive.into_inner();
+    let expected = create_big_archive(&objects);
+    assert_eq!(get_members(&archive), get_members(&expected));
+    assert_eq!(
+        list_with_llvm(&tmpdir, &archive),
+        list_with_llvm(&tmpdir, &expected)
+    );
+    // The existing members weren't moved, and the replaced member table is on
+    // the free list.
+    assert_eq!(
+        get_member_ranges(&archive)[..2],
+        get_member_ranges(&original)
+    );
+    let [member_table_offset, glob_sym_offset, glob_sym64_offset] = get_table_offsets(&original);
+    assert_eq!(
+        get_free_list(&archive),
+        [glob_sym64_offset, glob_sym_offset, member_table_offset]
+    );
+
+    let appended = archive;
+    let mut archive = Cursor::new(appended.clone());
+    ar_archive_writer::remove_from_big_archive(&mut archive, &["file3.o", "file1.o"]).unwrap();
+    let archive = archive.into_inner();
+    let expected = create_big_archive(&[objects[1], objects[3]]);
+    assert_eq!(get_members(&archive), get_members(&expected));
+    assert_eq!(
+        list_with_llvm(&tmpdir, &archive),
+        list_with_llvm(&tmpdir, &expected)
+    );
+    // The removed members and the replaced tables are added to the front of
+    // the free list.
+    let member_offsets = get_member_offsets(&appended);
+    let [member_table_offset, glob_sym_offset, glob_sym64_offset] = get_table_offsets(&appended);
+    assert_eq!(
+        get_free_list(&archive),
+        [
+            glob_sym64_offset,
+            glob_sym_offset,
+            member_table_offset,
+            member_offsets[2],
+            member_offsets[0],
+        ]
+        .into_iter()
+        .chain(get_free_list(&appended))
+        .collect::<Vec<_>>()
+    );
+
+    // Appending after removing works with the updated tables.
+    let mut archive = Cursor::new(archive);
+    ar_archive_writer::append_to_big_archive(&mut archive, &common::create_members(&objects[..1]))
+        .unwrap();
+    let archive = archive.into_inner();
+    let expected = create_big_archive(&[objects[1], objects[3], objects[0]]);
+    assert_eq!(get_members(&archive), get_members(&expected));
+    assert_eq!(
+        list_with_llvm(&tmpdir, &archive),
+        list_with_llvm(&tmpdir, &expected)
+    );
+
+    // Removing every member leaves an empty archive.
+    let mut archive = Cursor::new(archive);
+    ar_archive_writer::remove_from_big_archive(&mut archive, &["file2.o", "file4.o", "file1.o"])
+        .unwrap();
+    let archive = archive.into_inner();
+    assert_eq!(get_members(&archive), []);
+    assert_eq!(list_with_llvm(&tmpdir, &archive).0, "");
+}
+
+/// Tests appending to an archive without members.
+#[test]
+fn append_to_empty() {
+    let tmpdir = common::create_tmp_dir("big_archive_append_to_empty");
+    let object1 = common::create_object(BinaryFormat::Xcoff, Architecture::PowerPc64, &[b"func1"]);
+    let objects = [("file1.o", object1.as_slice())];
+
+    let mut archive = Cursor::new(create_big_archive(&[]));
+    ar_archive_writer::append_to_big_archive(&mut archive, &common::create_members(&objects))
+        .unwrap();
+    let archive = archive.into_inner();
+    let expected = create_big_archive(&objects);
+    assert_eq!(archive, expected);
+    assert_eq!(
+        list_with_llvm(&tmpdir, &archive),
+        list_with_llvm(&tmpdir, &expected)
+    );
+}
+
+#[test]
+fn update_errors() {
+    let object1 = common::create_object(BinaryFormat::Xcoff, Architecture::PowerPc64, &[b"func1"]);
+    let original = create_big_archive(&[("file1.o", &object1)]);
+
+    // Removing a missing member leaves the archive unchanged.
+    let mut archive = Cursor::new(original.clone());
+    let err = ar_archive_writer::remove_from_big_archive(&mut archive, &["file1.o", "file1.o"])
+        .unwrap_err();
+    assert_eq!(err.to_string(), "no member named file1.o in archive");
+    assert_eq!(archive.into_inner(), original);
+
+    let mut archive = Cursor::new(b"!<arch>\n".to_vec());
+    let err = ar_archive_writer::remove_from_big_archive(&mut archive, &[]).unwrap_err();
+    assert_eq!(err.to_string(), "not an AIX big archive");
+}
@@ -6,9 +6,12 @@ use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use ar_archive_writer::{ArchiveKind, NewArchiveMember};
+use object::read::archive::ArchiveFile;
 use object::write::{self, Object};
 use object::{
-    Architecture, BinaryFormat, Endianness, SubArchitecture, SymbolFlags, SymbolKind, SymbolScope,
+    Architecture, BinaryFormat, Endianness, Object as _, ObjectComdat, ObjectSection, ObjectSymbol,
+    RelocationTarget, SectionKind, SubArchitecture, SymbolFlags, SymbolKind, SymbolScope,
+    SymbolSection,
 };
 use pretty_assertions::assert_eq;
 
@@ -66,6 +69,10 @@ fn run_llvm_ar(
             command.arg("/machine:arm64ec");
         }
 
+        if thin {
+            command.arg("/llvmlibthin");
+        }
+
         // llvm-lib reverses the order of the files versus llvm-ar.
         let mut object_paths = Vec::from(object_paths);
         object_paths.reverse();
@@ -311,6 +318,23 @@ where
             ArchiveKind::Coff,
             false,
         ),
+        // PE + Coff + thin
+        (
+            Architecture::X86_64,
+            None,
+            Endianness::Little,
+            BinaryFormat::Coff,
+            ArchiveKind::Coff,
+            true,
+        ),
+        (
+            Architecture::Aarch64,
+            Some(SubArchitecture::Arm64EC),
+            Endianness::Little,
+            BinaryFormat::Coff,
+            ArchiveKind::Coff,
+            true,
+        ),
         // MachO
         (
             Architecture::X86_64,
@@ -359,9 +383,21 @@ where
             is_ec,
         );
 
+        // Describe the differences, since the bytes alone are hard to read.
+        let differences = if llvm_ar_archive == ar_archive_writer_archive {
+            String::new()
+        } else {
+            match ar_archive_writer::diff_archives(&llvm_ar_archive, &ar_archive_writer_archive) {
+                Ok(differences) => differences
+                    .iter()
+                    .map(|difference| format!("\n  {difference}"))
+                    .collect(),
+                Err(err) => format!("\n  failed to compare archives: {err}"),
+            }
+        };
         assert_eq!(
             llvm_ar_archive, ar_archive_writer_archive,
-            "Archives differ for architecture: {architecture:?}, binary_format: {binary_format:?}, archive_kind: {archive_kind:?}, thin: {thin}",
+            "Archives differ for architecture: {architecture:?}, binary_format: {binary_format:?}, archive_kind: {archive_kind:?}, thin: {thin}{differences}",
         );
     }
 }
@@ -391,3 +427,131 @@ pub fn add_file_with_functions_to_object(
         });
     }
 }
+
+/// Creates an object that defines the functions `func_names`.
+pub fn create_object(
+    binary_format: BinaryFormat,
+    architecture: Architecture,
+    func_names: &[&[u8]],
+) -> Vec<u8> {
+    let endianness = match binary_format {
+        BinaryFormat::Xcoff => Endianness::Big,
+        _ => Endianness::Little,
+    };
+    let mut object = Object::new(binary_format, architecture, endianness);
+    add_file_with_functions_to_object(&mut object, b"file.c", func_names);
+    object.write().unwrap()
+}
+
+/// Creates an x86-64 ELF object that defines the functions `func_names`.
+pub fn create_elf_object(func_names: &[&[u8]]) -> Vec<u8> {
+    create_object(BinaryFormat::Elf, Architecture::X86_64, func_names)
+}
+
+/// Creates a member with the default object reader for each pair of a member
+/// name and contents.
+pub fn create_members<T: AsRef<[u8]>>(objects: &[(&str, T)]) -> Vec<NewArchiveMember<'static>> {
+    objects
+        .iter()
+        .map(|(name, bytes)| {
+            NewArchiveMember::new(
+                bytes.as_ref().to_vec(),
+                &ar_archive_writer::DEFAULT_OBJECT_READER,
+                name.to_string(),
+            )
+        })
+        .collect()
+}
+
+/// Writes a regular archive of `kind` with `members`.
+pub fn write_archive(members: &[NewArchiveMember<'_>], kind: ArchiveKind) -> Vec<u8> {
+    let mut output_bytes = Cursor::new(Vec::new());
+    ar_archive_writer::write_archive_to_stream(&mut output_bytes, members, kind, false, false)
+        .unwrap();
+    output_bytes.into_inner()
+}
+
+/// Returns the names in the symbol table of `archive`, in order.
+pub fn get_archive_symbols(archive: &[u8]) -> Vec<String> {
+    ArchiveFile::parse(archive)
+        .unwrap()
+        .symbols()
+        .unwrap()
+        .unwrap()
+        .map(|symbol| String::from_utf8_lossy(symbol.unwrap().name()).into_owned())
+        .collect()
+}
+
+/// Describes the sections, relocations, COMDAT groups and symbols of an
+/// object, so that objects can be compared without depending on their layout.
+pub fn describe_object(data: &[u8]) -> Vec<String> {
+    let file = object::File::parse(data).unwrap();
+    let section_name = |index| {
+        file.section_by_index(index)
+            .unwrap()
+            .name()
+            .unwrap()
+            .to_string()
+    };
+    let mut description = Vec::new();
+    for section in file.sections() {
+        // The string and symbol tables are compared through the symbols.
+        if section.kind() == SectionKind::Metadata {
+            continue;
+        }
+        description.push(format!(
+            "section {} {:?} {:?}",
+            section.name().unwrap(),
+            section.kind(),
+            section.uncompressed_data().unwrap()
+        ));
+        for (offset, relocation) in section.relocations() {
+            let target = match relocation.target() {
+                RelocationTarget::Symbol(index) => {
+                    let symbol = file.symbol_by_index(index).unwrap();
+                    match symbol.kind() {
+                        SymbolKind::Section => {
+                            format!("section {}", section_name(symbol.section_index().unwrap()))
+                        }
+                        _ => symbol.name().unwrap().to_string(),
+                    }
+                }
+                RelocationTarget::Section(index) => format!("section {}", section_name(index)),
+                target => format!("{target:?}"),
+            };
+            description.push(format!(
+                "  relocation {offset} {target} {:?}",
+                relocation.flags()
+            ));
+        }
+    }
+    for comdat in file.comdats() {
+        let sections = comdat
+            .sections()
+            .map(section_name)
+            .collect::<Vec<_>>()
+            .join(", ");
+        description.push(format!("comdat {} {sections}", comdat.name().unwrap()));
+    }
+    for symbol in file.symbols() {
+        // Section and file symbols are described by the sections, and aren't
+        // kept by every tool that rewrites objects.
+        if matches!(symbol.kind(), SymbolKind::Section | SymbolKind::File) {
+            continue;
+        }
+        let section = match symbol.section() {
+            SymbolSection::Section(index) => section_name(index),
+            section => format!("{section:?}"),
+        };
+        description.push(format!(
+            "symbol {} {:?} {section} {} {} {:?} weak: {}",
+            symbol.name().unwrap(),
+            symbol.kind(),
+            symbol.address(),
+            symbol.size(),
+            symbol.scope(),
+            symbol.is_weak()
+        ));
+    }
+    description
+}
@@ -0,0 +1,300 @@
+use std::fs;
+use std::mem::size_of;
+use std::process::Command;
+
+use ar_archive_writer::ArchiveKind;
+use object::{
+    write, Architecture, BinaryFormat, Endianness, Object, ObjectSection, ObjectSymbol,
+    RelocationEncoding, RelocationFlags, RelocationKind,
+};
+use pretty_assertions::assert_eq;
+
+mod common;
+
+fn add_relocation(
+    object: &mut write::Object<'_>,
+    section: write::SectionId,
+    offset: u64,
+    symbol: write::SymbolId,
+) {
+    object
+        .add_relocation(
+            section,
+            write::Relocation {
+                offset,
+                symbol,
+                addend: 0,
+                flags: RelocationFlags::Generic {
+                    kind: RelocationKind::Absolute,
+                    encoding: RelocationEncoding::Generic,
+                    size: 32,
+                },
+            },
+        )
+        .unwrap();
+}
+
+/// Creates an object with functions, data that refers to them, and debug
+/// sections that refer to the code and to each other.
+fn create_object(binary_format: BinaryFormat) -> Vec<u8> {
+    let mut object = write::Object::new(binary_format, Architecture::X86_64, Endianness::Little);
+    common::add_file_with_functions_to_object(&mut object, b"file.c", &[b"func1", b"func2"]);
+    let text = object.section_id(write::StandardSection::Text);
+    let data = object.section_id(write::StandardSection::Data);
+    object.append_section_data(data, &[0; 8], 8);
+    let func1 = object.symbol_id(b"func1").unwrap();
+    add_relocation(&mut object, data, 0, func1);
+
+    let (segment, debug_info, debug_str) = match binary_format {
+        BinaryFormat::MachO => (&b"__DWARF"[..], &b"__debug_info"[..], &b"__debug_str"[..]),
+        _ => (&b""[..], &b".debug_info"[..], &b".debug_str"[..]),
+    };
+    let debug_info = object.add_section(
+        segment.to_vec(),
+        debug_info.to_vec(),
+        object::SectionKind::Debug,
+    );
+    let debug_str = object.add_section(
+        segment.to_vec(),
+        debug_str.to_vec(),
+        object::SectionKind::Debug,
+    );
+    object.append_section_data(debug_info, &[2; 24], 1);
+    object.append_section_data(debug_str, b"file.c\0func1\0", 1);
+    let debug_label = object.add_symbol(write::Symbol {
+        name: b"debug_label".to_vec(),
+        value: 7,
+        size: 0,
+        kind: object::SymbolKind::Data,
+        scope: object::SymbolScope::Compilation,
+        weak: false,
+        section: write::SymbolSection::Section(debug_str),
+        flags: object::SymbolFlags::None,
+    });
+    add_relocation(&mut object, debug_info, 0, func1);
+    add_relocation(&mut object, debug_info, 8, debug_label);
+    let text_symbol = object.section_symbol(text);
+    add_relocation(&mut object, debug_info, 16, text_symbol);
+
+    // Add code after the debug sections, so that sections are renumbered.
+    let bss = object.section_id(write::StandardSection::UninitializedData);
+    object.append_section_bss(bss, 16, 8);
+    let rodata = object.section_id(write::StandardSection::ReadOnlyData);
+    object.append_section_data(rodata, &[3; 8], 8);
+    let rodata_symbol = object.section_symbol(rodata);
+    add_relocation(&mut object, data, 4, rodata_symbol);
+    object.write().unwrap()
+}
+
+/// Tests that the debug sections are removed like `llvm-objcopy --strip-debug`
+/// removes them, and that the archive symbol table is unaffected.
+#[test]
+fn strip_debug_sections() {
+    let tmpdir = common::create_tmp_dir("debug_stripping_strip_debug_sections");
+    let objcopy_path = cargo_binutils::Tool::Objcopy.path().unwrap();
+    for (binary_format, kind) in [
+        (BinaryFormat::Elf, ArchiveKind::Gnu),
+        (BinaryFormat::MachO, ArchiveKind::Darwin),
+        (BinaryFormat::Coff, ArchiveKind::Coff),
+    ] {
+        let message = format!("binary_format: {binary_format:?}");
+        let object = create_object(binary_format);
+        let input_path = tmpdir.join(format!("{binary_format:?}.o"));
+        let output_path = tmpdir.join(format!("{binary_format:?}.stripped.o"));
+        fs::write(&input_path, &object).unwrap();
+        let output = Command::new(&objcopy_path)
+            .arg("--strip-debug")
+            .arg(&input_path)
+            .arg(&output_path)
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stderr),
+            "",
+            "llvm-objcopy failed. {message}"
+        );
+        let expected = fs::read(&output_path).unwrap();
+
+        let members =
+            common::create_members(&[("a.o", &object[..]), ("data.txt", b"not an object")]);
+        let original_symbols = common::get_archive_symbols(&common::write_archive(&members, kind));
+        let members = ar_archive_writer::strip_debug_sections(members).unwrap();
+        let stripped = (*members[0].buf).as_ref();
+        assert!(stripped.len() < object.len(), "{message}");
+        assert_eq!(
+            common::describe_object(stripped),
+            common::describe_object(&expected),
+            "{message}"
+        );
+        assert_eq!((*members[1].buf).as_ref(), b"not an object", "{message}");
+        assert_eq!(
+            common::get_archive_symbols(&common::write_archive(&members, kind)),
+            original_symbols,
+            "{message}"
+        );
+    }
+}
+
+/// Converts a COFF object to the bigobj format, which has a larger file
+/// header and 20-byte symbols with 32-bit section numbers.
+fn convert_to_bigobj(data: &[u8]) -> Vec<u8> {
+    use object::pe;
+    use object::read::coff::CoffHeader as _;
+    use object::{LittleEndian as LE, U16, U32};
+
+    let header = pe::ImageFileHeader::parse(data, &mut 0).unwrap();
+    let shift = (size_of::<pe::AnonObjectHeaderBigobj>() - size_of::<pe::ImageFileHeader>()) as u32;
+    let symbols_offset = header.pointer_to_symbol_table.get(LE) as usize;
+    let number_of_symbols = header.number_of_symbols.get(LE) as usize;
+    let bigobj_header = pe::AnonObjectHeaderBigobj {
+        sig1: U16::new(LE, pe::IMAGE_FILE_MACHINE_UNKNOWN),
+        sig2: U16::new(LE, 0xffff),
+        version: U16::new(LE, 2),
+        machine: header.machine,
+        time_date_stamp: header.time_date_stamp,
+        class_id: pe::ANON_OBJECT_HEADER_BIGOBJ_CLASS_ID,
+        size_of_data: U32::new(LE, 0),
+        flags: U32::new(LE, 0),
+        meta_data_size: U32::new(LE, 0),
+        meta_data_offset: U32::new(LE, 0),
+        number_of_sections: U32::new(LE, header.number_of_sections.get(LE).into()),
+        pointer_to_symbol_table: U32::new(LE, symbols_offset as u32 + shift),
+        number_of_symbols: header.number_of_symbols,
+    };
+    let mut out = object::pod::bytes_of(&bigobj_header).to_vec();
+    out.extend_from_slice(&data[size_of::<pe::ImageFileHeader>()..symbols_offset]);
+    for index in 0..usize::from(header.number_of_sections.get(LE)) {
+        let offset =
+            size_of::<pe::AnonObjectHeaderBigobj>() + index * size_of::<pe::ImageSectionHeader>();
+        let mut section = *object::pod::from_bytes::<pe::ImageSectionHeader>(&out[offset..])
+            .unwrap()
+            .0;
+        for field in [
+            &mut section.pointer_to_raw_data,
+            &mut section.pointer_to_relocations,
+        ] {
+            if field.get(LE) != 0 {
+                field.set(LE, field.get(LE) + shift);
+            }
+        }
+        out[offset..][..size_of::<pe::ImageSectionHeader>()]
+            .copy_from_slice(object::pod::bytes_of(&section));
+    }
+    let mut index = 0;
+    while index < number_of_symbols {
+        let symbol = &data[symbols_offset + index * 18..][..18];
+        let section_number = i16::from_le_bytes([symbol[12], symbol[13]]);
+        out.extend_from_slice(&symbol[..12]);
+        out.extend_from_slice(&i32::from(section_number).to_le_bytes());
+        out.extend_from_slice(&symbol[14..]);
+        // Auxiliary symbols are padded to the size of a symbol.
+        for _ in 0..symbol[17] {
+            index += 1;
+            out.extend_from_slice(&data[symbols_offset + index * 18..][..18]);
+            out.extend_from_slice(&[0; 2]);
+        }
+        index += 1;
+    }
+    out.extend_from_slice(&data[symbols_offset + number_of_symbols * 18..]);
+    out
+}
+
+/// Tests that the debug sections of bigobj COFF objects are removed like those
+/// of regular COFF objects, and that members of other kinds are kept
+/// unchanged.
+#[test]
+fn strip_bigobj_and_other_kinds() {
+    let object = create_object(BinaryFormat::Coff);
+    let bigobj = convert_to_bigobj(&object);
+    assert_eq!(
+        object::FileKind::parse(bigobj.as_slice()).unwrap(),
+        object::FileKind::CoffBig
+    );
+    assert_eq!(
+        common::describe_object(&bigobj),
+        common::describe_object(&object)
+    );
+    let xcoff = common::create_object(BinaryFormat::Xcoff, Architecture::PowerPc64, &[b"func1"]);
+
+    let members = common::create_members(&[
+        ("a.o", object),
+        ("bigobj.o", bigobj),
+        ("xcoff.o", xcoff.clone()),
+    ]);
+    let members = ar_archive_writer::strip_debug_sections(members).unwrap();
+    let stripped_bigobj = (*members[1].buf).as_ref();
+    assert_eq!(
+        object::FileKind::parse(stripped_bigobj).unwrap(),
+        object::FileKind::CoffBig
+    );
+    assert_eq!(
+        common::describe_object(stripped_bigobj),
+        common::describe_object((*members[0].buf).as_ref())
+    );
+    assert_eq!((*members[2].buf).as_ref(), xcoff);
+}
+
+/// Tests that the symbol indices in the LLVM address-significance table are
+/// updated for the removed symbols.
+#[test]
+fn strip_addrsig() {
+    const SHT_LLVM_ADDRSIG: u32 = 0x6fff_4c03;
+
+    // The table holds the indices of `debug_label`, which is removed with the
+    // debug sections, and `func2`, which follows it in the symbol table.
+    let create_object = |addrsig: &[u8]| {
+        let mut object =
+            write::Object::new(BinaryFormat::Elf, Architecture::X86_64, Endianness::Little);
+        common::add_file_with_functions_to_object(&mut object, b"file.c", &[b"func2"]);
+        let debug_str = object.add_section(
+            Vec::new(),
+            b".debug_str".to_vec(),
+            object::SectionKind::Debug,
+        );
+        object.append_section_data(debug_str, b"file.c\0", 1);
+        object.add_symbol(write::Symbol {
+            name: b"debug_label".to_vec(),
+            value: 0,
+            size: 0,
+            kind: object::SymbolKind::Data,
+            scope: object::SymbolScope::Compilation,
+            weak: false,
+            section: write::SymbolSection::Section(debug_str),
+            flags: object::SymbolFlags::None,
+        });
+        let addrsig_section = object.add_section(
+            Vec::new(),
+            b".llvm_addrsig".to_vec(),
+            object::SectionKind::Elf(SHT_LLVM_ADDRSIG),
+        );
+        object.append_section_data(addrsig_section, addrsig, 1);
+        object.write().unwrap()
+    };
+    // The indices are small enough to be single ULEB128 bytes.
+    let get_addrsig_symbols = |data: &[u8]| {
+        let file = object::File::parse(data).unwrap();
+        let section = file.section_by_name(".llvm_addrsig").unwrap();
+        section
+            .data()
+            .unwrap()
+            .iter()
+            .map(|&index| {
+                assert!(index < 0x80);
+                let symbol = file
+                    .symbol_by_index(object::SymbolIndex(index.into()))
+                    .unwrap();
+                symbol.name().unwrap().to_string()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let file_data = create_object(&[0, 0]);
+    let file = object::File::parse(file_data.as_slice()).unwrap();
+    let index = |name: &str| file.symbol_by_name(name).unwrap().index().0 as u8;
+    let object = create_object(&[index("debug_label"), index("func2")]);
+    assert_eq!(get_addrsig_symbols(&object), ["debug_label", "func2"]);
+
+    let members = common::create_members(&[("a.o", object)]);
+    let members = ar_archive_writer::strip_debug_sections(members).unwrap();
+    assert_eq!(get_addrsig_symbols((*members[0].buf).as_ref()), ["func2"]);
+}
@@ -0,0 +1,119 @@
+use std::io::Cursor;
+
+use ar_archive_writer::{
+    ArchiveKind, DuplicateMember, DuplicateMembers, NewArchiveMember, WriteArchiveOptions,
+};
+use object::read::archive::ArchiveFile;
+use pretty_assertions::assert_eq;
+
+mod common;
+
+fn duplicate(index: usize, member_name: &str, retained_index: usize) -> DuplicateMember {
+    DuplicateMember {
+        index,
+        member_name: member_name.to_string(),
+        retained_index,
+    }
+}
+
+/// Returns the symbols of an archive, with the names of the members that they
+/// refer to.
+fn get_archive_symbol_members(members: &[NewArchiveMember<'_>]) -> Vec<(String, String)> {
+    let archive = common::write_archive(members, ArchiveKind::Gnu);
+    let archive = ArchiveFile::parse(archive.as_slice()).unwrap();
+    archive
+        .symbols()
+        .unwrap()
+        .unwrap()
+        .map(|symbol| {
+            let symbol = symbol.unwrap();
+            let member = archive.member(symbol.offset()).unwrap();
+            (
+                String::from_utf8(symbol.name().to_vec()).unwrap(),
+                String::from_utf8(member.name().to_vec()).unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn duplicate_members() {
+    let objects = [
+        ("a.o", common::create_elf_object(&[b"func1"])),
+        ("b.o", common::create_elf_object(&[b"func2"])),
+        ("copy.o", common::create_elf_object(&[b"func1"])),
+        ("data.txt", b"not an object".to_vec()),
+        ("a.o", common::create_elf_object(&[b"func1"])),
+        ("data.txt", b"not an object".to_vec()),
+        ("b.o", common::create_elf_object(&[b"func3"])),
+    ];
+    let members = common::create_members(&objects);
+    assert_eq!(
+        ar_archive_writer::find_duplicate_members(&members, false),
+        [
+            duplicate(2, "copy.o", 0),
+            duplicate(4, "a.o", 0),
+            duplicate(5, "data.txt", 3),
+        ]
+    );
+    assert_eq!(
+        ar_archive_writer::find_duplicate_members(&members, true),
+        [duplicate(4, "a.o", 0), duplicate(5, "data.txt", 3)]
+    );
+
+    let (remaining, removed) = ar_archive_writer::remove_duplicate_members(members, false);
+    let member_names = remaining
+        .iter()
+        .map(|member| member.member_name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(member_names, ["a.o", "b.o", "data.txt", "b.o"]);
+    assert_eq!(removed.len(), 3);
+    // The symbols of the removed copies refer to the retained copy.
+    assert_eq!(
+        get_archive_symbol_members(&remaining),
+        [
+            ("func1".to_string(), "a.o".to_string()),
+            ("func2".to_string(), "b.o".to_string()),
+            ("func3".to_string(), "b.o".to_string()),
+        ]
+    );
+
+    // Writing the archive with duplicate members removed gives the same
+    // archive as writing the remaining members.
+    let write_archive_with_options = |members: &[NewArchiveMember<'_>], duplicate_members| {
+        let mut output_bytes = Cursor::new(Vec::new());
+        ar_archive_writer::write_archive_to_stream_with_options(
+            &mut output_bytes,
+            members,
+            ArchiveKind::Gnu,
+            false,
+            false,
+            WriteArchiveOptions {
+                duplicate_members,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        output_bytes.into_inner()
+    };
+    for match_names in [false, true] {
+        let (remaining, _) = ar_archive_writer::remove_duplicate_members(
+            common::create_members(&objects),
+            match_names,
+        );
+        assert_eq!(
+            write_archive_with_options(
+                &common::create_members(&objects),
+                DuplicateMembers::Remove { match_names }
+            ),
+            write_archive_with_options(&remaining, DuplicateMembers::Keep),
+            "match_names: {match_names}"
+        );
+    }
+
+    let members = common::create_members(&objects[..2]);
+    assert_eq!(
+        ar_archive_writer::find_duplicate_members(&members, false),
+        []
+    );
+}
@@ -0,0 +1,133 @@
+use ar_archive_writer::{DuplicateSymbol, SymbolDefinition};
+use object::{
+    write, Architecture, BinaryFormat, ComdatKind, Endianness, SectionKind, SymbolFlags,
+    SymbolKind, SymbolScope,
+};
+use pretty_assertions::assert_eq;
+
+mod common;
+
+fn definition(index: usize, member_name: &str) -> SymbolDefinition {
+    SymbolDefinition {
+        index,
+        member_name: member_name.to_string(),
+    }
+}
+
+#[test]
+fn duplicate_symbols() {
+    let objects = [
+        ("a.o", common::create_elf_object(&[b"func1", b"func2"])),
+        ("b.o", common::create_elf_object(&[b"func3"])),
+        ("c.o", common::create_elf_object(&[b"func2", b"func4"])),
+        ("data.txt", b"not an object".to_vec()),
+        ("a.o", common::create_elf_object(&[b"func4", b"func2"])),
+    ];
+    let members = common::create_members(&objects);
+    assert_eq!(
+        ar_archive_writer::find_duplicate_symbols(&members, false).unwrap(),
+        [
+            DuplicateSymbol {
+                name: b"func2".to_vec(),
+                definitions: vec![
+                    definition(0, "a.o"),
+                    definition(2, "c.o"),
+                    definition(4, "a.o")
+                ],
+            },
+            DuplicateSymbol {
+                name: b"func4".to_vec(),
+                definitions: vec![definition(2, "c.o"), definition(4, "a.o")],
+            },
+        ]
+    );
+    let err = ar_archive_writer::check_duplicate_symbols(&members, false).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "symbols are defined by more than one archive member: func2 (a.o at 0, c.o at 2, a.o at 4); func4 (c.o at 2, a.o at 4)"
+    );
+
+    let members = common::create_members(&objects[..2]);
+    assert_eq!(
+        ar_archive_writer::find_duplicate_symbols(&members, false).unwrap(),
+        []
+    );
+    ar_archive_writer::check_duplicate_symbols(&members, false).unwrap();
+}
+
+/// Creates an object that defines `name` as a weak symbol, a COMDAT symbol or
+/// a common symbol.
+fn create_weak_object(name: &[u8], kind: &str) -> Vec<u8> {
+    let mut object =
+        write::Object::new(BinaryFormat::Elf, Architecture::X86_64, Endianness::Little);
+    let mut symbol = write::Symbol {
+        name: name.to_vec(),
+        value: 0,
+        size: 4,
+        kind: SymbolKind::Data,
+        scope: SymbolScope::Linkage,
+        weak: kind == "weak",
+        section: write::SymbolSection::Undefined,
+        flags: SymbolFlags::None,
+    };
+    if kind == "common" {
+        object.add_common_symbol(symbol, 4, 4);
+    } else {
+        let section = object.add_section(
+            Vec::new(),
+            [b".data.".as_slice(), name].concat(),
+            SectionKind::Data,
+        );
+        object.append_section_data(section, &[0; 4], 4);
+        symbol.section = write::SymbolSection::Section(section);
+        let symbol = object.add_symbol(symbol);
+        if kind == "comdat" {
+            object.add_comdat(write::Comdat {
+                kind: ComdatKind::Any,
+                symbol,
+                sections: vec![section],
+            });
+        }
+    }
+    object.write().unwrap()
+}
+
+/// Tests that weak, COMDAT and common definitions are only reported when
+/// asked for, as linkers accept them.
+#[test]
+fn weak_definitions() {
+    let objects = [
+        ("strong.o", create_weak_object(b"strong", "strong")),
+        ("strong2.o", create_weak_object(b"strong", "strong")),
+        ("weak.o", create_weak_object(b"weak", "weak")),
+        ("weak2.o", create_weak_object(b"weak", "weak")),
+        ("comdat.o", create_weak_object(b"comdat", "comdat")),
+        ("comdat2.o", create_weak_object(b"comdat", "comdat")),
+        ("common.o", create_weak_object(b"common", "common")),
+        ("common2.o", create_weak_object(b"common", "common")),
+        ("strong_weak.o", create_weak_object(b"weak", "strong")),
+    ];
+    let members = common::create_members(&objects);
+    assert_eq!(
+        ar_archive_writer::find_duplicate_symbols(&members, false).unwrap(),
+        [DuplicateSymbol {
+            name: b"strong".to_vec(),
+            definitions: vec![definition(0, "strong.o"), definition(1, "strong2.o")],
+        }]
+    );
+
+    let names = ar_archive_writer::find_duplicate_symbols(&members, true)
+        .unwrap()
+        .into_iter()
+        .map(|symbol| (symbol.name, symbol.definitions.len()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            (b"strong".to_vec(), 2),
+            (b"weak".to_vec(), 3),
+            (b"comdat".to_vec(), 2),
+            (b"common".to_vec(), 2),
+        ]
+    );
+}
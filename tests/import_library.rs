@@ -3,7 +3,9 @@ use std::io::Cursor;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
-use ar_archive_writer::{ArchiveKind, COFFShortExport, MachineTypes};
+use ar_archive_writer::{
+    ArchiveKind, COFFShortExport, ImportHints, ImportLibraryOptions, MachineTypes,
+};
 use common::{create_archive_with_ar_archive_writer, create_archive_with_llvm_ar};
 use object::read::archive::ArchiveFile;
 use object::{Architecture, SubArchitecture};
@@ -117,6 +119,38 @@ fn create_import_library_with_ar_archive_writer(
     output_archive_bytes
 }
 
+/// Creates an import library from a .def file using `llvm-lib`.
+fn create_import_library_with_llvm_lib(
+    temp_dir: &Path,
+    machine_type: MachineTypes,
+    def_path: &Path,
+) -> Vec<u8> {
+    let machine_arg = match machine_type {
+        MachineTypes::I386 => "X86",
+        MachineTypes::AMD64 => "X64",
+        MachineTypes::ARMNT => "ARM",
+        MachineTypes::ARM64 => "ARM64",
+        MachineTypes::ARM64EC => "ARM64EC",
+        _ => panic!("Unsupported machine type"),
+    };
+
+    let llvm_lib_tool_path = common::create_llvm_lib_tool(temp_dir);
+    let output_library_path = temp_dir.join("output_llvm_lib.a");
+    let output = Command::new(llvm_lib_tool_path)
+        .arg(format!("/MACHINE:{machine_arg}"))
+        .arg(format!("/DEF:{}", def_path.to_string_lossy()))
+        .arg(format!("/OUT:{}", output_library_path.to_string_lossy()))
+        .output()
+        .unwrap();
+
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "",
+        "llvm-lib failed. archive: {output_library_path:?}"
+    );
+    fs::read(output_library_path).unwrap()
+}
+
 #[test]
 fn compare_to_lib() {
     for machine_type in [
@@ -131,34 +165,9 @@ fn compare_to_lib() {
         let archive_writer_bytes =
             create_import_library_with_ar_archive_writer(&temp_dir, machine_type, false);
 
-        let llvm_lib_bytes = {
-            let machine_arg = match machine_type {
-                MachineTypes::I386 => "X86",
-                MachineTypes::AMD64 => "X64",
-                MachineTypes::ARMNT => "ARM",
-                MachineTypes::ARM64 => "ARM64",
-                MachineTypes::ARM64EC => "ARM64EC",
-                _ => panic!("Unsupported machine type"),
-            };
-
-            let llvm_lib_tool_path = common::create_llvm_lib_tool(&temp_dir);
-            let output_library_path = temp_dir.join("output_llvm_lib.a");
-            let def_path =
-                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/import_library.def");
-            let output = Command::new(llvm_lib_tool_path)
-                .arg(format!("/MACHINE:{machine_arg}"))
-                .arg(format!("/DEF:{}", def_path.to_string_lossy()))
-                .arg(format!("/OUT:{}", output_library_path.to_string_lossy()))
-                .output()
-                .unwrap();
-
-            assert_eq!(
-                String::from_utf8_lossy(&output.stderr),
-                "",
-                "llvm-lib failed. archive: {output_library_path:?}"
-            );
-            fs::read(output_library_path).unwrap()
-        };
+        let def_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/import_library.def");
+        let llvm_lib_bytes =
+            create_import_library_with_llvm_lib(&temp_dir, machine_type, &def_path);
 
         assert_eq!(
             llvm_lib_bytes, archive_writer_bytes,
@@ -281,3 +290,708 @@ fn wrap_in_archive() {
         );
     }
 }
+
+/// Reads an import library back into its exports and checks that writing them
+/// again produces an identical import library.
+#[test]
+fn read_import_library_round_trip() {
+    for (machine_type, mingw) in [
+        (MachineTypes::I386, false),
+        (MachineTypes::AMD64, false),
+        (MachineTypes::ARMNT, false),
+        (MachineTypes::ARM64, false),
+        (MachineTypes::ARM64EC, false),
+        (MachineTypes::I386, true),
+        (MachineTypes::AMD64, true),
+        (MachineTypes::ARMNT, true),
+        (MachineTypes::ARM64, true),
+    ] {
+        let temp_dir = common::create_tmp_dir("import_library_read_import_library_round_trip");
+
+        let archive_writer_bytes =
+            create_import_library_with_ar_archive_writer(&temp_dir, machine_type, mingw);
+
+        let import_library = ar_archive_writer::read_import_library(&archive_writer_bytes).unwrap();
+        assert_eq!(import_library.import_name, "MyLibrary.dll");
+        assert_eq!(import_library.machine, machine_type);
+        // An ordinal of an export that is imported by name is read as a hint.
+        let (index, export) = import_library
+            .exports
+            .iter()
+            .enumerate()
+            .find(|(_, e)| e.name.ends_with("FuncWithOrdinal"))
+            .unwrap();
+        assert_eq!((export.ordinal, import_library.hints[index]), (0, 1));
+
+        let mut round_trip_bytes = Cursor::new(Vec::new());
+        ar_archive_writer::write_import_library_with_options(
+            &mut round_trip_bytes,
+            &import_library.import_name,
+            &import_library.exports,
+            import_library.machine,
+            ImportLibraryOptions {
+                mingw,
+                hints: ImportHints::PerExport(&import_library.hints),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            archive_writer_bytes,
+            round_trip_bytes.into_inner(),
+            "Import library differs after round-trip. Machine type: {machine_type:?}, mingw: {mingw}",
+        );
+    }
+}
+
+#[test]
+fn write_module_definition() {
+    const EXPECTED: &str = "LIBRARY MyLibrary.dll
+EXPORTS
+   NormalFunc
+   NormalData DATA
+   NormalConstant CONSTANT
+   PrivateFunc PRIVATE
+   FuncWithOrdinal @1
+   FuncWithNoName @2 NONAME
+   RenamedFunc=InternalName
+   ReexportedFunc=OtherModule.OtherName
+   ReexportedViaOrd=OtherModule.#42
+   FuncWithImportName==ImportName
+   ?CppFunc@SingleAt
+   ?CppFunc@@DoubleAt
+   ?CppFunc@@@TripleAt
+   \"DATA\"
+   \"Name With Spaces\" @3
+";
+
+    for machine_type in [
+        MachineTypes::I386,
+        MachineTypes::AMD64,
+        MachineTypes::ARMNT,
+        MachineTypes::ARM64,
+        MachineTypes::ARM64EC,
+    ] {
+        let prefix = match machine_type {
+            MachineTypes::I386 => "_",
+            _ => "",
+        };
+        let mut exports = get_members(machine_type);
+        exports.push(COFFShortExport {
+            name: format!("{prefix}DATA"),
+            ..DEFAULT_EXPORT
+        });
+        exports.push(COFFShortExport {
+            name: format!("{prefix}Name With Spaces"),
+            ordinal: 3,
+            ..DEFAULT_EXPORT
+        });
+
+        let mut def = Vec::new();
+        ar_archive_writer::write_module_definition(
+            &mut def,
+            "MyLibrary.dll",
+            &exports,
+            machine_type,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(def).unwrap(),
+            EXPECTED,
+            "Machine type: {machine_type:?}"
+        );
+    }
+}
+
+/// Checks that a written .def file is parsed by `llvm-lib` back into the same
+/// exports.
+#[test]
+fn module_definition_compare_to_lib() {
+    // Newer versions of `llvm-lib` reject some of the test's C++ names on
+    // ARM64EC, so it isn't included here.
+    for machine_type in [
+        MachineTypes::I386,
+        MachineTypes::AMD64,
+        MachineTypes::ARMNT,
+        MachineTypes::ARM64,
+    ] {
+        let temp_dir = common::create_tmp_dir("import_library_module_definition_compare_to_lib");
+
+        // Newer versions of `llvm-lib` treat `==` as an import name rather than
+        // a weak alias and use a different name type for re-exports, so leave
+        // those out.
+        let exports = get_members(machine_type)
+            .into_iter()
+            .filter(|e| e.alias_target.is_none() && !e.name.contains('.'))
+            .collect::<Vec<_>>();
+
+        let mut archive_writer_bytes = Cursor::new(Vec::new());
+        ar_archive_writer::write_import_library(
+            &mut archive_writer_bytes,
+            "MyLibrary.dll",
+            &exports,
+            machine_type,
+            false,
+        )
+        .unwrap();
+
+        let def_path = temp_dir.join("output_ar_archive_writer.def");
+        let mut def = Vec::new();
+        ar_archive_writer::write_module_definition(
+            &mut def,
+            "MyLibrary.dll",
+            &exports,
+            machine_type,
+            false,
+        )
+        .unwrap();
+        fs::write(&def_path, def).unwrap();
+
+        let llvm_lib_bytes =
+            create_import_library_with_llvm_lib(&temp_dir, machine_type, &def_path);
+        let archive_writer_bytes = archive_writer_bytes.into_inner();
+
+        // Compare the symbols first so that a difference in the symbols
+        // written for CONSTANT exports is easy to see.
+        assert_eq!(
+            common::get_archive_symbols(&llvm_lib_bytes),
+            common::get_archive_symbols(&archive_writer_bytes),
+            "Import library symbols differ. Machine type: {machine_type:?}",
+        );
+        assert_eq!(
+            llvm_lib_bytes, archive_writer_bytes,
+            "Import library differs. Machine type: {machine_type:?}",
+        );
+    }
+}
+
+/// Checks the hints that are written for exports imported by name.
+#[test]
+fn import_library_hints() {
+    for machine_type in [MachineTypes::I386, MachineTypes::AMD64, MachineTypes::ARM64] {
+        let write_and_read_ordinals_or_hints = |hints| {
+            let mut bytes = Cursor::new(Vec::new());
+            ar_archive_writer::write_import_library_with_options(
+                &mut bytes,
+                "MyLibrary.dll",
+                &get_members(machine_type),
+                machine_type,
+                ImportLibraryOptions {
+                    mingw: false,
+                    hints,
+                },
+            )
+            .unwrap();
+            let import_library = ar_archive_writer::read_import_library(bytes.get_ref()).unwrap();
+            import_library
+                .exports
+                .iter()
+                .zip(import_library.hints)
+                .map(|(e, hint)| if e.noname { e.ordinal } else { hint })
+                .collect::<Vec<_>>()
+        };
+
+        // Exports are: NormalFunc, NormalData, NormalConstant, FuncWithOrdinal,
+        // FuncWithNoName, RenamedFunc, ReexportedFunc, ReexportedViaOrd,
+        // FuncWithImportName (weak alias), ?CppFunc@SingleAt,
+        // ?CppFunc@@DoubleAt and ?CppFunc@@@TripleAt.
+        assert_eq!(
+            write_and_read_ordinals_or_hints(ImportHints::Ordinal),
+            [0, 0, 0, 1, 2, 0, 0, 0, 0, 0, 0, 0],
+            "Machine type: {machine_type:?}"
+        );
+        // The sorted names also include PrivateFunc, but not the aliased or
+        // NONAME exports.
+        assert_eq!(
+            write_and_read_ordinals_or_hints(ImportHints::SortedNames),
+            [6, 5, 4, 1, 2, 10, 8, 9, 0, 2, 1, 0],
+            "Machine type: {machine_type:?}"
+        );
+        assert_eq!(
+            write_and_read_ordinals_or_hints(ImportHints::ExportTable(&[
+                "NormalData",
+                "NormalFunc",
+                "RenamedFunc"
+            ])),
+            [1, 0, 0, 1, 2, 2, 0, 0, 0, 0, 0, 0],
+            "Machine type: {machine_type:?}"
+        );
+    }
+}
+
+/// Merges two import libraries and checks that only one NULL import
+/// descriptor is kept while each DLL keeps its own import descriptor.
+#[test]
+fn merge_import_libraries() {
+    for (machine_type, mingw) in [
+        (MachineTypes::I386, false),
+        (MachineTypes::AMD64, false),
+        (MachineTypes::ARM64EC, false),
+        (MachineTypes::AMD64, true),
+    ] {
+        let write_import_library = |import_name: &str, exports: &[COFFShortExport]| {
+            let mut bytes = Cursor::new(Vec::new());
+            ar_archive_writer::write_import_library(
+                &mut bytes,
+                import_name,
+                exports,
+                machine_type,
+                mingw,
+            )
+            .unwrap();
+            bytes.into_inner()
+        };
+        let first = write_import_library("MyLibrary.dll", &get_members(machine_type));
+        let second = write_import_library(
+            "OtherLibrary.dll",
+            &[COFFShortExport {
+                name: "OtherFunc".to_string(),
+                ..DEFAULT_EXPORT
+            }],
+        );
+
+        let mut merged = Cursor::new(Vec::new());
+        ar_archive_writer::merge_import_libraries(&mut merged, &[&first, &second], mingw).unwrap();
+        let merged = merged.into_inner();
+
+        // `object` returns the EC symbol table as a member, so don't count it.
+        let member_count = |bytes: &[u8]| {
+            ArchiveFile::parse(bytes)
+                .unwrap()
+                .members()
+                .filter(|member| member.as_ref().unwrap().name() != b"/<ECSYMBOLS>/")
+                .count()
+        };
+        let archive = ArchiveFile::parse(merged.as_slice()).unwrap();
+        assert_eq!(
+            member_count(&merged),
+            member_count(&first) + member_count(&second) - 1,
+            "Machine type: {machine_type:?}, mingw: {mingw}",
+        );
+
+        let symbols = archive
+            .symbols()
+            .unwrap()
+            .unwrap()
+            .map(|symbol| symbol.unwrap().name().to_vec())
+            .collect::<Vec<_>>();
+        let count = |name: &[u8]| symbols.iter().filter(|symbol| *symbol == name).count();
+        assert_eq!(count(b"__NULL_IMPORT_DESCRIPTOR"), 1);
+        assert_eq!(count(b"__IMPORT_DESCRIPTOR_MyLibrary"), 1);
+        assert_eq!(count(b"__IMPORT_DESCRIPTOR_OtherLibrary"), 1);
+        assert_eq!(count(b"\x7fMyLibrary_NULL_THUNK_DATA"), 1);
+        assert_eq!(count(b"\x7fOtherLibrary_NULL_THUNK_DATA"), 1);
+        if machine_type != MachineTypes::ARM64EC {
+            // For ARM64EC, this is in the EC symbol table instead.
+            assert_eq!(count(b"__imp_OtherFunc"), 1);
+        }
+
+        // Merging a single library is a no-op.
+        let mut merged = Cursor::new(Vec::new());
+        ar_archive_writer::merge_import_libraries(&mut merged, &[&first], mingw).unwrap();
+        assert_eq!(merged.into_inner(), first);
+    }
+}
+
+/// Compares the symbols written for DATA and CONSTANT exports to `llvm-lib`.
+#[test]
+fn data_and_constant_compare_to_lib() {
+    for machine_type in [
+        MachineTypes::I386,
+        MachineTypes::AMD64,
+        MachineTypes::ARMNT,
+        MachineTypes::ARM64,
+        MachineTypes::ARM64EC,
+    ] {
+        let temp_dir = common::create_tmp_dir("import_library_data_and_constant_compare_to_lib");
+
+        let prefix = match machine_type {
+            MachineTypes::I386 => "_",
+            _ => "",
+        };
+        let exports = [
+            COFFShortExport {
+                name: format!("{prefix}Func"),
+                ..DEFAULT_EXPORT
+            },
+            COFFShortExport {
+                name: format!("{prefix}Data"),
+                data: true,
+                ..DEFAULT_EXPORT
+            },
+            COFFShortExport {
+                name: format!("{prefix}Constant"),
+                constant: true,
+                ..DEFAULT_EXPORT
+            },
+            COFFShortExport {
+                name: format!("{prefix}ConstantWithOrdinal"),
+                ordinal: 5,
+                constant: true,
+                ..DEFAULT_EXPORT
+            },
+        ];
+
+        let mut archive_writer_bytes = Cursor::new(Vec::new());
+        ar_archive_writer::write_import_library(
+            &mut archive_writer_bytes,
+            "MyLibrary.dll",
+            &exports,
+            machine_type,
+            false,
+        )
+        .unwrap();
+
+        let def_path = temp_dir.join("input.def");
+        fs::write(
+            &def_path,
+            "LIBRARY MyLibrary
+EXPORTS
+   Func
+   Data DATA
+   Constant CONSTANT
+   ConstantWithOrdinal @5 CONSTANT
+",
+        )
+        .unwrap();
+        let llvm_lib_bytes =
+            create_import_library_with_llvm_lib(&temp_dir, machine_type, &def_path);
+
+        assert_eq!(
+            llvm_lib_bytes,
+            archive_writer_bytes.into_inner(),
+            "Import library differs. Machine type: {machine_type:?}",
+        );
+    }
+}
+
+/// Checks the archive symbols written for code, DATA and CONSTANT exports.
+#[test]
+fn data_and_constant_symbols() {
+    let exports = [
+        COFFShortExport {
+            name: "Func".to_string(),
+            ..DEFAULT_EXPORT
+        },
+        COFFShortExport {
+            name: "Data".to_string(),
+            data: true,
+            ..DEFAULT_EXPORT
+        },
+        COFFShortExport {
+            name: "Constant".to_string(),
+            constant: true,
+            ..DEFAULT_EXPORT
+        },
+    ];
+    let mut bytes = Cursor::new(Vec::new());
+    ar_archive_writer::write_import_library(
+        &mut bytes,
+        "MyLibrary.dll",
+        &exports,
+        MachineTypes::AMD64,
+        false,
+    )
+    .unwrap();
+
+    let symbols = common::get_archive_symbols(&bytes.into_inner());
+    let has_symbol = |name: &str| symbols.iter().any(|symbol| symbol == name);
+    // Code has the import symbol and the thunk.
+    assert!(has_symbol("__imp_Func"));
+    assert!(has_symbol("Func"));
+    // Data only has the import symbol.
+    assert!(has_symbol("__imp_Data"));
+    assert!(!has_symbol("Data"));
+    // Constants have the import symbol and the plain name.
+    assert!(has_symbol("__imp_Constant"));
+    assert!(has_symbol("Constant"));
+}
+
+#[test]
+fn validate_exports() {
+    let exports = [
+        COFFShortExport {
+            name: "Data".to_string(),
+            data: true,
+            ..DEFAULT_EXPORT
+        },
+        COFFShortExport {
+            name: "Constant".to_string(),
+            constant: true,
+            ..DEFAULT_EXPORT
+        },
+    ];
+    assert_eq!(
+        ar_archive_writer::validate_exports(&exports).unwrap(),
+        ["Constant: CONSTANT keyword is obsolete; use DATA"]
+    );
+    let warnings = ar_archive_writer::write_import_library_with_options(
+        &mut Cursor::new(Vec::new()),
+        "MyLibrary.dll",
+        &exports,
+        MachineTypes::AMD64,
+        ImportLibraryOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        warnings,
+        ["Constant: CONSTANT keyword is obsolete; use DATA"]
+    );
+
+    let exports = [COFFShortExport {
+        name: "DataAndConstant".to_string(),
+        data: true,
+        constant: true,
+        ..DEFAULT_EXPORT
+    }];
+    let error = ar_archive_writer::validate_exports(&exports).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(
+        error.to_string(),
+        "DataAndConstant: an export cannot be both DATA and CONSTANT"
+    );
+    let error = ar_archive_writer::write_import_library(
+        &mut Cursor::new(Vec::new()),
+        "MyLibrary.dll",
+        &exports,
+        MachineTypes::AMD64,
+        false,
+    )
+    .unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+/// Checks the public symbol and the name the loader imports for each I386
+/// calling convention. The name types are chosen like LLVM's `getNameType`.
+#[test]
+fn import_library_decoration() {
+    use object::read::coff::{ImportFile, ImportName};
+
+    let exports = [
+        COFFShortExport {
+            name: "_CdeclFunc".to_string(),
+            ..DEFAULT_EXPORT
+        },
+        COFFShortExport {
+            name: "_StdcallFunc@8".to_string(),
+            ..DEFAULT_EXPORT
+        },
+        COFFShortExport {
+            name: "@FastcallFunc@8".to_string(),
+            ..DEFAULT_EXPORT
+        },
+        COFFShortExport {
+            name: "VectorcallFunc@@8".to_string(),
+            ..DEFAULT_EXPORT
+        },
+        COFFShortExport {
+            name: "_UnderscoreVectorcallFunc@@8".to_string(),
+            ..DEFAULT_EXPORT
+        },
+        // Renaming an undecorated name keeps the calling convention of the
+        // symbol.
+        COFFShortExport {
+            name: "_InternalFastcall".to_string(),
+            ext_name: Some("_RenamedFastcall".to_string()),
+            symbol_name: Some("@InternalFastcall@8".to_string()),
+            ..DEFAULT_EXPORT
+        },
+        COFFShortExport {
+            name: "__UnderscoreVectorcall".to_string(),
+            symbol_name: Some("_UnderscoreVectorcall@@8".to_string()),
+            ..DEFAULT_EXPORT
+        },
+    ];
+
+    for (mingw, expected) in [
+        (
+            false,
+            [
+                ("_CdeclFunc", "CdeclFunc"),
+                ("_StdcallFunc@8", "_StdcallFunc@8"),
+                ("@FastcallFunc@8", "@FastcallFunc@8"),
+                ("VectorcallFunc@@8", "VectorcallFunc@@8"),
+                (
+                    "_UnderscoreVectorcallFunc@@8",
+                    "_UnderscoreVectorcallFunc@@8",
+                ),
+                ("@RenamedFastcall@8", "RenamedFastcall"),
+                ("_UnderscoreVectorcall@@8", "UnderscoreVectorcall"),
+            ],
+        ),
+        (
+            true,
+            [
+                ("_CdeclFunc", "CdeclFunc"),
+                ("_StdcallFunc@8", "StdcallFunc@8"),
+                ("@FastcallFunc@8", "@FastcallFunc@8"),
+                ("VectorcallFunc@@8", "VectorcallFunc@@8"),
+                (
+                    "_UnderscoreVectorcallFunc@@8",
+                    "UnderscoreVectorcallFunc@@8",
+                ),
+                ("@RenamedFastcall@8", "RenamedFastcall"),
+                ("_UnderscoreVectorcall@@8", "UnderscoreVectorcall"),
+            ],
+        ),
+    ] {
+        let mut bytes = Cursor::new(Vec::new());
+        ar_archive_writer::write_import_library(
+            &mut bytes,
+            "MyLibrary.dll",
+            &exports,
+            MachineTypes::I386,
+            mingw,
+        )
+        .unwrap();
+
+        let bytes = bytes.into_inner();
+        let archive = ArchiveFile::parse(bytes.as_slice()).unwrap();
+        let imports = archive
+            .members()
+            .filter_map(|member| {
+                let data = member.unwrap().data(bytes.as_slice()).unwrap();
+                let import = ImportFile::parse(data).ok()?;
+                let ImportName::Name(import_name) = import.import() else {
+                    panic!("unexpected import by ordinal");
+                };
+                Some((
+                    String::from_utf8(import.symbol().to_vec()).unwrap(),
+                    String::from_utf8(import_name.to_vec()).unwrap(),
+                ))
+            })
+            .collect::<Vec<_>>();
+        let expected = expected
+            .iter()
+            .map(|&(symbol, import_name)| (symbol.to_string(), import_name.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(imports, expected, "MinGW: {mingw}");
+    }
+}
+
+/// Checks where `$$h` is inserted into C++ names on ARM64EC, including names
+/// whose template arguments contain `@@` and `@@@`.
+#[test]
+fn arm64ec_cpp_mangling_compare_to_lib() {
+    let temp_dir = common::create_tmp_dir("import_library_arm64ec_cpp_mangling_compare_to_lib");
+
+    let names = [
+        "?Func@@YAXXZ",
+        "?Method@Class@Namespace@@QEAAHH@Z",
+        "??0Class@@QEAA@XZ",
+        "??4Class@@QEAAAEAV0@AEBV0@@Z",
+        "??$Template@H@@YAXH@Z",
+        "?Method@?$Outer@V?$Inner@H@@@@QEAAXXZ",
+        "??$Template@V?$Outer@V?$Inner@H@@@@@@YAXV?$Outer@V?$Inner@H@@@@@Z",
+        "?Func@?A0x12345678@@YAXXZ",
+    ];
+    let exports = names
+        .iter()
+        .map(|name| COFFShortExport {
+            name: name.to_string(),
+            ..DEFAULT_EXPORT
+        })
+        .collect::<Vec<_>>();
+
+    let mut archive_writer_bytes = Cursor::new(Vec::new());
+    ar_archive_writer::write_import_library(
+        &mut archive_writer_bytes,
+        "MyLibrary.dll",
+        &exports,
+        MachineTypes::ARM64EC,
+        false,
+    )
+    .unwrap();
+
+    let def_path = temp_dir.join("input.def");
+    fs::write(
+        &def_path,
+        format!("LIBRARY MyLibrary\nEXPORTS\n   {}\n", names.join("\n   ")),
+    )
+    .unwrap();
+    let llvm_lib_bytes =
+        create_import_library_with_llvm_lib(&temp_dir, MachineTypes::ARM64EC, &def_path);
+
+    assert_eq!(llvm_lib_bytes, archive_writer_bytes.into_inner());
+}
+
+/// Empty, non-ASCII and non-UTF-8 names must not panic on ARM64EC.
+#[test]
+fn arm64ec_unusual_names() {
+    let write_import_library = |name: &str| {
+        let exports = [COFFShortExport {
+            name: name.to_string(),
+            ..DEFAULT_EXPORT
+        }];
+        ar_archive_writer::write_import_library(
+            &mut Cursor::new(Vec::new()),
+            "MyLibrary.dll",
+            &exports,
+            MachineTypes::ARM64EC,
+            false,
+        )
+    };
+    write_import_library("Fonction\u{e9}").unwrap();
+    write_import_library("?Fonction\u{e9}@@YAXXZ").unwrap();
+    assert_eq!(
+        write_import_library("").unwrap_err().kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+    assert_eq!(
+        write_import_library("?Func$$h").unwrap_err().to_string(),
+        "Invalid ARM64EC function name '?Func$$h'"
+    );
+    // The mangler parses the name to find where to insert $$h. Names that are
+    // nested too deeply fall back to the legacy insertion point rather than
+    // overflowing the stack.
+    let name = format!("??$f@{}@@YAXXZ", "V?$f@".repeat(100_000));
+    write_import_library(&name).unwrap();
+
+    // Short import members are read as raw bytes.
+    let short_import = |symbol: &[u8]| {
+        let data = [symbol, b"\0MyLibrary.dll\0"].concat();
+        let mut member = Vec::new();
+        member.extend_from_slice(&0u16.to_le_bytes());
+        member.extend_from_slice(&0xffffu16.to_le_bytes());
+        member.extend_from_slice(&0u16.to_le_bytes());
+        member.extend_from_slice(&object::pe::IMAGE_FILE_MACHINE_ARM64EC.to_le_bytes());
+        member.extend_from_slice(&0u32.to_le_bytes());
+        member.extend_from_slice(&u32::try_from(data.len()).unwrap().to_le_bytes());
+        member.extend_from_slice(&0u16.to_le_bytes());
+        member.extend_from_slice(&(object::pe::IMPORT_OBJECT_NAME << 2).to_le_bytes());
+        member.extend_from_slice(&data);
+        member
+    };
+    let write_archive = |symbol: &[u8]| {
+        let members = [ar_archive_writer::NewArchiveMember::new(
+            short_import(symbol),
+            &ar_archive_writer::DEFAULT_OBJECT_READER,
+            "MyLibrary.dll".to_string(),
+        )];
+        let mut output = Cursor::new(Vec::new());
+        ar_archive_writer::write_archive_to_stream(
+            &mut output,
+            &members,
+            ArchiveKind::Coff,
+            false,
+            true,
+        )
+        .map(|()| output.into_inner())
+    };
+    let archive_bytes = write_archive(b"#Func\xff").unwrap();
+    let archive = ArchiveFile::parse(archive_bytes.as_slice()).unwrap();
+    let ec_symbols = archive
+        .members()
+        .map(|member| member.unwrap())
+        .find(|member| member.name() == b"/<ECSYMBOLS>/")
+        .unwrap()
+        .data(archive_bytes.as_slice())
+        .unwrap();
+    assert!(ec_symbols
+        .split(|&c| c == 0)
+        .any(|symbol| symbol.ends_with(b"__imp_Func\xff")));
+    write_archive(b"?Func\xff@@YAXXZ").unwrap();
+    // A member whose symbols can't be read is treated as non-symbolic.
+    write_archive(b"").unwrap();
+}
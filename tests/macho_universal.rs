@@ -0,0 +1,243 @@
+use std::io::Cursor;
+
+use ar_archive_writer::{
+    ArchiveKind, FatMembers, NewArchiveMember, UniversalSlice, WriteArchiveOptions,
+};
+use object::read::archive::ArchiveFile;
+use object::{macho, write, Architecture, BinaryFormat, Endianness};
+use pretty_assertions::assert_eq;
+
+mod common;
+
+fn write_universal_archive(slices: &[&[NewArchiveMember<'_>]]) -> std::io::Result<Vec<u8>> {
+    let mut output_bytes = Cursor::new(Vec::new());
+    ar_archive_writer::write_universal_archive(&mut output_bytes, slices)?;
+    Ok(output_bytes.into_inner())
+}
+
+/// Tests that a universal archive contains a Darwin archive per architecture,
+/// with arm64 last as `lipo` orders it.
+#[test]
+fn universal_archive() {
+    let arm64_object1 =
+        common::create_object(BinaryFormat::MachO, Architecture::Aarch64, &[b"func1"]);
+    let arm64_object2 =
+        common::create_object(BinaryFormat::MachO, Architecture::Aarch64, &[b"func2"]);
+    let x86_64_object =
+        common::create_object(BinaryFormat::MachO, Architecture::X86_64, &[b"func1"]);
+    let i386_object = common::create_object(BinaryFormat::MachO, Architecture::I386, &[b"func1"]);
+    let arm64_members =
+        common::create_members(&[("file1.o", &arm64_object1), ("file2.o", &arm64_object2)]);
+    let x86_64_members = common::create_members(&[("file1.o", &x86_64_object)]);
+    let i386_members = common::create_members(&[("file1.o", &i386_object)]);
+
+    let universal =
+        write_universal_archive(&[&arm64_members, &x86_64_members, &i386_members]).unwrap();
+    assert_eq!(&universal[..4], &macho::FAT_MAGIC.to_be_bytes());
+
+    let slices = ar_archive_writer::read_universal_binary(&universal).unwrap();
+    let summary = slices
+        .iter()
+        .map(|slice| (slice.cpu_type, slice.cpu_subtype, slice.p2_alignment))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        [
+            (macho::CPU_TYPE_X86, macho::CPU_SUBTYPE_I386_ALL, 2),
+            (macho::CPU_TYPE_X86_64, macho::CPU_SUBTYPE_X86_64_ALL, 3),
+            (macho::CPU_TYPE_ARM64, macho::CPU_SUBTYPE_ARM64_ALL, 3),
+        ]
+    );
+    assert_eq!(
+        slices[0].data,
+        common::write_archive(&i386_members, ArchiveKind::Darwin)
+    );
+    assert_eq!(
+        slices[1].data,
+        common::write_archive(&x86_64_members, ArchiveKind::Darwin)
+    );
+    assert_eq!(
+        slices[2].data,
+        common::write_archive(&arm64_members, ArchiveKind::Darwin)
+    );
+    for slice in &slices {
+        let offset = slice.data.as_ptr() as usize - universal.as_ptr() as usize;
+        assert_eq!(offset % (1 << slice.p2_alignment), 0);
+    }
+}
+
+/// Tests writing and reading back a universal binary made of arbitrary slices.
+#[test]
+fn universal_binary_round_trip() {
+    let slices = [
+        UniversalSlice {
+            cpu_type: macho::CPU_TYPE_X86_64,
+            cpu_subtype: macho::CPU_SUBTYPE_X86_64_ALL,
+            p2_alignment: 12,
+            data: b"x86_64 slice",
+        },
+        UniversalSlice {
+            cpu_type: macho::CPU_TYPE_ARM64,
+            cpu_subtype: macho::CPU_SUBTYPE_ARM64E,
+            p2_alignment: 14,
+            data: b"arm64e slice",
+        },
+    ];
+    let mut output_bytes = Cursor::new(Vec::new());
+    ar_archive_writer::write_universal_binary(&mut output_bytes, &slices).unwrap();
+    let universal = output_bytes.into_inner();
+    assert_eq!(universal.len(), (1 << 14) + b"arm64e slice".len());
+    assert_eq!(
+        ar_archive_writer::read_universal_binary(&universal).unwrap(),
+        slices
+    );
+
+    let err = ar_archive_writer::read_universal_binary(slices[0].data).unwrap_err();
+    assert_eq!(err.to_string(), "not a Mach-O universal binary");
+}
+
+#[test]
+fn universal_archive_errors() {
+    let arm64_object =
+        common::create_object(BinaryFormat::MachO, Architecture::Aarch64, &[b"func1"]);
+    let x86_64_object =
+        common::create_object(BinaryFormat::MachO, Architecture::X86_64, &[b"func1"]);
+    let elf_object = {
+        let mut object =
+            write::Object::new(BinaryFormat::Elf, Architecture::X86_64, Endianness::Little);
+        common::add_file_with_functions_to_object(&mut object, b"file.c", &[b"func1"]);
+        object.write().unwrap()
+    };
+
+    // Members of a slice must all have the same architecture.
+    let mixed_members =
+        common::create_members(&[("arm64.o", &arm64_object), ("x86_64.o", &x86_64_object)]);
+    let err = write_universal_archive(&[&mixed_members]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "archive member x86_64.o cputype ({}) and cpusubtype({}) does not match previous archive members cputype ({}) and cpusubtype({}) (all members must match)",
+            macho::CPU_TYPE_X86_64,
+            macho::CPU_SUBTYPE_X86_64_ALL,
+            macho::CPU_TYPE_ARM64,
+            macho::CPU_SUBTYPE_ARM64_ALL,
+        )
+    );
+
+    // Members must be Mach-O objects.
+    let elf_members = common::create_members(&[("elf.o", &elf_object)]);
+    let err = write_universal_archive(&[&elf_members]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "archive member elf.o is neither a MachO file or an LLVM IR file (not allowed in an archive)"
+    );
+
+    // Universal binaries can't be nested.
+    let arm64_members = common::create_members(&[("arm64.o", &arm64_object)]);
+    let fat_object = write_universal_archive(&[&arm64_members]).unwrap();
+    let fat_members = common::create_members(&[("fat.o", &fat_object)]);
+    let err = write_universal_archive(&[&fat_members]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "archive member fat.o is a fat file (not allowed in an archive)"
+    );
+
+    // Each architecture can only appear once.
+    let err = write_universal_archive(&[&arm64_members, &arm64_members]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "slices with cputype ({}) and cpusubtype ({}) have the same architecture",
+            macho::CPU_TYPE_ARM64,
+            macho::CPU_SUBTYPE_ARM64_ALL,
+        )
+    );
+
+    // The architecture of an empty slice is unknown.
+    let err = write_universal_archive(&[&[]]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "could not determine cpu type and subtype for archive"
+    );
+}
+
+/// Tests the handling of Mach-O universal binaries as archive members.
+#[test]
+fn fat_members() {
+    let arm64_object =
+        common::create_object(BinaryFormat::MachO, Architecture::Aarch64, &[b"arm64_func"]);
+    let x86_64_object =
+        common::create_object(BinaryFormat::MachO, Architecture::X86_64, &[b"x86_64_func"]);
+    let slices = [
+        UniversalSlice {
+            cpu_type: macho::CPU_TYPE_X86_64,
+            cpu_subtype: macho::CPU_SUBTYPE_X86_64_ALL,
+            p2_alignment: 3,
+            data: &x86_64_object,
+        },
+        UniversalSlice {
+            cpu_type: macho::CPU_TYPE_ARM64,
+            cpu_subtype: macho::CPU_SUBTYPE_ARM64_ALL,
+            p2_alignment: 3,
+            data: &arm64_object,
+        },
+    ];
+    let mut fat_object = Cursor::new(Vec::new());
+    ar_archive_writer::write_universal_binary(&mut fat_object, &slices).unwrap();
+    let fat_object = fat_object.into_inner();
+
+    let write_archive = |fat_members: FatMembers| {
+        let members = common::create_members(&[("fat.o", &fat_object)]);
+        let mut output_bytes = Cursor::new(Vec::new());
+        ar_archive_writer::write_archive_to_stream_with_options(
+            &mut output_bytes,
+            &members,
+            ArchiveKind::Darwin,
+            false,
+            false,
+            WriteArchiveOptions {
+                fat_members,
+                ..Default::default()
+            },
+        )
+        .map(|()| output_bytes.into_inner())
+    };
+
+    // By default, the member is added without symbols, like LLVM does.
+    let archive = write_archive(FatMembers::default()).unwrap();
+    assert_eq!(common::get_archive_symbols(&archive), Vec::<String>::new());
+
+    let err = write_archive(FatMembers::Reject).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "archive member fat.o is a fat file (not allowed in an archive)"
+    );
+
+    let archive = write_archive(FatMembers::Slice {
+        cpu_type: macho::CPU_TYPE_ARM64,
+        cpu_subtype: macho::CPU_SUBTYPE_ARM64_ALL,
+    })
+    .unwrap();
+    assert_eq!(common::get_archive_symbols(&archive), ["_arm64_func"]);
+    let member = ArchiveFile::parse(archive.as_slice())
+        .unwrap()
+        .members()
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(member.data(archive.as_slice()).unwrap(), fat_object);
+
+    let err = write_archive(FatMembers::Slice {
+        cpu_type: macho::CPU_TYPE_X86,
+        cpu_subtype: macho::CPU_SUBTYPE_I386_ALL,
+    })
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "archive member fat.o is a fat file without a slice for cputype ({}) and cpusubtype ({})",
+            macho::CPU_TYPE_X86,
+            macho::CPU_SUBTYPE_I386_ALL,
+        )
+    );
+}
@@ -0,0 +1,156 @@
+use std::io::Cursor;
+
+use ar_archive_writer::{ArchiveKind, MemberSelection, SelectedMember};
+use object::{write, Architecture, BinaryFormat, Endianness, SymbolFlags, SymbolKind, SymbolScope};
+use pretty_assertions::assert_eq;
+
+mod common;
+
+fn create_object(defined: &[&[u8]], undefined: &[&[u8]]) -> Vec<u8> {
+    create_object_with_weak_undefined(defined, undefined, &[])
+}
+
+fn create_object_with_weak_undefined(
+    defined: &[&[u8]],
+    undefined: &[&[u8]],
+    weak_undefined: &[&[u8]],
+) -> Vec<u8> {
+    let mut object =
+        write::Object::new(BinaryFormat::Elf, Architecture::X86_64, Endianness::Little);
+    common::add_file_with_functions_to_object(&mut object, b"file.c", defined);
+    let undefined = undefined.iter().map(|name| (name, false));
+    let weak_undefined = weak_undefined.iter().map(|name| (name, true));
+    for (name, weak) in undefined.chain(weak_undefined) {
+        object.add_symbol(write::Symbol {
+            name: name.to_vec(),
+            value: 0,
+            size: 0,
+            kind: SymbolKind::Text,
+            scope: SymbolScope::Linkage,
+            weak,
+            section: write::SymbolSection::Undefined,
+            flags: SymbolFlags::None,
+        });
+    }
+    object.write().unwrap()
+}
+
+fn selected(
+    index: usize,
+    member_name: &str,
+    symbol: &[u8],
+    referenced_by: Option<usize>,
+) -> SelectedMember {
+    SelectedMember {
+        index,
+        member_name: member_name.to_string(),
+        symbol: symbol.to_vec(),
+        referenced_by,
+    }
+}
+
+#[test]
+fn select_members() {
+    let objects = [
+        (
+            "main.o",
+            create_object(&[b"main"], &[b"b_func", b"missing"]),
+        ),
+        ("b.o", create_object(&[b"b_func"], &[b"c_func", b"main"])),
+        ("unused.o", create_object(&[b"unused"], &[b"b_func"])),
+        (
+            "c.o",
+            create_object(&[b"c_func", b"c_other"], &[b"missing"]),
+        ),
+        ("c2.o", create_object(&[b"c_func", b"c2_func"], &[])),
+        ("c_other.o", create_object(&[b"c_other"], &[])),
+        ("e.o", create_object(&[b"e_func"], &[b"c_other"])),
+    ];
+    let members = common::create_members(&objects);
+
+    // Undefined symbols are resolved in the order they are referenced, so c.o
+    // is included for the reference to c_other from e.o, and c2.o and
+    // c_other.o aren't included as c.o already defines c_func and c_other.
+    let expected = MemberSelection {
+        selected: vec![
+            selected(0, "main.o", b"main", None),
+            selected(6, "e.o", b"e_func", None),
+            selected(1, "b.o", b"b_func", Some(0)),
+            selected(3, "c.o", b"c_other", Some(1)),
+        ],
+        unresolved: vec![b"undefined_root".to_vec(), b"missing".to_vec()],
+    };
+    let roots: [&[u8]; 4] = [b"main", b"e_func", b"undefined_root", b"main"];
+    assert_eq!(
+        ar_archive_writer::select_members(
+            &members,
+            ar_archive_writer::get_native_object_undefined_symbols,
+            &roots
+        )
+        .unwrap(),
+        expected
+    );
+
+    // The same members are selected from an archive.
+    let mut output_bytes = Cursor::new(Vec::new());
+    ar_archive_writer::write_archive_to_stream(
+        &mut output_bytes,
+        &members,
+        ArchiveKind::Gnu,
+        false,
+        false,
+    )
+    .unwrap();
+    let archive = output_bytes.into_inner();
+    assert_eq!(
+        ar_archive_writer::select_archive_members(
+            &archive,
+            &ar_archive_writer::DEFAULT_OBJECT_READER,
+            ar_archive_writer::get_native_object_undefined_symbols,
+            &roots
+        )
+        .unwrap(),
+        expected
+    );
+
+    let err = ar_archive_writer::select_archive_members(
+        &objects[0].1,
+        &ar_archive_writer::DEFAULT_OBJECT_READER,
+        ar_archive_writer::get_native_object_undefined_symbols,
+        &roots,
+    )
+    .unwrap_err();
+    assert_eq!(err.to_string(), "not an archive");
+}
+
+/// Tests that weak undefined ELF symbols don't cause members to be included,
+/// as linkers don't resolve them from archives.
+#[test]
+fn weak_undefined_symbols() {
+    let objects = [
+        (
+            "main.o",
+            create_object_with_weak_undefined(&[b"main"], &[b"strong"], &[b"weak"]),
+        ),
+        ("strong.o", create_object(&[b"strong"], &[])),
+        ("weak.o", create_object(&[b"weak"], &[])),
+    ];
+    let members = common::create_members(&objects);
+
+    let roots: [&[u8]; 1] = [b"main"];
+    assert_eq!(
+        ar_archive_writer::select_members(
+            &members,
+            ar_archive_writer::get_native_object_undefined_symbols,
+            &roots
+        )
+        .unwrap(),
+        MemberSelection {
+            selected: vec![
+                selected(0, "main.o", b"main", None),
+                selected(1, "strong.o", b"strong", Some(0)),
+            ],
+            unresolved: vec![],
+        }
+    );
+}
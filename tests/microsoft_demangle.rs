@@ -0,0 +1,97 @@
+use ar_archive_writer::microsoft_demangle;
+use pretty_assertions::assert_eq;
+
+// Expected output is from llvm-undname.
+const NAMES: &[(&str, &str)] = &[
+    ("?f@@YAXHD@Z", "void __cdecl f(int, char)"),
+    ("?f@@YAXHZZ", "void __cdecl f(int, ...)"),
+    ("?f@@YAXPEBH@Z", "void __cdecl f(int const *)"),
+    ("?f@@YAX$$QEAH@Z", "void __cdecl f(int &&)"),
+    ("?f@@YAXQEAY02H@Z", "void __cdecl f(int (*const)[3])"),
+    ("?f@@YAXAEAY01H@Z", "void __cdecl f(int (&)[2])"),
+    ("?f@@YAXP6AXH@Z@Z", "void __cdecl f(void (__cdecl *)(int))"),
+    (
+        "?f@@YAXP8A@@EAAXXZ@Z",
+        "void __cdecl f(void (__cdecl A::*)(void))",
+    ),
+    ("?f@@YAXPEQA@@H@Z", "void __cdecl f(int A::*)"),
+    ("?f@@YAX_N_W@Z", "void __cdecl f(bool, wchar_t)"),
+    ("?f@@YAX$$T@Z", "void __cdecl f(std::nullptr_t)"),
+    ("?f@@YAXW4E@@@Z", "void __cdecl f(enum E)"),
+    ("?x@@3PEBHEB", "int const *x"),
+    ("?x@A@@0HA", "private: static int A::x"),
+    (
+        "?f@A@@UEBAHXZ",
+        "public: virtual int __cdecl A::f(void) const",
+    ),
+    ("??0?$A@H@@QEAA@XZ", "public: __cdecl A<int>::A<int>(void)"),
+    ("??1A@@QEAA@XZ", "public: __cdecl A::~A(void)"),
+    ("??BA@@QEAAHXZ", "public: int __cdecl A::operator int(void)"),
+    (
+        "??4A@@QEAAAEAV0@AEBV0@@Z",
+        "public: class A & __cdecl A::operator=(class A const &)",
+    ),
+    ("??_7A@@6B@", "const A::`vftable'"),
+    ("?g@?1??f@@YAXXZ@4HA", "int `void __cdecl f(void)'::`2'::g"),
+    (
+        "?f@?A0x1234@@YAXXZ",
+        "void __cdecl `anonymous namespace'::f(void)",
+    ),
+    ("??$f@$0A@@@YAXXZ", "void __cdecl f<0>(void)"),
+    ("??$f@$1?x@@3HA@@YAXXZ", "void __cdecl f<&int x>(void)"),
+    (
+        "?f@@YAXV?$A@V?$B@H@@@@@Z",
+        "void __cdecl f(class A<class B<int>>)",
+    ),
+    (
+        "?push_back@?$vector@HV?$allocator@H@std@@@std@@QEAAX$$QEAH@Z",
+        "public: void __cdecl std::vector<int, class std::allocator<int>>::push_back(int &&)",
+    ),
+    (
+        "?f@C@@$4PPPPPPPM@A@EAAXXZ",
+        "[thunk]: public: virtual void __cdecl C::f`vtordisp{-4, 0}'(void)",
+    ),
+    // ARM64EC names demangle like the name without $$h.
+    ("?f@@$$hYAXXZ", "void __cdecl f(void)"),
+];
+
+#[test]
+fn demangle() {
+    for (mangled, demangled) in NAMES {
+        assert_eq!(
+            microsoft_demangle(mangled).as_deref(),
+            Some(*demangled),
+            "Mangled name: {mangled}"
+        );
+    }
+}
+
+#[test]
+fn invalid_names() {
+    for name in ["", "f", "_f@4", "?", "?f", "?f@@", "?f@@YAX", "?f@@YAXXZ@"] {
+        assert_eq!(microsoft_demangle(name), None, "Name: {name}");
+    }
+
+    // Truncated names must be rejected without panicking.
+    for (mangled, _) in NAMES {
+        for len in 0..mangled.len() {
+            microsoft_demangle(&mangled[..len]);
+        }
+    }
+}
+
+#[test]
+fn deeply_nested_names() {
+    // A function taking a pointer to a function returning a pointer to a
+    // function, and so on.
+    let nested = |depth: usize| format!("?f@@YAX{}X{}@Z", "P6A".repeat(depth), "XZ".repeat(depth));
+    assert!(microsoft_demangle(&nested(100)).is_some());
+
+    // Names that are nested too deeply are rejected rather than overflowing
+    // the stack.
+    assert_eq!(microsoft_demangle(&nested(100_000)), None);
+    let name = format!("?f@@YAX{}XXZ", "P6A".repeat(100_000));
+    assert_eq!(microsoft_demangle(&name), None);
+    let name = format!("??$f@{}@@YAXXZ", "V?$f@".repeat(100_000));
+    assert_eq!(microsoft_demangle(&name), None);
+}
@@ -0,0 +1,205 @@
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use ar_archive_writer::{ArchiveKind, NewArchiveMember};
+use pretty_assertions::assert_eq;
+
+mod common;
+
+/// Writes `file1.o` to `file5.o` to `tmpdir`, and the archives `lib.a` with
+/// `file4.o` and `file5.o` and `thin.a`, which is a thin archive with them.
+fn create_inputs(tmpdir: &Path) {
+    let objects = (1..=5)
+        .map(|i| common::create_elf_object(&[format!("func{i}").as_bytes()]))
+        .collect::<Vec<_>>();
+    for (i, object) in objects.iter().enumerate() {
+        fs::write(tmpdir.join(format!("file{}.o", i + 1)), object).unwrap();
+    }
+    for (name, thin) in [("lib.a", false), ("thin.a", true)] {
+        let members = [
+            NewArchiveMember::new(
+                objects[3].as_slice(),
+                &ar_archive_writer::DEFAULT_OBJECT_READER,
+                "file4.o".to_string(),
+            ),
+            NewArchiveMember::new(
+                objects[4].as_slice(),
+                &ar_archive_writer::DEFAULT_OBJECT_READER,
+                "file5.o".to_string(),
+            ),
+        ];
+        let mut output_bytes = Cursor::new(Vec::new());
+        ar_archive_writer::write_archive_to_stream(
+            &mut output_bytes,
+            &members,
+            ArchiveKind::Gnu,
+            thin,
+            false,
+        )
+        .unwrap();
+        fs::write(tmpdir.join(name), output_bytes.into_inner()).unwrap();
+    }
+}
+
+/// Runs `script` with `llvm-ar -M`.
+fn run_llvm_ar(script: &str) {
+    let mut child = Command::new(cargo_binutils::Tool::Ar.path().unwrap())
+        .arg("-M")
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(script.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+    assert!(output.status.success());
+}
+
+/// Tests that running MRI scripts produces the same archives as `llvm-ar -M`.
+#[test]
+fn matches_llvm_ar() {
+    let tmpdir = common::create_tmp_dir("mri_script_matches_llvm_ar");
+    create_inputs(&tmpdir);
+    // Use relative paths, which are relative to the archive in thin archives.
+    // The temporary directory may be anywhere, such as in a `CARGO_TARGET_DIR`
+    // outside of the crate, so find its path relative to the current directory.
+    let cwd = std::env::current_dir().unwrap();
+    let dir =
+        ar_archive_writer::compute_archive_relative_path(&cwd.join("script"), &tmpdir).unwrap();
+
+    for (create, lib) in [("create", "lib.a"), ("CREATETHIN", "thin.a")] {
+        let script = |archive_name: &str| {
+            format!(
+                "* Comments and blank lines are ignored.\n\
+                 {create} {dir}/{archive_name}\n\
+                 \n\
+                 addmod {dir}/file1.o ; Comments can follow commands.\n\
+                 ADDMOD \"{dir}/file2.o\"\n\
+                 ; Add the members of a library.\n\
+                 AddLib {dir}/{lib}\n\
+                 delete file2.o\n\
+                 addmod {dir}/file3.o\n\
+                 save * with either comment character\n\
+                 end\n"
+            )
+        };
+        ar_archive_writer::run_mri_script(
+            &script("ours.a"),
+            ArchiveKind::Gnu,
+            &ar_archive_writer::DEFAULT_OBJECT_READER,
+        )
+        .unwrap();
+        run_llvm_ar(&script("llvm.a"));
+        assert_eq!(
+            fs::read(tmpdir.join("ours.a")).unwrap(),
+            fs::read(tmpdir.join("llvm.a")).unwrap(),
+            "{create} with {lib}"
+        );
+    }
+}
+
+#[test]
+fn replace() {
+    let tmpdir = common::create_tmp_dir("mri_script_replace");
+    create_inputs(&tmpdir);
+    fs::create_dir_all(tmpdir.join("new")).unwrap();
+    let new_object = common::create_elf_object(&[b"new_func"]);
+    fs::write(tmpdir.join("new/file1.o"), &new_object).unwrap();
+    let dir = tmpdir.to_str().unwrap();
+
+    let archive = ar_archive_writer::execute_mri_script(
+        &format!(
+            "create {dir}/out.a\n\
+             addmod {dir}/file1.o\n\
+             addmod {dir}/file2.o\n\
+             replace {dir}/new/file1.o\n\
+             save\n"
+        ),
+        &ar_archive_writer::DEFAULT_OBJECT_READER,
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(archive.archive_path, tmpdir.join("out.a"));
+    assert!(!archive.thin);
+    let members = archive
+        .members
+        .iter()
+        .map(|member| (member.member_name.as_str(), (*member.buf).as_ref().to_vec()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        members,
+        [
+            ("file1.o", new_object),
+            ("file2.o", fs::read(tmpdir.join("file2.o")).unwrap()),
+        ]
+    );
+
+    // Nothing is written if the archive isn't saved.
+    let script = format!("create {dir}/unsaved.a\naddmod {dir}/file1.o\nend\nsave\n");
+    assert!(ar_archive_writer::execute_mri_script(
+        &script,
+        &ar_archive_writer::DEFAULT_OBJECT_READER
+    )
+    .unwrap()
+    .is_none());
+    ar_archive_writer::run_mri_script(
+        &script,
+        ArchiveKind::Gnu,
+        &ar_archive_writer::DEFAULT_OBJECT_READER,
+    )
+    .unwrap();
+    assert!(!tmpdir.join("unsaved.a").exists());
+}
+
+#[test]
+fn script_errors() {
+    let tmpdir = common::create_tmp_dir("mri_script_script_errors");
+    create_inputs(&tmpdir);
+    let dir = tmpdir.to_str().unwrap();
+
+    for (script, expected) in [
+        (
+            format!("addmod {dir}/file1.o"),
+            "script line 1: no output archive has been opened".to_string(),
+        ),
+        (
+            format!("create {dir}/out.a\nextract file1.o"),
+            "script line 2: unknown command: extract".to_string(),
+        ),
+        (
+            format!("create {dir}/out.a\n\ncreate {dir}/other.a"),
+            "script line 3: editing multiple archives not supported".to_string(),
+        ),
+        (
+            "create".to_string(),
+            "script line 1: missing archive name".to_string(),
+        ),
+        (
+            format!("create {dir}/out.a\nreplace {dir}/file1.o"),
+            "script line 2: no member named file1.o in archive".to_string(),
+        ),
+        (
+            format!("create {dir}/out.a\naddlib {dir}/file1.o"),
+            format!("script line 2: {dir}/file1.o: not an archive"),
+        ),
+        (
+            format!("createthin {dir}/out.a\naddlib {dir}/lib.a"),
+            "script line 2: Cannot convert a regular archive to a thin one".to_string(),
+        ),
+    ] {
+        let err = ar_archive_writer::execute_mri_script(
+            &script,
+            &ar_archive_writer::DEFAULT_OBJECT_READER,
+        )
+        .map(|_| ())
+        .unwrap_err();
+        assert_eq!(err.to_string(), expected);
+    }
+}
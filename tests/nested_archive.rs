@@ -0,0 +1,210 @@
+use std::fs;
+use std::io::Cursor;
+use std::process::Command;
+
+use ar_archive_writer::{ArchiveKind, NewArchiveMember};
+use pretty_assertions::assert_eq;
+
+mod common;
+
+fn write_archive(members: &[NewArchiveMember<'_>], kind: ArchiveKind, thin: bool) -> Vec<u8> {
+    let mut output_bytes = Cursor::new(Vec::new());
+    ar_archive_writer::write_archive_to_stream(&mut output_bytes, members, kind, thin, false)
+        .unwrap();
+    output_bytes.into_inner()
+}
+
+fn new_member<'a>(buf: &'a [u8], name: &str) -> NewArchiveMember<'a> {
+    NewArchiveMember::new(
+        buf,
+        &ar_archive_writer::DEFAULT_OBJECT_READER,
+        name.to_string(),
+    )
+}
+
+/// Tests that flattening archive members produces the same archive as
+/// `llvm-ar q` with the `L` modifier.
+#[test]
+fn flatten_matches_llvm_ar() {
+    let tmpdir = common::create_tmp_dir("nested_archive_flatten_matches_llvm_ar");
+    let object1 = common::create_elf_object(&[b"func1"]);
+    let object2 = common::create_elf_object(&[b"func2"]);
+    let object3 = common::create_elf_object(&[b"func3"]);
+    fs::write(tmpdir.join("file1.o"), &object1).unwrap();
+
+    for inner_kind in [ArchiveKind::Gnu, ArchiveKind::Bsd, ArchiveKind::Coff] {
+        let inner = write_archive(
+            &[
+                new_member(&object2, "file2.o"),
+                new_member(&object3, "long_file_name3.o"),
+            ],
+            inner_kind,
+            false,
+        );
+        fs::write(tmpdir.join("inner.a"), &inner).unwrap();
+
+        let members = ar_archive_writer::flatten_archive_members(vec![
+            new_member(&object1, "file1.o"),
+            new_member(&inner, "inner.a"),
+        ])
+        .unwrap();
+        let archive = write_archive(&members, ArchiveKind::Gnu, false);
+
+        let outer_path = tmpdir.join("outer.a");
+        let _ = fs::remove_file(&outer_path);
+        let output = Command::new(cargo_binutils::Tool::Ar.path().unwrap())
+            .current_dir(&tmpdir)
+            .args(["qcsL", "--format=gnu", "outer.a", "file1.o", "inner.a"])
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+        assert!(output.status.success());
+        assert_eq!(
+            archive,
+            fs::read(&outer_path).unwrap(),
+            "inner archive kind: {inner_kind:?}"
+        );
+        assert_eq!(
+            common::get_archive_symbols(&archive),
+            ["func1", "func2", "func3"]
+        );
+    }
+}
+
+/// Tests that the EC symbol table of an ARM64EC archive isn't flattened into a
+/// member.
+#[test]
+fn flatten_arm64ec_archive() {
+    let exports = [ar_archive_writer::COFFShortExport {
+        name: "Func".to_string(),
+        ext_name: None,
+        symbol_name: None,
+        alias_target: None,
+        ordinal: 0,
+        noname: false,
+        data: false,
+        private: false,
+        constant: false,
+    }];
+    let mut import_library = Cursor::new(Vec::new());
+    ar_archive_writer::write_import_library(
+        &mut import_library,
+        "MyLibrary.dll",
+        &exports,
+        ar_archive_writer::MachineTypes::ARM64EC,
+        false,
+    )
+    .unwrap();
+    let import_library = import_library.into_inner();
+
+    let members =
+        ar_archive_writer::flatten_archive_members(vec![new_member(&import_library, "inner.lib")])
+            .unwrap();
+    let member_names = members
+        .iter()
+        .map(|member| member.member_name.as_str())
+        .collect::<Vec<_>>();
+    assert!(!member_names.contains(&"/<ECSYMBOLS>/"));
+
+    let mut archive = Cursor::new(Vec::new());
+    ar_archive_writer::write_archive_to_stream(
+        &mut archive,
+        &members,
+        ArchiveKind::Coff,
+        false,
+        true,
+    )
+    .unwrap();
+    assert_eq!(archive.into_inner(), import_library);
+}
+
+/// Tests that nested archives are flattened recursively and keep the metadata
+/// of their members.
+#[test]
+fn flatten_recursively() {
+    let object1 = common::create_elf_object(&[b"func1"]);
+    let object2 = common::create_elf_object(&[b"func2"]);
+    let mut member1 = new_member(&object1, "file1.o");
+    member1.mtime = 1234;
+    member1.uid = 56;
+    member1.gid = 78;
+    member1.perms = 0o600;
+    let innermost = write_archive(&[member1], ArchiveKind::Bsd, false);
+    let inner = write_archive(
+        &[
+            new_member(&innermost, "innermost.a"),
+            new_member(&object2, "file2.o"),
+        ],
+        ArchiveKind::Gnu,
+        false,
+    );
+
+    let members =
+        ar_archive_writer::flatten_archive_members(vec![new_member(&inner, "inner.a")]).unwrap();
+    let summary = members
+        .iter()
+        .map(|member| {
+            (
+                member.member_name.as_str(),
+                (*member.buf).as_ref().to_vec(),
+                member.mtime,
+                member.uid,
+                member.gid,
+                member.perms,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        [
+            ("file1.o", object1.clone(), 1234, 56, 78, 0o600),
+            ("file2.o", object2.clone(), 0, 0, 0, 0o644),
+        ]
+    );
+    let archive = write_archive(&members, ArchiveKind::Gnu, false);
+    assert_eq!(common::get_archive_symbols(&archive), ["func1", "func2"]);
+
+    // Members that aren't archives are unchanged.
+    let members =
+        ar_archive_writer::flatten_archive_members(vec![new_member(&object1, "file1.o")]).unwrap();
+    assert_eq!(members.len(), 1);
+    assert_eq!((*members[0].buf).as_ref(), object1);
+}
+
+/// Tests flattening thin archives, whose members are read from disk.
+#[test]
+fn flatten_thin_archive() {
+    let tmpdir = common::create_tmp_dir("nested_archive_flatten_thin_archive");
+    let object1 = common::create_elf_object(&[b"func1"]);
+    fs::write(tmpdir.join("file1.o"), &object1).unwrap();
+    let thin = write_archive(&[new_member(&object1, "file1.o")], ArchiveKind::Gnu, true);
+    let thin_path = tmpdir.join("thin.a");
+    fs::write(&thin_path, &thin).unwrap();
+
+    let members = ar_archive_writer::flatten_archive_members(vec![new_member(
+        &thin,
+        thin_path.to_str().unwrap(),
+    )])
+    .unwrap();
+    assert_eq!(members.len(), 1);
+    assert_eq!(
+        members[0].member_name,
+        tmpdir.join("file1.o").to_str().unwrap()
+    );
+    assert_eq!((*members[0].buf).as_ref(), object1);
+
+    // A thin archive that contains itself can't be flattened.
+    let cycle = write_archive(&[new_member(&thin, "cycle.a")], ArchiveKind::Gnu, true);
+    let cycle_path = tmpdir.join("cycle.a");
+    fs::write(&cycle_path, &cycle).unwrap();
+    let err = ar_archive_writer::flatten_archive_members(vec![new_member(
+        &cycle,
+        cycle_path.to_str().unwrap(),
+    )])
+    .map(|_| ())
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("thin archive {} contains itself", cycle_path.display())
+    );
+}
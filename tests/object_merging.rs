@@ -0,0 +1,277 @@
+use ar_archive_writer::ArchiveKind;
+use object::{
+    elf, write, Architecture, BinaryFormat, Endianness, Object, ObjectSection, ObjectSymbol,
+    RelocationEncoding, RelocationFlags, RelocationKind, RelocationTarget, SectionFlags,
+    SectionKind, SymbolFlags, SymbolKind, SymbolScope,
+};
+use pretty_assertions::assert_eq;
+
+mod common;
+
+fn new_elf_object() -> write::Object<'static> {
+    write::Object::new(BinaryFormat::Elf, Architecture::X86_64, Endianness::Little)
+}
+
+fn add_symbol(
+    object: &mut write::Object<'_>,
+    name: &[u8],
+    section: Option<write::SectionId>,
+    weak: bool,
+) -> write::SymbolId {
+    let section = match section {
+        Some(section) => write::SymbolSection::Section(section),
+        None => write::SymbolSection::Undefined,
+    };
+    object.add_symbol(write::Symbol {
+        name: name.to_vec(),
+        value: 0,
+        size: 0,
+        kind: SymbolKind::Text,
+        scope: SymbolScope::Dynamic,
+        weak,
+        section,
+        flags: SymbolFlags::None,
+    })
+}
+
+/// Adds a data section with absolute relocations to the symbols.
+fn add_data_with_relocations(object: &mut write::Object<'_>, symbols: &[write::SymbolId]) {
+    let data = object.section_id(write::StandardSection::Data);
+    object.append_section_data(data, &vec![0; symbols.len() * 4], 4);
+    for (i, &symbol) in symbols.iter().enumerate() {
+        object
+            .add_relocation(
+                data,
+                write::Relocation {
+                    offset: i as u64 * 4,
+                    symbol,
+                    addend: 0,
+                    flags: RelocationFlags::Generic {
+                        kind: RelocationKind::Absolute,
+                        encoding: RelocationEncoding::Generic,
+                        size: 32,
+                    },
+                },
+            )
+            .unwrap();
+    }
+}
+
+/// Adds a COMDAT group that defines a weak function.
+fn add_comdat_function(object: &mut write::Object<'_>, name: &[u8], code: u8) {
+    let section = object.add_section(
+        Vec::new(),
+        [&b".text."[..], name].concat(),
+        SectionKind::Text,
+    );
+    object.append_section_data(section, &[code; 16], 16);
+    object.section_mut(section).flags = SectionFlags::Elf {
+        sh_flags: u64::from(elf::SHF_ALLOC | elf::SHF_EXECINSTR | elf::SHF_GROUP),
+    };
+    let symbol = add_symbol(object, name, Some(section), true);
+    object.add_comdat(write::Comdat {
+        kind: object::ComdatKind::Any,
+        symbol,
+        sections: vec![section],
+    });
+}
+
+fn add_common_symbol(object: &mut write::Object<'_>, name: &[u8], size: u64, align: u64) {
+    object.add_common_symbol(
+        write::Symbol {
+            name: name.to_vec(),
+            value: 0,
+            size: 0,
+            kind: SymbolKind::Data,
+            scope: SymbolScope::Dynamic,
+            weak: false,
+            section: write::SymbolSection::Undefined,
+            flags: SymbolFlags::None,
+        },
+        size,
+        align,
+    );
+}
+
+fn create_objects() -> Vec<(&'static str, Vec<u8>)> {
+    let mut a = new_elf_object();
+    common::add_file_with_functions_to_object(&mut a, b"a.c", &[b"func1"]);
+    let func1 = a.symbol_id(b"func1").unwrap();
+    let shared = add_symbol(&mut a, b"shared", None, false);
+    add_data_with_relocations(&mut a, &[func1, shared]);
+    add_comdat_function(&mut a, b"inline_fn", 1);
+    add_common_symbol(&mut a, b"common_var", 4, 4);
+
+    let mut b = new_elf_object();
+    common::add_file_with_functions_to_object(&mut b, b"b.c", &[b"shared"]);
+    let text = b.section_id(write::StandardSection::Text);
+    add_symbol(&mut b, b"weak_fn", Some(text), true);
+    let func1 = add_symbol(&mut b, b"func1", None, false);
+    add_comdat_function(&mut b, b"inline_fn", 2);
+    let inline_fn = b.symbol_id(b"inline_fn").unwrap();
+    add_data_with_relocations(&mut b, &[func1, inline_fn]);
+    add_common_symbol(&mut b, b"common_var", 8, 8);
+
+    let mut c = new_elf_object();
+    common::add_file_with_functions_to_object(&mut c, b"c.c", &[b"weak_fn"]);
+    let inline_fn = add_symbol(&mut c, b"inline_fn", None, false);
+    let undefined = add_symbol(&mut c, b"undefined", None, true);
+    add_data_with_relocations(&mut c, &[inline_fn, undefined]);
+
+    vec![
+        ("a.o", a.write().unwrap()),
+        ("b.o", b.write().unwrap()),
+        ("c.o", c.write().unwrap()),
+    ]
+}
+
+/// Tests that the members are merged into a single object, with the global
+/// symbols resolved, the duplicate COMDAT group discarded, and the relocations
+/// referring to the merged symbols.
+#[test]
+fn merge_archive_members() {
+    let mut objects = create_objects();
+    objects.insert(1, ("data.txt", b"not an object".to_vec()));
+    let members = common::create_members(&objects);
+    let members = ar_archive_writer::merge_archive_members(members, "merged.o").unwrap();
+    let member_names = members
+        .iter()
+        .map(|member| member.member_name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(member_names, ["merged.o", "data.txt"]);
+    assert_eq!((*members[1].buf).as_ref(), b"not an object");
+
+    let merged = (*members[0].buf).as_ref();
+    let text = [1; 30]
+        .into_iter()
+        .chain([0; 2])
+        .chain([1; 30])
+        .collect::<Vec<u8>>();
+    let text = format!("{:?}", text);
+    let expected = [
+        format!("section .text Text {text}"),
+        "section .data Data [0, 0, 0, 0, 0, 0, 0, 0]".to_string(),
+        "  relocation 0 func1 Elf { r_type: 10 }".to_string(),
+        "  relocation 4 shared Elf { r_type: 10 }".to_string(),
+        format!("section .text.inline_fn Text {:?}", [1; 16]),
+        format!("section .text Text {text}"),
+        "section .data Data [0, 0, 0, 0, 0, 0, 0, 0]".to_string(),
+        "  relocation 0 func1 Elf { r_type: 10 }".to_string(),
+        "  relocation 4 inline_fn Elf { r_type: 10 }".to_string(),
+        format!("section .text Text {text}"),
+        "section .data Data [0, 0, 0, 0, 0, 0, 0, 0]".to_string(),
+        "  relocation 0 inline_fn Elf { r_type: 10 }".to_string(),
+        "  relocation 4 undefined Elf { r_type: 10 }".to_string(),
+        "comdat inline_fn .text.inline_fn".to_string(),
+        "symbol func1 Text .text 32 32 Linkage weak: false".to_string(),
+        "symbol shared Text .text 32 32 Linkage weak: false".to_string(),
+        "symbol inline_fn Text .text.inline_fn 0 0 Dynamic weak: true".to_string(),
+        "symbol common_var Data Common 0 8 Dynamic weak: false".to_string(),
+        "symbol weak_fn Text .text 32 32 Linkage weak: false".to_string(),
+        "symbol undefined Unknown Undefined 0 0 Unknown weak: true".to_string(),
+    ];
+
+    assert_eq!(common::describe_object(merged), expected);
+
+    assert_eq!(
+        common::get_archive_symbols(&common::write_archive(&members, ArchiveKind::Gnu)),
+        ["func1", "shared", "inline_fn", "common_var", "weak_fn"]
+    );
+}
+
+/// Tests that a symbol that is defined by more than one member is an error.
+#[test]
+fn merge_duplicate_definitions() {
+    let objects = ["a.o", "b.o"].map(|name| {
+        let mut object = new_elf_object();
+        common::add_file_with_functions_to_object(&mut object, b"file.c", &[b"func1"]);
+        (name, object.write().unwrap())
+    });
+    let members = common::create_members(&objects);
+    let err = match ar_archive_writer::merge_archive_members(members, "merged.o") {
+        Ok(_) => panic!("merging duplicate definitions should fail"),
+        Err(err) => err,
+    };
+    assert_eq!(
+        err.to_string(),
+        "symbol func1 is defined by both a.o and b.o"
+    );
+}
+
+/// Tests that the relocations of `.eh_frame` that refer to the section symbol
+/// of a discarded duplicate COMDAT group are replaced by `R_X86_64_NONE`
+/// relocations, as `ld -r` does.
+#[test]
+fn merge_comdat_with_eh_frame() {
+    let objects = [("a.o", 1), ("b.o", 2)].map(|(name, code)| {
+        let mut object = new_elf_object();
+        common::add_file_with_functions_to_object(&mut object, b"file.c", &[name.as_bytes()]);
+        add_comdat_function(&mut object, b"inline_fn", code);
+        let text = object.section_id(write::StandardSection::Text);
+        let inline_fn = object.symbol_id(b"inline_fn").unwrap();
+        let write::SymbolSection::Section(comdat_section) = object.symbol(inline_fn).section else {
+            panic!("inline_fn must be defined");
+        };
+        let eh_frame =
+            object.add_section(Vec::new(), b".eh_frame".to_vec(), SectionKind::ReadOnlyData);
+        object.append_section_data(eh_frame, &[0; 8], 8);
+        for (offset, section) in [(0, text), (4, comdat_section)] {
+            let symbol = object.section_symbol(section);
+            object
+                .add_relocation(
+                    eh_frame,
+                    write::Relocation {
+                        offset,
+                        symbol,
+                        addend: 4,
+                        flags: RelocationFlags::Elf {
+                            r_type: elf::R_X86_64_PC32,
+                        },
+                    },
+                )
+                .unwrap();
+        }
+        (name, object.write().unwrap())
+    });
+    let members = common::create_members(&objects);
+    let members = ar_archive_writer::merge_archive_members(members, "merged.o").unwrap();
+
+    let file = object::File::parse((*members[0].buf).as_ref()).unwrap();
+    let relocations = file
+        .sections()
+        .filter(|section| section.name() == Ok(".eh_frame"))
+        .flat_map(|section| section.relocations())
+        .map(|(offset, relocation)| {
+            let target = match relocation.target() {
+                RelocationTarget::Symbol(index) => {
+                    let symbol = file.symbol_by_index(index).unwrap();
+                    let section = symbol.section_index().unwrap();
+                    file.section_by_index(section).unwrap().name()
+                }
+                RelocationTarget::Absolute => Ok("absolute"),
+                _ => panic!("unexpected relocation target"),
+            };
+            (
+                offset,
+                target.unwrap().to_string(),
+                relocation.flags(),
+                relocation.addend(),
+            )
+        })
+        .collect::<Vec<_>>();
+    let pc32 = RelocationFlags::Elf {
+        r_type: elf::R_X86_64_PC32,
+    };
+    let none = RelocationFlags::Elf {
+        r_type: elf::R_X86_64_NONE,
+    };
+    assert_eq!(
+        relocations,
+        [
+            (0, ".text".to_string(), pc32, 4),
+            (4, ".text.inline_fn".to_string(), pc32, 4),
+            (0, ".text".to_string(), pc32, 4),
+            (4, "absolute".to_string(), none, 0),
+        ]
+    );
+}
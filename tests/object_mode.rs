@@ -0,0 +1,61 @@
+use ar_archive_writer::{NewArchiveMember, ObjectMode};
+use object::{Architecture, BinaryFormat};
+use pretty_assertions::assert_eq;
+
+mod common;
+
+#[test]
+fn object_mode() {
+    let object32 = common::create_object(BinaryFormat::Xcoff, Architecture::PowerPc, &[b"func"]);
+    let object64 = common::create_object(BinaryFormat::Xcoff, Architecture::PowerPc64, &[b"func"]);
+    let create_members = || {
+        common::create_members(&[
+            ("file32.o", object32.as_slice()),
+            ("file64.o", object64.as_slice()),
+            ("notes.txt", b"not an object".as_slice()),
+        ])
+    };
+    let get_names = |members: &[NewArchiveMember<'_>]| {
+        members
+            .iter()
+            .map(|m| m.member_name.clone())
+            .collect::<Vec<_>>()
+    };
+
+    for (mode, valid_names, invalid_names) in [
+        (
+            ObjectMode::Bit32,
+            &["file32.o", "notes.txt"][..],
+            &["file64.o"][..],
+        ),
+        (ObjectMode::Bit64, &["file64.o", "notes.txt"], &["file32.o"]),
+        (
+            ObjectMode::Bit32_64,
+            &["file32.o", "file64.o", "notes.txt"],
+            &[],
+        ),
+        (ObjectMode::Any, &["file32.o", "file64.o", "notes.txt"], &[]),
+    ] {
+        let (valid, invalid) =
+            ar_archive_writer::filter_by_object_mode(create_members(), mode).unwrap();
+        assert_eq!(get_names(&valid), valid_names, "{mode}");
+        assert_eq!(get_names(&invalid), invalid_names, "{mode}");
+
+        let result = ar_archive_writer::check_object_mode(&create_members(), mode);
+        if invalid_names.is_empty() {
+            result.unwrap();
+        } else {
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                format!(
+                    "archive members are not valid with the object mode -X{mode}: {}",
+                    invalid_names.join(", ")
+                )
+            );
+        }
+        assert_eq!(mode.to_string().parse::<ObjectMode>().unwrap(), mode);
+    }
+
+    let err = "32-64".parse::<ObjectMode>().unwrap_err();
+    assert_eq!(err.to_string(), "invalid object mode: 32-64");
+}
@@ -0,0 +1,265 @@
+use std::io::Cursor;
+
+use ar_archive_writer::ArchiveKind;
+use object::read::archive::ArchiveFile;
+use object::{
+    write, Architecture, BinaryFormat, Endianness, Object, ObjectComdat, ObjectSection,
+    ObjectSymbol, RelocationEncoding, RelocationFlags, RelocationKind, RelocationTarget,
+};
+use pretty_assertions::assert_eq;
+
+mod common;
+
+/// Creates an object that defines `func1` and `func2`, and relocates against
+/// `func2` and the undefined `puts`.
+fn create_object(binary_format: BinaryFormat) -> Vec<u8> {
+    let mut object = write::Object::new(binary_format, Architecture::X86_64, Endianness::Little);
+    common::add_file_with_functions_to_object(&mut object, b"file.c", &[b"func1", b"func2"]);
+    let data = object.section_id(write::StandardSection::Data);
+    object.append_section_data(data, &[0; 16], 8);
+    for (offset, name) in [(0, &b"func2"[..]), (8, b"puts")] {
+        let symbol = object.symbol_id(name).unwrap_or_else(|| {
+            object.add_symbol(write::Symbol {
+                name: name.to_vec(),
+                value: 0,
+                size: 0,
+                kind: object::SymbolKind::Text,
+                scope: object::SymbolScope::Dynamic,
+                weak: false,
+                section: write::SymbolSection::Undefined,
+                flags: object::SymbolFlags::None,
+            })
+        });
+        object
+            .add_relocation(
+                data,
+                write::Relocation {
+                    offset,
+                    symbol,
+                    addend: 0,
+                    flags: RelocationFlags::Generic {
+                        kind: RelocationKind::Absolute,
+                        encoding: RelocationEncoding::Generic,
+                        size: 64,
+                    },
+                },
+            )
+            .unwrap();
+    }
+    if binary_format != BinaryFormat::MachO {
+        let section = object.add_subsection(write::StandardSection::Text, b"func3");
+        let offset = object.append_section_data(section, &[1; 16], 4);
+        // COFF COMDAT sections need a section symbol.
+        object.section_symbol(section);
+        let symbol = object.add_symbol(write::Symbol {
+            name: b"func3".to_vec(),
+            value: offset,
+            size: 16,
+            kind: object::SymbolKind::Text,
+            scope: object::SymbolScope::Linkage,
+            weak: false,
+            section: write::SymbolSection::Section(section),
+            flags: object::SymbolFlags::None,
+        });
+        object.add_comdat(write::Comdat {
+            kind: object::ComdatKind::Any,
+            symbol,
+            sections: vec![section],
+        });
+    }
+    object.write().unwrap()
+}
+
+/// Returns the names of the global and local symbols of an object.
+fn get_symbols(file: &object::File<'_>) -> (Vec<String>, Vec<String>) {
+    let mut globals = Vec::new();
+    let mut locals = Vec::new();
+    for symbol in file.symbols() {
+        let name = symbol.name().unwrap().to_string();
+        if symbol.kind() == object::SymbolKind::Section || name.is_empty() || name == "file.c" {
+            continue;
+        }
+        if symbol.is_global() {
+            globals.push(name);
+        } else {
+            locals.push(name);
+        }
+    }
+    globals.sort();
+    locals.sort();
+    (globals, locals)
+}
+
+/// Returns the offsets of the relocations in the data section and the names
+/// of their symbols.
+fn get_relocations(file: &object::File<'_>) -> Vec<(u64, String)> {
+    let section = file
+        .sections()
+        .find(|section| section.kind() == object::SectionKind::Data)
+        .unwrap();
+    let mut relocations = section
+        .relocations()
+        .map(|(offset, relocation)| {
+            let RelocationTarget::Symbol(index) = relocation.target() else {
+                panic!("unexpected relocation target");
+            };
+            let symbol = file.symbol_by_index(index).unwrap();
+            (offset, symbol.name().unwrap().to_string())
+        })
+        .collect::<Vec<_>>();
+    relocations.sort();
+    relocations
+}
+
+/// Tests that the definitions that aren't exported are made local, that the
+/// relocations and COMDATs still refer to the same symbols, and that the
+/// archive symbol table only has the exported symbols.
+#[test]
+fn localize_archive_symbols() {
+    for binary_format in [BinaryFormat::Elf, BinaryFormat::MachO, BinaryFormat::Coff] {
+        let message = format!("binary_format: {binary_format:?}");
+        // Mach-O symbols have a leading underscore.
+        let mangle = |name: &str| match binary_format {
+            BinaryFormat::MachO => format!("_{name}"),
+            _ => name.to_string(),
+        };
+        let object = create_object(binary_format);
+        let members =
+            common::create_members(&[("a.o", &object[..]), ("data.txt", b"not an object")]);
+        let exported = mangle("func1");
+        let members =
+            ar_archive_writer::localize_archive_symbols(members, &[exported.as_bytes()]).unwrap();
+
+        let original = object::File::parse(object.as_slice()).unwrap();
+        let localized = object::File::parse((*members[0].buf).as_ref()).unwrap();
+        let mut expected_locals = vec![mangle("func2")];
+        if binary_format != BinaryFormat::MachO {
+            expected_locals.push(mangle("func3"));
+        }
+        assert_eq!(
+            get_symbols(&localized),
+            (vec![mangle("func1"), mangle("puts")], expected_locals),
+            "{message}"
+        );
+        assert_eq!(
+            get_relocations(&localized),
+            get_relocations(&original),
+            "{message}"
+        );
+        let comdat_symbols = |file: &object::File<'_>| {
+            file.comdats()
+                .map(|comdat| comdat.name().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            comdat_symbols(&localized),
+            comdat_symbols(&original),
+            "{message}"
+        );
+        assert_eq!((*members[1].buf).as_ref(), b"not an object", "{message}");
+
+        let kind = match binary_format {
+            BinaryFormat::Coff => ArchiveKind::Coff,
+            BinaryFormat::MachO => ArchiveKind::Darwin,
+            _ => ArchiveKind::Gnu,
+        };
+        assert_eq!(
+            common::get_archive_symbols(&common::write_archive(&members, kind)),
+            [mangle("func1")],
+            "{message}"
+        );
+    }
+}
+
+#[test]
+fn localize_common_symbol() {
+    let mut object =
+        write::Object::new(BinaryFormat::Elf, Architecture::X86_64, Endianness::Little);
+    object.add_common_symbol(
+        write::Symbol {
+            name: b"common".to_vec(),
+            value: 0,
+            size: 0,
+            kind: object::SymbolKind::Data,
+            scope: object::SymbolScope::Linkage,
+            weak: false,
+            section: write::SymbolSection::Undefined,
+            flags: object::SymbolFlags::None,
+        },
+        8,
+        8,
+    );
+    let members = common::create_members(&[("common.o", object.write().unwrap())]);
+    let Err(err) = ar_archive_writer::localize_archive_symbols(members, &[]) else {
+        panic!("common symbol was made local");
+    };
+    assert_eq!(
+        err.to_string(),
+        "common.o: common symbol common can't be made local"
+    );
+}
+
+/// Tests that short import members are kept unchanged if their symbols are
+/// exported and rejected otherwise, and that bitcode members are rejected.
+#[test]
+fn localize_short_imports_and_bitcode() {
+    let exports = [ar_archive_writer::COFFShortExport {
+        name: "ImportedFunc".to_string(),
+        ext_name: None,
+        symbol_name: None,
+        alias_target: None,
+        ordinal: 0,
+        noname: false,
+        data: false,
+        private: false,
+        constant: false,
+    }];
+    let mut import_library = Cursor::new(Vec::new());
+    ar_archive_writer::write_import_library(
+        &mut import_library,
+        "MyLibrary.dll",
+        &exports,
+        ar_archive_writer::MachineTypes::AMD64,
+        false,
+    )
+    .unwrap();
+    let import_library = import_library.into_inner();
+    let archive = ArchiveFile::parse(import_library.as_slice()).unwrap();
+    let short_import = archive
+        .members()
+        .map(|member| member.unwrap().data(import_library.as_slice()).unwrap())
+        .find(|data| object::FileKind::parse(*data) == Ok(object::FileKind::CoffImport))
+        .unwrap()
+        .to_vec();
+    let members = |bytes: &[u8], name: &str| common::create_members(&[(name, bytes)]);
+
+    let localized = ar_archive_writer::localize_archive_symbols(
+        members(&short_import, "MyLibrary.dll"),
+        &[b"__imp_ImportedFunc", b"ImportedFunc"],
+    )
+    .unwrap();
+    assert_eq!((*localized[0].buf).as_ref(), short_import);
+
+    let err = ar_archive_writer::localize_archive_symbols(
+        members(&short_import, "MyLibrary.dll"),
+        &[b"__imp_ImportedFunc"],
+    )
+    .err()
+    .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "MyLibrary.dll: short import symbol ImportedFunc can't be made local"
+    );
+
+    let err = ar_archive_writer::localize_archive_symbols(
+        members(b"BC\xc0\xde\x35\x14\x00\x00", "bitcode.o"),
+        &[],
+    )
+    .err()
+    .unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+    assert_eq!(
+        err.to_string(),
+        "bitcode.o: localizing symbols is not supported for LLVM bitcode files"
+    );
+}
@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use ar_archive_writer::{ArchiveKind, NewArchiveMember};
+use object::read::archive::ArchiveFile;
+use object::{write, Architecture, BinaryFormat, Endianness, Object, ObjectSymbol};
+use pretty_assertions::assert_eq;
+
+mod common;
+
+/// Creates an object that defines `func_name` and references `references`.
+fn create_object(binary_format: BinaryFormat, func_name: &[u8], references: &[&[u8]]) -> Vec<u8> {
+    let mut object = write::Object::new(binary_format, Architecture::X86_64, Endianness::Little);
+    common::add_file_with_functions_to_object(&mut object, b"file.c", &[func_name]);
+    for reference in references {
+        object.add_symbol(write::Symbol {
+            name: reference.to_vec(),
+            value: 0,
+            size: 0,
+            kind: object::SymbolKind::Text,
+            scope: object::SymbolScope::Dynamic,
+            weak: false,
+            section: write::SymbolSection::Undefined,
+            flags: object::SymbolFlags::None,
+        });
+    }
+    object.write().unwrap()
+}
+
+fn create_test_members(binary_format: BinaryFormat) -> Vec<NewArchiveMember<'static>> {
+    common::create_members(&[
+        (
+            "a.o",
+            create_object(binary_format, b"func1", &[b"func2", b"puts"]),
+        ),
+        ("b.o", create_object(binary_format, b"func2", &[])),
+        ("data.txt", b"not an object".to_vec()),
+    ])
+}
+
+/// Returns the global symbols of a member, and whether they are defined.
+fn get_global_symbols(member: &NewArchiveMember<'_>) -> Vec<(String, bool)> {
+    let file = object::File::parse((*member.buf).as_ref()).unwrap();
+    file.symbols()
+        .filter(|symbol| symbol.is_global())
+        .map(|symbol| (symbol.name().unwrap().to_string(), symbol.is_definition()))
+        .collect()
+}
+
+/// Returns the sorted names in the symbol table of the archive of `members`.
+fn get_sorted_archive_symbols(
+    members: &[NewArchiveMember<'_>],
+    binary_format: BinaryFormat,
+) -> Vec<String> {
+    let kind = match binary_format {
+        BinaryFormat::Coff => ArchiveKind::Coff,
+        BinaryFormat::MachO => ArchiveKind::Darwin,
+        _ => ArchiveKind::Gnu,
+    };
+    let mut symbols = common::get_archive_symbols(&common::write_archive(members, kind));
+    symbols.sort();
+    symbols
+}
+
+/// Tests that the symbols defined by the members, and the references to them,
+/// are prefixed, and that the archive symbol table uses the new names.
+#[test]
+fn prefix_archive_symbols() {
+    for binary_format in [BinaryFormat::Elf, BinaryFormat::MachO, BinaryFormat::Coff] {
+        let message = format!("binary_format: {binary_format:?}");
+        // Mach-O symbols have a leading underscore.
+        let mangle = |name: &str| match binary_format {
+            BinaryFormat::MachO => format!("_{name}"),
+            _ => name.to_string(),
+        };
+        // The prefix is long enough for COFF to use the string table.
+        let members = ar_archive_writer::prefix_archive_symbols(
+            create_test_members(binary_format),
+            b"vendor_v1_",
+        )
+        .unwrap();
+        assert_eq!(
+            get_global_symbols(&members[0]),
+            [
+                (format!("vendor_v1_{}", mangle("func1")), true),
+                (format!("vendor_v1_{}", mangle("func2")), false),
+                (mangle("puts"), false),
+            ],
+            "{message}"
+        );
+        assert_eq!(
+            get_global_symbols(&members[1]),
+            [(format!("vendor_v1_{}", mangle("func2")), true)],
+            "{message}"
+        );
+        assert_eq!((*members[2].buf).as_ref(), b"not an object", "{message}");
+        assert_eq!(
+            get_sorted_archive_symbols(&members, binary_format),
+            [
+                format!("vendor_v1_{}", mangle("func1")),
+                format!("vendor_v1_{}", mangle("func2")),
+            ],
+            "{message}"
+        );
+    }
+}
+
+/// Tests that only the symbols in the map are renamed, including short COFF
+/// names that are stored in the symbol.
+#[test]
+fn redefine_archive_symbols() {
+    for binary_format in [BinaryFormat::Elf, BinaryFormat::MachO, BinaryFormat::Coff] {
+        let message = format!("binary_format: {binary_format:?}");
+        let mangle = |name: &str| match binary_format {
+            BinaryFormat::MachO => format!("_{name}"),
+            _ => name.to_string(),
+        };
+        let renames = HashMap::from([
+            (
+                mangle("func2").into_bytes(),
+                mangle("renamed_func2").into_bytes(),
+            ),
+            (mangle("puts").into_bytes(), mangle("f").into_bytes()),
+        ]);
+        let members = ar_archive_writer::redefine_archive_symbols(
+            create_test_members(binary_format),
+            &renames,
+        )
+        .unwrap();
+        assert_eq!(
+            get_global_symbols(&members[0]),
+            [
+                (mangle("func1"), true),
+                (mangle("renamed_func2"), false),
+                (mangle("f"), false),
+            ],
+            "{message}"
+        );
+        assert_eq!(
+            get_global_symbols(&members[1]),
+            [(mangle("renamed_func2"), true)],
+            "{message}"
+        );
+        assert_eq!(
+            get_sorted_archive_symbols(&members, binary_format),
+            [mangle("func1"), mangle("renamed_func2")],
+            "{message}"
+        );
+    }
+}
+
+/// Tests that short import members and the references to their symbols are
+/// kept unchanged, and that bitcode members are rejected.
+#[test]
+fn short_imports_and_bitcode() {
+    let exports = [ar_archive_writer::COFFShortExport {
+        name: "ImportedFunc".to_string(),
+        ext_name: None,
+        symbol_name: None,
+        alias_target: None,
+        ordinal: 0,
+        noname: false,
+        data: false,
+        private: false,
+        constant: false,
+    }];
+    let mut import_library = Cursor::new(Vec::new());
+    ar_archive_writer::write_import_library(
+        &mut import_library,
+        "MyLibrary.dll",
+        &exports,
+        ar_archive_writer::MachineTypes::AMD64,
+        false,
+    )
+    .unwrap();
+    let import_library = import_library.into_inner();
+    let archive = ArchiveFile::parse(import_library.as_slice()).unwrap();
+    let short_import = archive
+        .members()
+        .map(|member| member.unwrap().data(import_library.as_slice()).unwrap())
+        .find(|data| object::FileKind::parse(*data) == Ok(object::FileKind::CoffImport))
+        .unwrap()
+        .to_vec();
+
+    let members = || {
+        common::create_members(&[
+            (
+                "a.o",
+                create_object(BinaryFormat::Coff, b"func1", &[b"ImportedFunc"]),
+            ),
+            ("MyLibrary.dll", short_import.clone()),
+        ])
+    };
+
+    let prefixed = ar_archive_writer::prefix_archive_symbols(members(), b"vendor_v1_").unwrap();
+    assert_eq!(
+        get_global_symbols(&prefixed[0]),
+        [
+            ("vendor_v1_func1".to_string(), true),
+            ("ImportedFunc".to_string(), false),
+        ]
+    );
+    assert_eq!((*prefixed[1].buf).as_ref(), short_import);
+
+    let renames = HashMap::from([(b"ImportedFunc".to_vec(), b"OtherFunc".to_vec())]);
+    let redefined = ar_archive_writer::redefine_archive_symbols(members(), &renames).unwrap();
+    assert_eq!(
+        get_global_symbols(&redefined[0]),
+        [
+            ("func1".to_string(), true),
+            ("OtherFunc".to_string(), false),
+        ]
+    );
+    assert_eq!((*redefined[1].buf).as_ref(), short_import);
+
+    let mut members = members();
+    members.push(NewArchiveMember::new(
+        b"BC\xc0\xde\x35\x14\x00\x00",
+        &ar_archive_writer::DEFAULT_OBJECT_READER,
+        "bitcode.o".to_string(),
+    ));
+    let err = ar_archive_writer::prefix_archive_symbols(members, b"vendor_v1_")
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+    assert_eq!(
+        err.to_string(),
+        "bitcode.o: renaming symbols is not supported for LLVM bitcode files"
+    );
+}
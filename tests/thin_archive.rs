@@ -0,0 +1,168 @@
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use std::process::Command;
+
+use ar_archive_writer::ArchiveKind;
+use pretty_assertions::assert_eq;
+
+mod common;
+
+fn write_thin_archive(archive_path: &Path, member_paths: &[&Path]) -> Vec<u8> {
+    let members = ar_archive_writer::read_thin_archive_members(
+        archive_path,
+        member_paths,
+        &ar_archive_writer::DEFAULT_OBJECT_READER,
+    )
+    .unwrap();
+    let mut output_bytes = Cursor::new(Vec::new());
+    ar_archive_writer::write_archive_to_stream(
+        &mut output_bytes,
+        &members,
+        ArchiveKind::Gnu,
+        true,
+        false,
+    )
+    .unwrap();
+    output_bytes.into_inner()
+}
+
+#[test]
+fn archive_relative_path() {
+    for (archive_path, member_path, expected) in [
+        ("/a/b/lib.a", "/a/b/c.o", "c.o"),
+        ("/a/b/lib.a", "/a/b/d/c.o", "d/c.o"),
+        ("/a/b/lib.a", "/a/d/c.o", "../d/c.o"),
+        ("/a/b/lib.a", "/c.o", "../../c.o"),
+        ("/a/./b/lib.a", "/a/x/../b/c.o", "c.o"),
+    ] {
+        assert_eq!(
+            ar_archive_writer::compute_archive_relative_path(
+                Path::new(archive_path),
+                Path::new(member_path)
+            )
+            .unwrap(),
+            expected,
+            "archive: {archive_path}, member: {member_path}"
+        );
+    }
+
+    // Relative paths are relative to the current directory.
+    let cwd = std::env::current_dir().unwrap();
+    assert_eq!(
+        ar_archive_writer::compute_archive_relative_path(
+            Path::new("out/lib.a"),
+            &cwd.join("obj/c.o")
+        )
+        .unwrap(),
+        "../obj/c.o"
+    );
+}
+
+/// Tests that nested thin archives are flattened like `llvm-ar` does, and that
+/// the member names are relative to the archive.
+#[test]
+fn flatten_nested_thin_archives() {
+    let tmpdir = common::create_tmp_dir("flatten_nested_thin_archives");
+    fs::create_dir_all(tmpdir.join("out")).unwrap();
+    fs::create_dir_all(tmpdir.join("nested/dir")).unwrap();
+    fs::write(tmpdir.join("a.o"), common::create_elf_object(&[b"func_a"])).unwrap();
+    fs::write(
+        tmpdir.join("nested/b.o"),
+        common::create_elf_object(&[b"func_b"]),
+    )
+    .unwrap();
+    fs::write(
+        tmpdir.join("nested/dir/c.o"),
+        common::create_elf_object(&[b"func_c"]),
+    )
+    .unwrap();
+
+    let ar_path = cargo_binutils::Tool::Ar.path().unwrap();
+    let run_llvm_ar = |archive_path: &str, member_paths: &[&str]| {
+        let output = Command::new(&ar_path)
+            .current_dir(&tmpdir)
+            .args(["rcs", "--format=gnu", "--thin", archive_path])
+            .args(member_paths)
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+        fs::read(tmpdir.join(archive_path)).unwrap()
+    };
+
+    let nested_archive = run_llvm_ar("nested/libnested.a", &["nested/b.o", "nested/dir/c.o"]);
+    assert_eq!(
+        write_thin_archive(
+            &tmpdir.join("nested/libnested.a"),
+            &[&tmpdir.join("nested/b.o"), &tmpdir.join("nested/dir/c.o")]
+        ),
+        nested_archive
+    );
+
+    let llvm_ar_archive = run_llvm_ar("out/lib.a", &["a.o", "nested/libnested.a"]);
+    let ar_archive_writer_archive = write_thin_archive(
+        &tmpdir.join("out/lib.a"),
+        &[&tmpdir.join("a.o"), &tmpdir.join("nested/libnested.a")],
+    );
+    assert_eq!(llvm_ar_archive, ar_archive_writer_archive);
+    assert!(String::from_utf8_lossy(&ar_archive_writer_archive).contains("../nested/dir/c.o/"));
+}
+
+#[test]
+fn thin_archive_cycle() {
+    let tmpdir = common::create_tmp_dir("thin_archive_cycle");
+    fs::write(tmpdir.join("a.o"), common::create_elf_object(&[b"func_a"])).unwrap();
+    let archive_path = tmpdir.join("x.a");
+    fs::write(
+        &archive_path,
+        write_thin_archive(&archive_path, &[&tmpdir.join("a.o")]),
+    )
+    .unwrap();
+    // Make the archive refer to itself.
+    let mut archive = fs::read(&archive_path).unwrap();
+    let name_offset = archive
+        .windows(5)
+        .position(|name| name == b"a.o/\n")
+        .unwrap();
+    archive[name_offset..name_offset + 3].copy_from_slice(b"x.a");
+    fs::write(&archive_path, archive).unwrap();
+
+    let err = ar_archive_writer::read_thin_archive_members(
+        &tmpdir.join("out.a"),
+        &[&archive_path],
+        &ar_archive_writer::DEFAULT_OBJECT_READER,
+    )
+    .err()
+    .unwrap();
+    assert_eq!(
+        err.to_string(),
+        format!("thin archive {} contains itself", archive_path.display())
+    );
+}
+
+#[test]
+fn thin_unsupported_kinds() {
+    let object = common::create_elf_object(&[b"func_a"]);
+    let members = [ar_archive_writer::NewArchiveMember::new(
+        object.as_slice(),
+        &ar_archive_writer::DEFAULT_OBJECT_READER,
+        "a.o".to_string(),
+    )];
+    for archive_kind in [
+        ArchiveKind::Bsd,
+        ArchiveKind::Darwin,
+        ArchiveKind::Darwin64,
+        ArchiveKind::AixBig,
+    ] {
+        let err = ar_archive_writer::write_archive_to_stream(
+            &mut Cursor::new(Vec::new()),
+            &members,
+            archive_kind,
+            true,
+            false,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+        assert_eq!(err.to_string(), "Only the gnu format has a thin mode");
+    }
+}
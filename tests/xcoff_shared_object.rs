@@ -0,0 +1,198 @@
+use std::io::Cursor;
+use std::process::Command;
+
+use ar_archive_writer::ArchiveKind;
+use object::{write, Architecture, BinaryFormat, Endianness};
+use pretty_assertions::assert_eq;
+
+mod common;
+
+const L_EXPORT: u8 = 0x10;
+const L_IMPORT: u8 = 0x40;
+const XTY_SD: u8 = 1;
+const XTY_ER: u8 = 0;
+
+/// Creates an XCOFF shared object with only a loader section, which has the
+/// given (name, l_smtype) symbols.
+fn create_shared_object(is_64: bool, symbols: &[(&str, u8)]) -> Vec<u8> {
+    let (header_size, section_header_size, loader_header_size) =
+        if is_64 { (24, 72, 56) } else { (20, 40, 32) };
+
+    let mut symbol_table = Vec::new();
+    let mut string_table = Vec::new();
+    for &(name, smtype) in symbols {
+        let name_offset = string_table.len() as u32 + 2;
+        let is_long_name = is_64 || name.len() > 8;
+        if is_long_name {
+            string_table.extend_from_slice(&(name.len() as u16 + 1).to_be_bytes());
+            string_table.extend_from_slice(name.as_bytes());
+            string_table.push(0);
+        }
+        if is_64 {
+            symbol_table.extend_from_slice(&0u64.to_be_bytes()); // l_value
+            symbol_table.extend_from_slice(&name_offset.to_be_bytes());
+        } else {
+            if is_long_name {
+                symbol_table.extend_from_slice(&0u32.to_be_bytes());
+                symbol_table.extend_from_slice(&name_offset.to_be_bytes());
+            } else {
+                let mut inline_name = [0; 8];
+                inline_name[..name.len()].copy_from_slice(name.as_bytes());
+                symbol_table.extend_from_slice(&inline_name);
+            }
+            symbol_table.extend_from_slice(&0u32.to_be_bytes()); // l_value
+        }
+        symbol_table.extend_from_slice(&1u16.to_be_bytes()); // l_scnum
+        symbol_table.push(smtype);
+        symbol_table.push(0); // l_smclas
+        symbol_table.extend_from_slice(&0u32.to_be_bytes()); // l_ifile
+        symbol_table.extend_from_slice(&0u32.to_be_bytes()); // l_parm
+    }
+
+    let num_syms = symbols.len() as u32;
+    let string_table_offset = (loader_header_size + symbol_table.len()) as u64;
+    let mut loader = Vec::new();
+    loader.extend_from_slice(&(if is_64 { 2u32 } else { 1 }).to_be_bytes());
+    loader.extend_from_slice(&num_syms.to_be_bytes());
+    loader.extend_from_slice(&0u32.to_be_bytes()); // l_nreloc
+    loader.extend_from_slice(&0u32.to_be_bytes()); // l_istlen
+    loader.extend_from_slice(&0u32.to_be_bytes()); // l_nimpid
+    if is_64 {
+        loader.extend_from_slice(&(string_table.len() as u32).to_be_bytes());
+        loader.extend_from_slice(&0u64.to_be_bytes()); // l_impoff
+        loader.extend_from_slice(&string_table_offset.to_be_bytes());
+        loader.extend_from_slice(&(loader_header_size as u64).to_be_bytes());
+        loader.extend_from_slice(&0u64.to_be_bytes()); // l_rldoff
+    } else {
+        loader.extend_from_slice(&0u32.to_be_bytes()); // l_impoff
+        loader.extend_from_slice(&(string_table.len() as u32).to_be_bytes());
+        loader.extend_from_slice(&(string_table_offset as u32).to_be_bytes());
+    }
+    assert_eq!(loader.len(), loader_header_size);
+    loader.extend_from_slice(&symbol_table);
+    loader.extend_from_slice(&string_table);
+
+    let loader_offset = (header_size + section_header_size) as u64;
+    let mut data = Vec::new();
+    if is_64 {
+        data.extend_from_slice(&object::xcoff::MAGIC_64.to_be_bytes());
+        data.extend_from_slice(&1u16.to_be_bytes()); // f_nscns
+        data.extend_from_slice(&0u32.to_be_bytes()); // f_timdat
+        data.extend_from_slice(&0u64.to_be_bytes()); // f_symptr
+        data.extend_from_slice(&0u16.to_be_bytes()); // f_opthdr
+        data.extend_from_slice(&object::xcoff::F_SHROBJ.to_be_bytes());
+        data.extend_from_slice(&0u32.to_be_bytes()); // f_nsyms
+        data.extend_from_slice(b".loader\0");
+        data.extend_from_slice(&0u64.to_be_bytes()); // s_paddr
+        data.extend_from_slice(&0u64.to_be_bytes()); // s_vaddr
+        data.extend_from_slice(&(loader.len() as u64).to_be_bytes());
+        data.extend_from_slice(&loader_offset.to_be_bytes());
+        data.extend_from_slice(&[0; 24]); // s_relptr, s_lnnoptr, s_nreloc, s_nlnno
+        data.extend_from_slice(&u32::from(object::xcoff::STYP_LOADER).to_be_bytes());
+        data.extend_from_slice(&0u32.to_be_bytes()); // s_reserve
+    } else {
+        data.extend_from_slice(&object::xcoff::MAGIC_32.to_be_bytes());
+        data.extend_from_slice(&1u16.to_be_bytes()); // f_nscns
+        data.extend_from_slice(&0u32.to_be_bytes()); // f_timdat
+        data.extend_from_slice(&0u32.to_be_bytes()); // f_symptr
+        data.extend_from_slice(&0u32.to_be_bytes()); // f_nsyms
+        data.extend_from_slice(&0u16.to_be_bytes()); // f_opthdr
+        data.extend_from_slice(&object::xcoff::F_SHROBJ.to_be_bytes());
+        data.extend_from_slice(b".loader\0");
+        data.extend_from_slice(&0u32.to_be_bytes()); // s_paddr
+        data.extend_from_slice(&0u32.to_be_bytes()); // s_vaddr
+        data.extend_from_slice(&(loader.len() as u32).to_be_bytes());
+        data.extend_from_slice(&(loader_offset as u32).to_be_bytes());
+        data.extend_from_slice(&[0; 12]); // s_relptr, s_lnnoptr, s_nreloc, s_nlnno
+        data.extend_from_slice(&u32::from(object::xcoff::STYP_LOADER).to_be_bytes());
+    }
+    assert_eq!(data.len() as u64, loader_offset);
+    data.extend_from_slice(&loader);
+    data
+}
+
+fn get_symbols(buf: &[u8]) -> (bool, Vec<String>) {
+    let mut symbols = Vec::new();
+    let is_symbolic = (ar_archive_writer::DEFAULT_OBJECT_READER.get_symbols)(buf, &mut |name| {
+        symbols.push(String::from_utf8(name.to_vec()).unwrap());
+        Ok(())
+    })
+    .unwrap();
+    (is_symbolic, symbols)
+}
+
+/// Tests that the symbols of XCOFF shared objects are the symbols exported
+/// from their loader section.
+#[test]
+fn loader_section_symbols() {
+    let symbols = [
+        ("exported", XTY_SD | L_EXPORT),
+        ("exported_long_name", XTY_SD | L_EXPORT),
+        ("local", XTY_SD),
+        ("imported", XTY_ER | L_IMPORT),
+    ];
+    for is_64 in [false, true] {
+        let shared_object = create_shared_object(is_64, &symbols);
+        assert_eq!(
+            get_symbols(&shared_object),
+            (
+                true,
+                vec!["exported".to_string(), "exported_long_name".to_string()]
+            ),
+            "is_64: {is_64}"
+        );
+    }
+
+    // Objects that aren't shared use their symbol table.
+    let mut object = write::Object::new(
+        BinaryFormat::Xcoff,
+        Architecture::PowerPc64,
+        Endianness::Big,
+    );
+    common::add_file_with_functions_to_object(&mut object, b"file.c", &[b"func1"]);
+    assert_eq!(
+        get_symbols(&object.write().unwrap()),
+        (true, vec!["func1".to_string()])
+    );
+
+    // A truncated loader section is an error, rather than missing symbols.
+    let shared_object = create_shared_object(false, &symbols);
+    let err = (ar_archive_writer::DEFAULT_OBJECT_READER.get_symbols)(
+        &shared_object[..shared_object.len() - 8],
+        &mut |_| Ok(()),
+    )
+    .unwrap_err();
+    assert_eq!(err.to_string(), "Invalid XCOFF loader section");
+}
+
+/// Tests that the global symbol tables of a big archive contain the exported
+/// symbols of shared objects.
+#[test]
+fn big_archive_with_shared_objects() {
+    let tmpdir = common::create_tmp_dir("big_archive_with_shared_objects");
+    let shr = create_shared_object(false, &[("func32", XTY_SD | L_EXPORT)]);
+    let shr_64 = create_shared_object(true, &[("func64", XTY_SD | L_EXPORT)]);
+    let members = common::create_members(&[("shr.o", shr), ("shr_64.o", shr_64)]);
+    let mut archive = Cursor::new(Vec::new());
+    ar_archive_writer::write_archive_to_stream(
+        &mut archive,
+        &members,
+        ArchiveKind::AixBig,
+        false,
+        false,
+    )
+    .unwrap();
+
+    let archive_path = tmpdir.join("libc.a");
+    std::fs::write(&archive_path, archive.into_inner()).unwrap();
+    let output = Command::new(cargo_binutils::Tool::Nm.path().unwrap())
+        .args(["--print-armap", "-X32_64"])
+        .arg(&archive_path)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.starts_with("Archive map\nfunc32 in shr.o\nfunc64 in shr_64.o\n"),
+        "{stdout}"
+    );
+}